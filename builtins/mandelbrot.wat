@@ -0,0 +1,62 @@
+;; the mandelbrot set, with its escape time colors cycling over time
+(module
+ (memory $mem 4)
+ (global $time (mut i32) (i32.const 0))
+
+ (func $tick
+    (local $x i32)
+    (local $y i32)
+    (local $cr f64)
+    (local $ci f64)
+    (local $zr f64)
+    (local $zi f64)
+    (local $t f64)
+    (local $n i32)
+    (local $shade i32)
+    (local.set $y (i32.const 0))
+    (loop $rows
+        (local.set $x (i32.const 0))
+        (loop $columns
+            ;; x in -2.25..0.75, y in -1.5..1.5
+            (local.set $cr (f64.sub (f64.mul (f64.convert_i32_u (local.get $x)) (f64.const 0.01171875))
+                                    (f64.const 2.25)))
+            (local.set $ci (f64.sub (f64.mul (f64.convert_i32_u (local.get $y)) (f64.const 0.01171875))
+                                    (f64.const 1.5)))
+            (local.set $zr (f64.const 0))
+            (local.set $zi (f64.const 0))
+            (local.set $n (i32.const 0))
+            (block $escaped
+                (loop $iterate
+                    (br_if $escaped (f64.gt (f64.add (f64.mul (local.get $zr) (local.get $zr))
+                                                     (f64.mul (local.get $zi) (local.get $zi)))
+                                            (f64.const 4)))
+                    (local.set $t (f64.add (f64.sub (f64.mul (local.get $zr) (local.get $zr))
+                                                    (f64.mul (local.get $zi) (local.get $zi)))
+                                           (local.get $cr)))
+                    (local.set $zi (f64.add (f64.mul (f64.mul (f64.const 2) (local.get $zr)) (local.get $zi))
+                                            (local.get $ci)))
+                    (local.set $zr (local.get $t))
+                    (local.set $n (i32.add (local.get $n) (i32.const 1)))
+                    (br_if $iterate (i32.lt_u (local.get $n) (i32.const 64)))))
+            (local.set $shade (i32.and (i32.add (i32.mul (local.get $n) (i32.const 8))
+                                                (i32.mul (global.get $time) (i32.const 2)))
+                                       (i32.const 255)))
+            (i32.store
+                (i32.shl (i32.add (i32.shl (local.get $y) (i32.const 8)) (local.get $x)) (i32.const 2))
+                (if (result i32) (i32.eq (local.get $n) (i32.const 64))
+                    ;; inside the set
+                    (then (i32.const 0xff000000))
+                    (else (i32.or
+                        (i32.or (local.get $shade)
+                                (i32.shl (i32.xor (local.get $shade) (i32.const 0x80)) (i32.const 8)))
+                        (i32.or (i32.shl (i32.sub (i32.const 255) (local.get $shade)) (i32.const 16))
+                                (i32.const 0xff000000))))))
+            (local.set $x (i32.add (local.get $x) (i32.const 1)))
+            (br_if $columns (i32.lt_u (local.get $x) (i32.const 256))))
+        (local.set $y (i32.add (local.get $y) (i32.const 1)))
+        (br_if $rows (i32.lt_u (local.get $y) (i32.const 256))))
+    (global.set $time (i32.add (global.get $time) (i32.const 1))))
+
+ (export "tick" (func $tick))
+ (export "image_buffer" (memory $mem))
+)
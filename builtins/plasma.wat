@@ -0,0 +1,49 @@
+;; overlapping waves scrolling across each other, built from triangle waves since wasm has no sin
+(module
+ (memory $mem 4)
+ (global $time (mut i32) (i32.const 0))
+
+ ;; a triangle wave over 0..=255 with a period of 512
+ (func $wave (param $v i32) (result i32)
+    (local $p i32)
+    (local.set $p (i32.and (local.get $v) (i32.const 511)))
+    (if (result i32) (i32.lt_u (local.get $p) (i32.const 256))
+        (then (local.get $p))
+        (else (i32.sub (i32.const 511) (local.get $p)))))
+
+ (func $tick
+    (local $x i32)
+    (local $y i32)
+    (local $a i32)
+    (local $b i32)
+    (local $c i32)
+    (local.set $y (i32.const 0))
+    (loop $rows
+        (local.set $x (i32.const 0))
+        (loop $columns
+            (local.set $a (call $wave (i32.add (i32.mul (local.get $x) (i32.const 3))
+                                               (i32.mul (global.get $time) (i32.const 4)))))
+            (local.set $b (call $wave (i32.sub (i32.mul (local.get $y) (i32.const 2))
+                                               (i32.mul (global.get $time) (i32.const 3)))))
+            (local.set $c (call $wave (i32.add (i32.add (local.get $x) (local.get $y))
+                                               (i32.mul (global.get $time) (i32.const 5)))))
+            (i32.store
+                (i32.shl (i32.add (i32.shl (local.get $y) (i32.const 8)) (local.get $x)) (i32.const 2))
+                (i32.or
+                    (i32.or
+                        (i32.shr_u (i32.add (local.get $a) (local.get $b)) (i32.const 1))
+                        (i32.shl (i32.shr_u (i32.add (local.get $b) (local.get $c)) (i32.const 1))
+                                 (i32.const 8)))
+                    (i32.or
+                        (i32.shl (i32.shr_u (i32.add (local.get $c) (local.get $a)) (i32.const 1))
+                                 (i32.const 16))
+                        (i32.const 0xff000000))))
+            (local.set $x (i32.add (local.get $x) (i32.const 1)))
+            (br_if $columns (i32.lt_u (local.get $x) (i32.const 256))))
+        (local.set $y (i32.add (local.get $y) (i32.const 1)))
+        (br_if $rows (i32.lt_u (local.get $y) (i32.const 256))))
+    (global.set $time (i32.add (global.get $time) (i32.const 1))))
+
+ (export "tick" (func $tick))
+ (export "image_buffer" (memory $mem))
+)
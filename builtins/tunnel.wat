@@ -0,0 +1,50 @@
+;; flying down a tunnel of checkered rings, darker towards the far end
+(module
+ (memory $mem 4)
+ (global $time (mut i32) (i32.const 0))
+
+ (func $tick
+    (local $x i32)
+    (local $y i32)
+    (local $dx f32)
+    (local $dy f32)
+    (local $distance f32)
+    (local $depth i32)
+    (local $side i32)
+    (local $shade i32)
+    (local.set $y (i32.const 0))
+    (loop $rows
+        (local.set $x (i32.const 0))
+        (loop $columns
+            (local.set $dx (f32.convert_i32_s (i32.sub (local.get $x) (i32.const 128))))
+            (local.set $dy (f32.convert_i32_s (i32.sub (local.get $y) (i32.const 128))))
+            (local.set $distance (f32.sqrt (f32.add (f32.mul (local.get $dx) (local.get $dx))
+                                                    (f32.mul (local.get $dy) (local.get $dy)))))
+            ;; rings get closer together towards the center, and move towards the viewer
+            (local.set $depth (i32.add (i32.trunc_f32_s (f32.div (f32.const 2048)
+                                                                 (f32.add (local.get $distance) (f32.const 1))))
+                                       (i32.mul (global.get $time) (i32.const 2))))
+            ;; which half of the tunnel wall, left or right, top or bottom
+            (local.set $side (i32.xor (f32.lt (local.get $dx) (local.get $dy))
+                                      (f32.lt (local.get $dx) (f32.neg (local.get $dy)))))
+            (local.set $shade (i32.trunc_f32_s (f32.min (f32.mul (local.get $distance) (f32.const 1.4))
+                                                        (f32.const 255))))
+            (if (i32.and (i32.xor (i32.shr_u (local.get $depth) (i32.const 4)) (local.get $side))
+                         (i32.const 1))
+                (then (local.set $shade (i32.shr_u (local.get $shade) (i32.const 1)))))
+            (i32.store
+                (i32.shl (i32.add (i32.shl (local.get $y) (i32.const 8)) (local.get $x)) (i32.const 2))
+                (i32.or
+                    (i32.or (i32.shr_u (local.get $shade) (i32.const 2))
+                            (i32.shl (i32.shr_u (local.get $shade) (i32.const 1)) (i32.const 8)))
+                    (i32.or (i32.shl (local.get $shade) (i32.const 16))
+                            (i32.const 0xff000000))))
+            (local.set $x (i32.add (local.get $x) (i32.const 1)))
+            (br_if $columns (i32.lt_u (local.get $x) (i32.const 256))))
+        (local.set $y (i32.add (local.get $y) (i32.const 1)))
+        (br_if $rows (i32.lt_u (local.get $y) (i32.const 256))))
+    (global.set $time (i32.add (global.get $time) (i32.const 1))))
+
+ (export "tick" (func $tick))
+ (export "image_buffer" (memory $mem))
+)
@@ -0,0 +1,100 @@
+use crate::format::PixelFormat;
+
+/// progressively averages successive frames to reduce noise in stochastic renderers.
+///
+/// the average over the first `limit` frames is exact; after that each new frame is weighted by
+/// `1 / limit` so older frames fade out rather than being kept around individually.
+#[derive(Debug)]
+pub struct Accumulator {
+    limit: u32,
+    frames: u32,
+    samples: Vec<f32>,
+}
+
+impl Accumulator {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit: limit.max(1),
+            frames: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// discards the accumulated history and averages over up to `limit` frames from now on
+    pub fn set_limit(&mut self, limit: u32) {
+        self.limit = limit.max(1);
+        self.reset();
+    }
+
+    /// discards the accumulated history, e.g. because the module's camera or inputs changed
+    pub fn reset(&mut self) {
+        self.frames = 0;
+    }
+
+    /// folds `frame` into the running average and overwrites it with the result
+    pub fn accumulate(&mut self, frame: &mut [u8], format: PixelFormat) {
+        let len = match format {
+            PixelFormat::Rgba8 | PixelFormat::Alpha8 | PixelFormat::Yuv420p => frame.len(),
+            PixelFormat::Rgba16 => frame.len() / 2,
+            // averaging palette indices doesn't average their colors
+            PixelFormat::Indexed8 => return,
+        };
+        // a change in frame size invalidates the history
+        if self.samples.len() != len {
+            self.samples = vec![0.0; len];
+            self.frames = 0;
+        }
+
+        self.frames = (self.frames + 1).min(self.limit);
+        let weight = 1.0 / self.frames as f32;
+
+        match format {
+            PixelFormat::Rgba8 | PixelFormat::Alpha8 | PixelFormat::Yuv420p => {
+                for (sample, byte) in self.samples.iter_mut().zip(frame.iter_mut()) {
+                    *sample += (*byte as f32 - *sample) * weight;
+                    *byte = sample.round() as u8;
+                }
+            }
+            PixelFormat::Indexed8 => {}
+            PixelFormat::Rgba16 => {
+                for (sample, bytes) in self.samples.iter_mut().zip(frame.chunks_exact_mut(2)) {
+                    let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+                    *sample += (value as f32 - *sample) * weight;
+                    bytes.copy_from_slice(&(sample.round() as u16).to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_two_frames() {
+        let mut accumulator = Accumulator::new(4);
+        let mut first = [0, 100, 200, 255];
+        let mut second = [50, 100, 0, 255];
+        accumulator.accumulate(&mut first, PixelFormat::Rgba8);
+        assert_eq!(first, [0, 100, 200, 255]);
+        accumulator.accumulate(&mut second, PixelFormat::Rgba8);
+        assert_eq!(second, [25, 100, 100, 255]);
+
+        // starting over forgets the first two
+        accumulator.reset();
+        let mut third = [10, 20, 30, 40];
+        accumulator.accumulate(&mut third, PixelFormat::Rgba8);
+        assert_eq!(third, [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn averages_two_rgba16_frames() {
+        let mut accumulator = Accumulator::new(2);
+        let mut first = 1000u16.to_le_bytes();
+        let mut second = 3000u16.to_le_bytes();
+        accumulator.accumulate(&mut first, PixelFormat::Rgba16);
+        accumulator.accumulate(&mut second, PixelFormat::Rgba16);
+        assert_eq!(u16::from_le_bytes(second), 2000);
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+// ticks taking more than this fraction of the interval between them count as under pressure
+const PRESSURE: f64 = 0.8;
+// and ticks taking less than this fraction as having headroom
+const HEADROOM: f64 = 0.4;
+// how many ticks in a row it takes to change the rate, so a single slow frame doesn't
+const STREAK: u32 = 8;
+// how much the rate is lowered by under pressure. it's raised back more gently so it doesn't
+// bounce between two rates.
+const LOWER: f64 = 0.8;
+const RAISE: f64 = 1.1;
+
+/// the frame rates `--adaptive-fps` keeps to, parsed from `MIN:MAX`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FpsRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// parses `MIN:MAX`, e.g. `20:60`
+pub fn parse_fps_range(s: &str) -> Result<FpsRange, String> {
+    let fps = |value: &str| {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|fps| fps.is_finite() && *fps > 0.0)
+            .ok_or_else(|| format!("invalid frame rate '{}' in '{}'", value, s))
+    };
+    let (min, max) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected MIN:MAX, got '{}'", s))?;
+    let (min, max) = (fps(min)?, fps(max)?);
+    if min > max {
+        return Err(format!("the minimum is above the maximum in '{}'", s));
+    }
+    Ok(FpsRange { min, max })
+}
+
+/// `--adaptive-fps`: lowers the tick rate while ticks take up most of the time between them,
+/// e.g. because the CPU is throttling, and raises it back once they don't
+#[derive(Debug)]
+pub struct AdaptiveFps {
+    range: FpsRange,
+    fps: f64,
+    // consecutive ticks under pressure, or with headroom
+    slow: u32,
+    fast: u32,
+}
+
+impl AdaptiveFps {
+    /// starts at `fps`, clamped to `range`
+    pub fn new(range: FpsRange, fps: f64) -> Self {
+        Self {
+            range,
+            fps: fps.clamp(range.min, range.max),
+            slow: 0,
+            fast: 0,
+        }
+    }
+
+    /// the interval between ticks at the current rate
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps)
+    }
+
+    /// moves to `fps`, e.g. as set by `setfps`, clamped to the range
+    pub fn set_fps(&mut self, fps: f64) {
+        self.fps = fps.clamp(self.range.min, self.range.max);
+        self.slow = 0;
+        self.fast = 0;
+    }
+
+    /// records that a tick took `frame_time`, returning the new rate if it changed
+    pub fn record(&mut self, frame_time: Duration) -> Option<f64> {
+        let load = frame_time.as_secs_f64() * self.fps;
+        if load > PRESSURE {
+            self.slow += 1;
+            self.fast = 0;
+        } else if load < HEADROOM {
+            self.fast += 1;
+            self.slow = 0;
+        } else {
+            self.slow = 0;
+            self.fast = 0;
+        }
+
+        let fps = if self.slow >= STREAK {
+            self.fps * LOWER
+        } else if self.fast >= STREAK {
+            self.fps * RAISE
+        } else {
+            return None;
+        };
+        let previous = self.fps;
+        self.set_fps(fps);
+        (self.fps != previous).then_some(self.fps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_frames_lower_the_rate_and_fast_ones_raise_it() {
+        let range = parse_fps_range("20:60").unwrap();
+        let mut adaptive = AdaptiveFps::new(range, 100.0);
+        assert_eq!(adaptive.interval(), Duration::from_secs_f64(1.0 / 60.0));
+
+        // ticks taking 15ms of a 16.7ms budget, one too few to count as a streak
+        let slow = Duration::from_millis(15);
+        for _ in 1..STREAK {
+            assert_eq!(adaptive.record(slow), None);
+        }
+        assert_eq!(adaptive.record(slow), Some(48.0));
+        // which leaves them enough time at 48 fps
+        for _ in 0..STREAK * 2 {
+            assert_eq!(adaptive.record(slow), None);
+        }
+
+        // a tick neither slow nor fast breaks a streak
+        let slower = Duration::from_millis(18);
+        for _ in 1..STREAK {
+            assert_eq!(adaptive.record(slower), None);
+        }
+        assert_eq!(adaptive.record(Duration::from_millis(10)), None);
+        assert_eq!(adaptive.record(slower), None);
+
+        // it doesn't go below the minimum however slow ticks get
+        let stalled = Duration::from_millis(500);
+        for _ in 0..STREAK * 10 {
+            adaptive.record(stalled);
+        }
+        assert_eq!(adaptive.interval(), Duration::from_secs_f64(1.0 / 20.0));
+
+        // and comes back up to the maximum once there's headroom
+        for _ in 0..STREAK * 20 {
+            adaptive.record(Duration::from_millis(1));
+        }
+        assert_eq!(adaptive.interval(), Duration::from_secs_f64(1.0 / 60.0));
+    }
+
+    #[test]
+    fn ranges_need_a_positive_minimum_below_the_maximum() {
+        assert_eq!(
+            parse_fps_range("12.5:30"),
+            Ok(FpsRange {
+                min: 12.5,
+                max: 30.0
+            })
+        );
+        for invalid in ["30", "0:30", "-1:30", "30:12", "inf:inf", "a:30"] {
+            assert!(parse_fps_range(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+/// pins the calling thread to the `index`th CPU core the OS reports, for timings that aren't
+/// perturbed by the scheduler migrating the thread
+pub fn pin_current_thread(index: usize) -> Result<(), String> {
+    let cores = core_affinity::get_core_ids().ok_or("couldn't list the CPU cores")?;
+    let core = cores
+        .get(index)
+        .ok_or_else(|| format!("there's no core {}, only {}", index, cores.len()))?;
+    if !core_affinity::set_for_current(*core) {
+        return Err(format!("setting the affinity to core {} failed", index));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads_are_pinned_to_the_chosen_core() {
+        // on a thread of its own since pinning can't be undone
+        std::thread::spawn(|| {
+            let first = core_affinity::get_core_ids().unwrap()[0];
+            pin_current_thread(0).unwrap();
+            #[cfg(target_os = "linux")]
+            {
+                let status = std::fs::read_to_string("/proc/thread-self/status").unwrap();
+                let allowed = status
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+                    .unwrap();
+                assert_eq!(allowed.trim(), first.id.to_string());
+            }
+            assert!(pin_current_thread(usize::MAX).is_err());
+        })
+        .join()
+        .unwrap();
+    }
+}
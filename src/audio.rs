@@ -0,0 +1,223 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rodio::{Decoder, OutputStream, Sink, Source};
+
+use crate::error::RunnerError;
+use crate::spectrum::{self, Spectrum};
+
+/// plays an audio file alongside the module. the output stream can't leave the thread that opened
+/// it so the player stays on the main thread and the runner only gets an `AudioClock`.
+pub struct AudioPlayer {
+    // playback stops when the stream is dropped
+    _stream: OutputStream,
+    sink: Arc<Sink>,
+    track: Track,
+}
+
+/// the whole file decoded up front, shared between playback and spectrum analysis
+#[derive(Clone)]
+struct Track {
+    // interleaved
+    samples: Arc<[f32]>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl AudioPlayer {
+    /// queues `path` on the default output device, paused until the runner starts ticking
+    pub fn open(path: &Path) -> Result<Self, RunnerError> {
+        let audio_error =
+            |e: &dyn std::fmt::Display| RunnerError::Audio(format!("{}: {}", path.display(), e));
+
+        let decoder =
+            Decoder::new(BufReader::new(File::open(path)?)).map_err(|e| audio_error(&e))?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        let track = Track {
+            samples: decoder.convert_samples::<f32>().collect(),
+            channels,
+            sample_rate,
+        };
+
+        let (stream, handle) = OutputStream::try_default().map_err(|e| audio_error(&e))?;
+        let sink = Sink::try_new(&handle).map_err(|e| audio_error(&e))?;
+        sink.pause();
+        sink.append(TrackSource {
+            track: track.clone(),
+            next: 0,
+        });
+
+        Ok(Self {
+            _stream: stream,
+            sink: Arc::new(sink),
+            track,
+        })
+    }
+
+    pub fn clock(&self) -> AudioClock {
+        AudioClock {
+            sink: self.sink.clone(),
+            track: self.track.clone(),
+        }
+    }
+}
+
+/// a handle on the player's playback position that can be shared with the runner thread
+#[derive(Clone)]
+pub struct AudioClock {
+    sink: Arc<Sink>,
+    track: Track,
+}
+
+impl AudioClock {
+    pub fn start(&self) {
+        self.sink.play();
+    }
+
+    /// how far into the file playback has got; stops advancing once the file has finished
+    pub fn position(&self) -> Duration {
+        self.sink.get_pos()
+    }
+
+    /// which frame is due `position` into the track when it's shown at `fps` frames per second
+    pub fn frame_at(position: Duration, fps: f64) -> u64 {
+        (position.as_secs_f64() * fps) as u64
+    }
+
+    /// the spectrum of the `spectrum::WINDOW` samples, mixed down to mono, leading up to the
+    /// current playback position
+    pub fn spectrum(&self) -> Spectrum {
+        let track = &self.track;
+        let channels = track.channels.max(1) as usize;
+        let frame = (self.position().as_secs_f64() * track.sample_rate as f64) as usize;
+        let start = frame.saturating_sub(spectrum::WINDOW);
+
+        // silence past either end of the file
+        let mut window = [0.0; spectrum::WINDOW];
+        for (i, sample) in window.iter_mut().enumerate() {
+            let offset = (start + i) * channels;
+            if let Some(frame) = track.samples.get(offset..offset + channels) {
+                *sample = frame.iter().sum::<f32>() / channels as f32;
+            }
+        }
+        Spectrum::analyze(&window)
+    }
+}
+
+// plays a `Track` without copying its samples
+struct TrackSource {
+    track: Track,
+    next: usize,
+}
+
+impl Iterator for TrackSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.track.samples.get(self.next).copied();
+        self.next += 1;
+        sample
+    }
+}
+
+impl Source for TrackSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.track.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.track.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let frames = self.track.samples.len() / self.track.channels.max(1) as usize;
+        Some(Duration::from_secs_f64(
+            frames as f64 / self.track.sample_rate as f64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::test_util::*;
+    use crate::WasmDemoRunner;
+
+    // a clock on a second of stereo silence at 8 kHz, paused, and the device it's played on by
+    // pulling samples out of it like an output device would
+    fn idle_clock() -> (AudioClock, impl Iterator<Item = f32>) {
+        let track = Track {
+            samples: vec![0.0; 16_000].into(),
+            channels: 2,
+            sample_rate: 8_000,
+        };
+        let (sink, device) = Sink::new_idle();
+        sink.pause();
+        sink.append(TrackSource {
+            track: track.clone(),
+            next: 0,
+        });
+        let clock = AudioClock {
+            sink: Arc::new(sink),
+            track,
+        };
+        (clock, device)
+    }
+
+    #[test]
+    fn position_follows_playback() {
+        let (clock, mut device) = idle_clock();
+
+        // nothing plays before the runner starts ticking
+        device.by_ref().take(8_000).for_each(drop);
+        assert_eq!(clock.position(), Duration::ZERO);
+
+        clock.start();
+        let mut played = Duration::ZERO;
+        // a tenth of a second at a time, in step with a device playing it
+        for _ in 0..5 {
+            device.by_ref().take(1_600).for_each(drop);
+            played += Duration::from_millis(100);
+            // the sink only updates its position every 5 ms of playback
+            let position = clock.position();
+            assert!(
+                played - position <= Duration::from_millis(5),
+                "{:?} of {:?}",
+                position,
+                played
+            );
+        }
+        assert_eq!(AudioClock::frame_at(clock.position(), 60.0), 29);
+    }
+
+    #[test]
+    fn ticks_follow_the_audio_position() {
+        let mut config = config(EMPTY_MODULE, &[]);
+        config.frame_limit_fps_from_audio = true;
+        let mut runner = WasmDemoRunner::new(&config).unwrap();
+        let (clock, mut device) = idle_clock();
+        clock.start();
+        runner.host_env.as_mut(&mut runner.wasm_store).audio = Some(clock);
+        let (_commands_sender, commands) = mpsc::channel();
+
+        // 10 ms frames, with playback moved on by this many milliseconds before each wait: a
+        // tick on time, then one so slow the frames in between are skipped
+        let mut played = 0;
+        for (ms, frame) in [(25, 2), (10, 3), (40, 7)] {
+            // 16 stereo samples a millisecond
+            device.by_ref().take(ms * 16).for_each(drop);
+            played += ms;
+            assert!(runner.wait(&commands).unwrap());
+            assert_eq!(runner.audio_frame, Some(frame), "after {} ms", played);
+        }
+    }
+}
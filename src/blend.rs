@@ -0,0 +1,59 @@
+/// straight (non premultiplied) alpha `over` compositing of one 8 bit RGBA pixel onto another,
+/// with the top pixel's alpha further scaled by `alpha`, which is clamped to [0, 1]
+pub fn over(top: [u8; 4], bottom: [u8; 4], alpha: f32) -> [u8; 4] {
+    let top_alpha = top[3] as f32 / 255.0 * alpha.clamp(0.0, 1.0);
+    let bottom_alpha = bottom[3] as f32 / 255.0 * (1.0 - top_alpha);
+    let out_alpha = top_alpha + bottom_alpha;
+    if out_alpha == 0.0 {
+        return [0; 4];
+    }
+
+    let mut out = [0; 4];
+    for c in 0..3 {
+        let color = (top[c] as f32 * top_alpha + bottom[c] as f32 * bottom_alpha) / out_alpha;
+        out[c] = color.round() as u8;
+    }
+    out[3] = (out_alpha * 255.0).round() as u8;
+    out
+}
+
+/// converts the premultiplied 8 bit RGBA image `rgba` to straight alpha in place. fully
+/// transparent pixels come out black.
+pub fn unpremultiply(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            pixel[..3].fill(0);
+            continue;
+        }
+        for channel in &mut pixel[..3] {
+            // rounded, and clamped for colors brighter than their alpha allows
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// makes every pixel of the 8 bit RGBA image `rgba` whose color is `key` fully transparent,
+/// regardless of its alpha
+pub fn apply_color_key(rgba: &mut [u8], key: [u8; 3]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        if pixel[..3] == key {
+            pixel[3] = 0;
+        }
+    }
+}
+
+/// composites every pixel of the 8 bit RGBA image `top` over the one at the same position in
+/// `bottom` into `out`. all three should be the same size; extra pixels in any of them are left
+/// alone.
+pub fn blend_over(top: &[u8], bottom: &[u8], alpha: f32, out: &mut [u8]) {
+    for ((top, bottom), out) in top
+        .chunks_exact(4)
+        .zip(bottom.chunks_exact(4))
+        .zip(out.chunks_exact_mut(4))
+    {
+        let top = [top[0], top[1], top[2], top[3]];
+        let bottom = [bottom[0], bottom[1], bottom[2], bottom[3]];
+        out.copy_from_slice(&over(top, bottom, alpha));
+    }
+}
@@ -0,0 +1,73 @@
+/// a module embedded in the binary, so the renderer can be tried without writing one
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static [u8],
+    /// checksum of its first frame at the default 256x256 in rgba8, as `--checksum-interval 1`
+    /// prints it
+    pub golden: u64,
+}
+
+/// every builtin module, selected with `--builtin NAME`
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "plasma",
+        description: "overlapping waves scrolling across each other",
+        source: include_bytes!("../builtins/plasma.wat"),
+        golden: 0x8552_6369_614f_65d3,
+    },
+    Builtin {
+        name: "mandelbrot",
+        description: "the mandelbrot set with cycling colors",
+        source: include_bytes!("../builtins/mandelbrot.wat"),
+        golden: 0x29d6_7f0a_b503_b5bd,
+    },
+    Builtin {
+        name: "tunnel",
+        description: "flying down a tunnel of checkered rings",
+        source: include_bytes!("../builtins/tunnel.wat"),
+        golden: 0x4664_2af6_b11f_5516,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|builtin| builtin.name)
+}
+
+/// `--list-builtins`
+pub fn list() {
+    for builtin in BUILTINS {
+        println!(
+            "{:<12} {:016x}  {}",
+            builtin.name, builtin.golden, builtin.description
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::WasmDemoRunner;
+
+    #[test]
+    fn builtins_match_their_golden_checksums() {
+        for builtin in BUILTINS {
+            let config = Config::try_parse_from(["wasm-renderer", "--builtin", builtin.name]);
+            let mut runner = WasmDemoRunner::new(&config.unwrap()).unwrap();
+            let checksum = runner.tick_once().unwrap().checksum();
+            assert_eq!(
+                checksum, builtin.golden,
+                "{} rendered {:016x}",
+                builtin.name, checksum
+            );
+        }
+    }
+}
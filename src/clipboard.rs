@@ -0,0 +1,44 @@
+use std::borrow::Cow;
+
+use arboard::{Clipboard, ImageData};
+
+/// wraps a `width` x `height` 8 bit RGBA image in the form the clipboard accepts
+pub fn image(rgba: &[u8], width: usize, height: usize) -> ImageData<'_> {
+    ImageData {
+        width,
+        height,
+        bytes: Cow::Borrowed(rgba),
+    }
+}
+
+/// puts `image` on the system clipboard. `clipboard` is opened on first use and should be kept
+/// around afterwards since on some platforms the image is only available while it's open.
+pub fn copy(clipboard: &mut Option<Clipboard>, image: ImageData) -> Result<(), arboard::Error> {
+    let clipboard = match clipboard {
+        Some(clipboard) => clipboard,
+        None => clipboard.insert(Clipboard::new()?),
+    };
+    clipboard.set_image(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::Dither;
+    use crate::format::PixelFormat;
+
+    #[test]
+    fn frames_become_rgba8_clipboard_images() {
+        // a 2x1 rgba16 frame, converted the way the frame view does before copying it
+        let frame: Vec<u8> = [0xffffu16, 0x8080, 0, 0xffff, 0, 0, 0xffff, 0x8080]
+            .iter()
+            .flat_map(|channel| channel.to_le_bytes())
+            .collect();
+        let mut rgba = Vec::new();
+        PixelFormat::Rgba16.to_rgba8(&frame, 2, Dither::None, &[], &mut rgba);
+
+        let image = image(&rgba, 2, 1);
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(&image.bytes[..], [0xff, 0x80, 0, 0xff, 0, 0, 0xff, 0x80]);
+    }
+}
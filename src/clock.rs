@@ -0,0 +1,133 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// where the run loop reads the time from and how it waits between ticks
+pub trait Clock: Send {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+/// the system clock
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// a clock that only moves when it's slept on or advanced by hand, so pacing can be stepped
+/// through deterministically without real sleeps. clones share the same time.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    // returns straight away, as if the time had passed
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// the largest pulse read; anything past it in a datagram is ignored
+const MAX_PULSE_LEN: usize = 64;
+
+/// ticks driven by an external controller instead of a timer, for `--external-clock`. every UDP
+/// datagram received on the socket is one pulse, whatever it contains, and each pulse lets
+/// exactly one tick through. pulses queue up while a tick runs rather than collapsing like
+/// display refreshes do.
+pub struct ExternalClock {
+    pulses: Receiver<()>,
+}
+
+/// what waiting for a pulse came to
+pub enum Pulse {
+    Received,
+    TimedOut,
+    /// the socket failed, so no more pulses will arrive
+    Stopped,
+}
+
+impl ExternalClock {
+    pub fn bind(address: SocketAddr) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        tracing::info!("waiting for clock pulses on {}", socket.local_addr()?);
+
+        let (sender, pulses) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0; MAX_PULSE_LEN];
+            loop {
+                if let Err(e) = socket.recv_from(&mut buf) {
+                    eprintln!("external clock stopped: {}", e);
+                    return;
+                }
+                if sender.send(()).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Self { pulses })
+    }
+
+    pub fn wait(&self, timeout: Duration) -> Pulse {
+        match self.pulses.recv_timeout(timeout) {
+            Ok(()) => Pulse::Received,
+            Err(RecvTimeoutError::Timeout) => Pulse::TimedOut,
+            Err(RecvTimeoutError::Disconnected) => Pulse::Stopped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn mock_clocks_step_through_ticks_without_waiting() {
+        let mut runner = runner(
+            EMPTY_MODULE,
+            &["--max-frames", "6", "--pool-policy", "grow"],
+        );
+        let clock = MockClock::new();
+        let start = clock.now();
+        runner.set_clock(clock.clone());
+        runner.handle_command(crate::Command::SetFps(1.0)).unwrap();
+
+        let real_start = Instant::now();
+        let mut sink = Collect::default();
+        run_until_stopped(&mut runner, &mut sink).unwrap();
+        assert_eq!(sink.frames.len(), 6);
+        // a second between each tick and the next, which the mock clock skips
+        assert_eq!(clock.now() - start, Duration::from_secs(5));
+        assert!(real_start.elapsed() < Duration::from_secs(5));
+    }
+}
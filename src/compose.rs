@@ -0,0 +1,152 @@
+use std::path::Path;
+
+use wasmer::{FunctionEnv, Instance, Store, Value};
+
+use crate::config::Config;
+use crate::error::RunnerError;
+use crate::imports::HostEnv;
+use crate::{
+    grow_memory, imports, load_module, new_store, optional_function, optional_global,
+    resolve_memory_name,
+};
+
+/// a second module run after the main one on every tick, for multi-pass effects like blurs and
+/// feedback trails. the main module's frame is copied into the pass's memory at its input offset
+/// and whatever the pass leaves at the start of its memory after ticking is displayed instead.
+///
+/// the input offset is the pass's exported `input_offset` i32 global, or just past its own
+/// framebuffer if it doesn't export one. since its own output stays in memory between ticks a
+/// pass can read back its previous frame for feedback effects.
+pub struct PostPass {
+    store: Store,
+    instance: Instance,
+    host_env: FunctionEnv<HostEnv>,
+    memory_name: String,
+    max_memory_pages: Option<u32>,
+}
+
+impl PostPass {
+    pub fn load(config: &Config, path: &Path) -> Result<Self, RunnerError> {
+        let mut store = new_store(config);
+        let (_, module) = load_module(path, &store)?;
+        let (import_object, host_env) =
+            imports::host_imports(&mut store, &config.import_namespace, config.time_source);
+        let instance = Instance::new(&mut store, &module, &import_object)?;
+        let memory_name = resolve_memory_name(&instance, None)?;
+        let memory = instance.exports.get_memory(&memory_name)?;
+        host_env.as_mut(&mut store).memory = Some(memory.clone());
+
+        Ok(Self {
+            store,
+            instance,
+            host_env,
+            memory_name,
+            max_memory_pages: config.max_memory_pages,
+        })
+    }
+
+    /// passes a new frame size, covering both eyes of stereo frames, on to the module's optional
+    /// `resize` export
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), RunnerError> {
+        let resize = optional_function::<(i32, i32), ()>(&self.store, &self.instance, "resize")?;
+        if let Some(resize) = resize {
+            resize.call(&mut self.store, width as i32, height as i32)?;
+        }
+        Ok(())
+    }
+
+    /// hands `frame` to the module as its input, ticks it and replaces `frame` with its output
+    pub fn run(&mut self, frame: &mut [u8]) -> Result<(), RunnerError> {
+        let len = frame.len() as u64;
+        let offset = self.input_offset(len)?;
+        let memory = self.instance.exports.get_memory(&self.memory_name)?.clone();
+        grow_memory(
+            &memory,
+            &mut self.store,
+            offset + len,
+            self.max_memory_pages,
+            true,
+        )?;
+
+        memory.view(&self.store).write(offset, frame)?;
+        self.host_env.as_mut(&mut self.store).drawing.clear();
+        self.instance
+            .exports
+            .get_function("tick")?
+            .call(&mut self.store, &[])?;
+        memory.view(&self.store).read(0, frame)?;
+        Ok(())
+    }
+
+    fn input_offset(&mut self, frame_len: u64) -> Result<u64, RunnerError> {
+        let Some(global) = optional_global(&self.store, &self.instance, "input_offset")? else {
+            return Ok(frame_len);
+        };
+        match global.get(&mut self.store) {
+            Value::I32(offset) => Ok(offset as u32 as u64),
+            other => Err(RunnerError::invalid_export(
+                "input_offset",
+                format!("expected an i32, got {:?}", other),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+
+    #[test]
+    fn post_pass_renders_from_the_main_modules_frame() {
+        // the main module leaves a fixed 2x2 image in its memory
+        let image = r#"(module
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\00\01\02\03\10\20\30\40\80\90\a0\b0\fd\fe\ff\ff")
+            (func (export "resize") (param i32 i32))
+            (func (export "tick")))"#;
+        // and the post pass inverts every byte of its input at 1024
+        let invert = module_file(
+            r#"(module
+                (memory (export "memory") 1)
+                (global (export "input_offset") i32 (i32.const 1024))
+                (func (export "resize") (param i32 i32))
+                (func (export "tick") (local $i i32)
+                    (loop $bytes
+                        (i32.store8
+                            (local.get $i)
+                            (i32.xor
+                                (i32.load8_u (i32.add (local.get $i) (i32.const 1024)))
+                                (i32.const 0xff)))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $bytes (i32.lt_u (local.get $i) (i32.const 16))))))"#,
+        );
+        let invert = invert.to_str().unwrap();
+
+        let mut runner = runner(image, &["--internal-res", "2x2", "--post", invert]);
+        let frame = runner.tick_once().unwrap();
+        assert_eq!(
+            frame[..],
+            [
+                0xff, 0xfe, 0xfd, 0xfc, 0xef, 0xdf, 0xcf, 0xbf, 0x7f, 0x6f, 0x5f, 0x4f, 0x02, 0x01,
+                0x00, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn post_passes_use_the_configured_time_source() {
+        // writes the whole milliseconds `now_ms` reports over the first pixel
+        let clock = module_file(
+            r#"(module
+                (import "env" "now_ms" (func $now_ms (result f64)))
+                (memory (export "memory") 1)
+                (func (export "tick")
+                    (i32.store (i32.const 0) (i32.trunc_f64_u (call $now_ms)))))"#,
+        );
+        let clock = clock.to_str().unwrap();
+        let args = ["--post", clock, "--time-source", "manual:1234"];
+        let mut runner = runner(EMPTY_MODULE, &args);
+        let frame = runner.tick_once().unwrap();
+        assert_eq!(frame[..4], [0xd2, 0x04, 0, 0]);
+    }
+}
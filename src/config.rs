@@ -0,0 +1,479 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::builder::PossibleValuesParser;
+use clap::Parser;
+
+use crate::adaptive::{parse_fps_range, FpsRange};
+use crate::builtins;
+use crate::dither::Dither;
+use crate::features::WasmFeature;
+use crate::format::PixelFormat;
+use crate::layout::{parse_layout, Layout};
+use crate::limits;
+use crate::pattern::TestPattern;
+use crate::require::{parse_requirements, Requirements};
+use crate::rotate::Rotation;
+use crate::screenshot::ScreenshotFormat;
+use crate::stereo::StereoMode;
+use crate::time::{parse_time_source, TimeSource};
+use crate::PoolPolicy;
+
+#[derive(Clone, Debug, Parser)]
+#[command(name = "wasm-renderer", about = "WebAssembly Demo Runner")]
+pub struct Config {
+    /// wasm module to run, in binary or text format
+    #[arg(env = "WASM_RENDERER_MODULE", default_value = "demo.wast")]
+    pub module: PathBuf,
+
+    /// more modules to play after the first one, each instantiated when it's switched to with
+    /// `n` and `p` or once the one before it reports it's done through `is_done`
+    #[arg(value_name = "MODULE", conflicts_with = "builtin")]
+    pub playlist: Vec<PathBuf>,
+
+    /// run one of the modules built into the renderer instead of a file
+    #[arg(long, value_name = "NAME", conflicts_with = "module", value_parser = PossibleValuesParser::new(builtins::names()))]
+    pub builtin: Option<String>,
+
+    /// run two modules side by side, ticking both every frame, and display how much their frames
+    /// differ, amplified so small differences show up. the biggest and mean differences are
+    /// printed at the end of the run.
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["builtin", "playlist", "per_pixel", "scanlines", "test_pattern"])]
+    pub diff: Vec<PathBuf>,
+
+    /// print the JSON the module's `describe` export writes, after validating it, then exit
+    #[arg(long)]
+    pub describe: bool,
+
+    /// list the builtin modules along with the checksum of their first frame, then exit
+    #[arg(long)]
+    pub list_builtins: bool,
+
+    /// what to do when every frame in the pool is still in use
+    #[arg(long, value_enum, default_value_t = PoolPolicy::Fixed)]
+    pub pool_policy: PoolPolicy,
+
+    /// upper bound on the number of frames a growing pool allocates
+    #[arg(long, default_value_t = 16, value_name = "N")]
+    pub max_pool_size: usize,
+
+    /// grow the pool as needed, up to `--max-pool-size`, then after a few seconds of ticks
+    /// shrink it to the most frames in use at once plus one
+    #[arg(long)]
+    pub auto_pool: bool,
+
+    /// pixel format the module writes into its image buffer
+    #[arg(long, value_enum, default_value_t = PixelFormat::Rgba8)]
+    pub pixel_format: PixelFormat,
+
+    /// cycle the `indexed8` palette by N entries every frame, so index i shows the color at
+    /// i + N after one frame, i + 2N after two and so on. negative values cycle the other way.
+    #[arg(long, value_name = "N", allow_hyphen_values = true)]
+    pub palette_rotate: Option<i32>,
+
+    /// dithering applied when downconverting 16 bit formats for display
+    #[arg(long, value_enum, default_value_t = Dither::None)]
+    pub dither: Dither,
+
+    /// color, as RRGGBB hex, that frames are composited over
+    #[arg(long, value_parser = parse_rgb, default_value = "000000", value_name = "RRGGBB")]
+    pub clear_color: [u8; 3],
+
+    /// render at this size, telling modules that export `resize` about it once instead of
+    /// following the window's size, and scale frames to fit the window. with `--stereo` this is
+    /// the size of both eyes side by side.
+    #[arg(long, value_parser = parse_size, value_name = "WxH")]
+    pub internal_res: Option<(u32, u32)>,
+
+    /// how the module arranges its frame's pixels in memory: `linear` rows, or `tiled:BLOCK`
+    /// for square tiles of BLOCK pixels stored one after another, each in row major order
+    #[arg(long, value_parser = parse_layout, default_value = "linear", value_name = "LAYOUT")]
+    pub layout: Layout,
+
+    /// display pixels of this color, as RRGGBB hex, as transparent so the clear color shows
+    /// through. overrides the module's `color_key` global.
+    #[arg(long, value_parser = parse_rgb, value_name = "RRGGBB")]
+    pub color_key: Option<[u8; 3]>,
+
+    /// the module writes premultiplied alpha, i.e. colors already scaled by their alpha. they're
+    /// displayed as such and unpremultiplied before being saved, since PNG and BMP are straight
+    /// alpha.
+    #[arg(long)]
+    pub premultiplied: bool,
+
+    /// display frames upside down, for modules whose origin is the bottom left corner
+    #[arg(long)]
+    pub flip_v: bool,
+
+    /// display frames mirrored left to right
+    #[arg(long)]
+    pub flip_h: bool,
+
+    /// rotate displayed frames clockwise by this many degrees. modules exporting `resize` are
+    /// given the rotated size of the window.
+    #[arg(long, value_enum, value_name = "DEGREES")]
+    pub rotate: Option<Rotation>,
+
+    /// treat the framebuffer as left and right eye images side by side, each 256 pixels wide,
+    /// and display them this way
+    #[arg(long, value_enum)]
+    pub stereo: Option<StereoMode>,
+
+    /// display a test pattern instead of calling the module, to check the display path alone
+    #[arg(long, value_enum)]
+    pub test_pattern: Option<TestPattern>,
+
+    /// call the module's `shade(x, y) -> i32` export for every pixel instead of reading its
+    /// memory. the result is treated as a little endian RGBA value, red in the lowest byte.
+    #[arg(long)]
+    pub per_pixel: bool,
+
+    /// render progressively: each tick the module's `next_scanline() -> i32` export returns the
+    /// row it just finished and only that row is copied from memory. rows past the bottom wrap
+    /// around to the top; negative rows copy nothing.
+    #[arg(long, conflicts_with = "per_pixel")]
+    pub scanlines: bool,
+
+    /// treat the i32 `tick` returns as the offset in memory of the frame to display rather than
+    /// a status, so the module can double buffer by alternating between two offsets
+    #[arg(long, conflicts_with_all = ["per_pixel", "scanlines"])]
+    pub tick_returns_ptr: bool,
+
+    /// number of threads, each with its own module instance, used to render `--per-pixel` frames
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub per_pixel_threads: usize,
+
+    /// run this second module after the main one on every tick. the main module's frame is
+    /// copied into its memory, at its `input_offset` global or right after its own framebuffer,
+    /// and the frame it renders is displayed instead.
+    #[arg(long, value_name = "MODULE")]
+    pub post: Option<PathBuf>,
+
+    /// display a running average of the last N frames, reset whenever the module sets its `dirty`
+    /// global
+    #[arg(long, value_name = "N")]
+    pub accumulate: Option<u32>,
+
+    /// scale the colors of every frame so their average brightness stays constant, adapting
+    /// over a few frames when the scene gets brighter or darker. only affects rgba8 and rgba16.
+    #[arg(long)]
+    pub auto_exposure: bool,
+
+    /// standard deviation of frame intervals, in milliseconds, above which a warning is logged
+    #[arg(long = "jitter-threshold-ms", value_parser = parse_millis, default_value = "4", value_name = "MS")]
+    pub jitter_threshold: Duration,
+
+    /// where the time `now_ms` reports comes from: `real` for the wall clock, `fixed:MS` to step
+    /// it by MS milliseconds every tick, `manual:MS` to hold it at MS, or `audio` to follow the
+    /// `--audio` playback position. the time scrub slider overrides it while it's held.
+    #[arg(long, value_name = "SOURCE", value_parser = parse_time_source, default_value = "real")]
+    pub time_source: TimeSource,
+
+    /// play this audio file, starting with the first tick. modules can sync to it through the
+    /// `audio_time() -> f64` import, which returns the playback position in seconds.
+    #[cfg(feature = "audio")]
+    #[arg(long, value_name = "PATH")]
+    pub audio: Option<PathBuf>,
+
+    /// pace ticks by the `--audio` playback position instead of a timer: each tick renders the
+    /// frame the track has reached at the current frame rate, skipping frames when ticks fall
+    /// behind and holding the last one while playback hasn't moved on
+    #[cfg(feature = "audio")]
+    #[arg(
+        long,
+        requires = "audio",
+        conflicts_with_all = ["vsync", "external_clock", "adaptive_fps"]
+    )]
+    pub frame_limit_fps_from_audio: bool,
+
+    /// namespace the module imports host functions (`log`, `now_ms`, `random`, `audio_time`)
+    /// from
+    #[arg(long, default_value = "env")]
+    pub import_namespace: String,
+
+    /// the most 64KiB pages any module memory may use. modules declaring larger memories, or
+    /// whose frames wouldn't fit, are rejected and growing past it fails inside the module.
+    #[arg(long, value_name = "N")]
+    pub max_memory_pages: Option<u32>,
+
+    /// the WebAssembly proposals modules are compiled with, comma separated, instead of wasmer's
+    /// defaults of threads, reference-types, simd, bulk-memory and multi-value
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "FEATURES")]
+    pub wasm_features: Option<Vec<WasmFeature>>,
+
+    /// grow every module memory to N 64KiB pages before the first tick, so growing it later
+    /// doesn't stall a tick. can't be more than `--max-memory-pages`.
+    #[arg(long, value_name = "N")]
+    pub reserve_pages: Option<u32>,
+
+    /// never grow a module's memory to fit frames, failing instead if the memory it declares is
+    /// too small, including after a resize
+    #[arg(long, conflicts_with = "reserve_pages")]
+    pub no_grow: bool,
+
+    /// run the module in a separate process, so it crashing leaves the window open with its last
+    /// frame. the module's sliders aren't shown.
+    #[arg(long, conflicts_with = "term")]
+    pub isolate: bool,
+
+    /// used by `--isolate` to start the child process, which sends its frames to this address
+    #[arg(long, hide = true, value_name = "ADDRESS")]
+    pub isolated_child: Option<SocketAddr>,
+
+    /// size in KiB of the stack module code runs on, from 8 to 102400. deeply recursive modules
+    /// trap once they exhaust it. wasmer's default is 1024.
+    #[arg(
+        long,
+        value_name = "KIB",
+        value_parser = clap::value_parser!(u64).range(limits::MIN_STACK_KIB..=limits::MAX_STACK_KIB)
+    )]
+    pub max_stack: Option<u64>,
+
+    /// exported memory to read frames from; required when the module exports more than one
+    #[arg(long, value_name = "NAME")]
+    pub memory_name: Option<String>,
+
+    /// set the module's exported mutable global NAME to VALUE before the first tick; may be
+    /// repeated
+    #[arg(long = "param", value_parser = crate::params::parse_param, value_name = "NAME=VALUE")]
+    pub params: Vec<(String, String)>,
+
+    /// list the module's imports, flag any the runner doesn't provide, and exit without running
+    #[arg(long)]
+    pub check: bool,
+
+    /// fail before running unless the module exports all of these, e.g.
+    /// `tick,image_buffer,resize(i32,i32)`. a signature in parentheses, optionally followed
+    /// by `->` and the results, also requires the export to be a function of that type.
+    #[arg(long, value_parser = parse_requirements, value_name = "EXPORTS")]
+    pub require: Option<Requirements>,
+
+    /// reload the module whenever its file changes
+    #[arg(long)]
+    pub watch: bool,
+
+    /// tick once per display refresh, as reported by the window, instead of on a timer
+    #[arg(long)]
+    pub vsync: bool,
+
+    /// tick once per UDP datagram received on ADDR instead of on a timer, so an external
+    /// controller can drive several renderers frame by frame. pulses arriving while paused are
+    /// dropped.
+    #[arg(long, value_name = "ADDR", conflicts_with = "vsync")]
+    pub external_clock: Option<SocketAddr>,
+
+    /// keep the tick rate between MIN and MAX frames per second, lowering it while ticks take
+    /// up most of the time between them and raising it back once there's headroom again, so a
+    /// throttled laptop isn't kept pinned at a rate it can't hold
+    #[arg(
+        long,
+        value_name = "MIN:MAX",
+        value_parser = parse_fps_range,
+        conflicts_with_all = ["vsync", "external_clock"]
+    )]
+    pub adaptive_fps: Option<FpsRange>,
+
+    /// read commands such as `pause`, `step` and `screenshot PATH` from stdin, one per line
+    #[arg(long)]
+    pub stdin_commands: bool,
+
+    /// write a log of the whole run to this file, from loading the module down to the timing
+    /// of every tick, replacing the log of the previous run. under `--isolate` the child
+    /// process's log goes next to it, with a `.child.log` extension.
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// only print errors. output that was asked for, like `--checksum-interval` or `--check`,
+    /// is still printed.
+    #[arg(long, short)]
+    pub quiet: bool,
+
+    /// preview frames in the terminal instead of opening a window
+    #[arg(long)]
+    pub term: bool,
+
+    /// width of the terminal preview in characters
+    #[arg(long, default_value_t = 80, value_name = "COLUMNS")]
+    pub term_width: usize,
+
+    /// print the module's custom sections and its `wr_metadata` section before running
+    #[arg(long)]
+    pub show_metadata: bool,
+
+    /// close the window once the module's `is_done` export returns nonzero
+    #[arg(long)]
+    pub close_on_done: bool,
+
+    /// stop and close the window after N ticks, whether or not the module has finished
+    #[arg(long = "max-frames", visible_alias = "frames", value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub frame_limit: Option<u64>,
+
+    /// write the module's raw linear memory to this file once `--dump-after-tick` ticks have run
+    #[arg(long, value_name = "PATH")]
+    pub dump_memory: Option<PathBuf>,
+
+    /// number of ticks to run before dumping memory
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub dump_after_tick: u64,
+
+    /// only dump the framebuffer region rather than the entire memory
+    #[arg(long)]
+    pub dump_framebuffer_only: bool,
+
+    /// append a `frame_index,checksum` line to this file for every frame
+    #[arg(long, value_name = "PATH")]
+    pub hash_log: Option<PathBuf>,
+
+    /// compare every frame's checksum against a log written by `--hash-log`, reporting the first
+    /// frame that differs and failing the run if any did
+    #[arg(long, value_name = "PATH")]
+    pub compare_log: Option<PathBuf>,
+
+    /// pin the thread running the module to this CPU core, counting from 0, for steadier timings
+    #[cfg(feature = "affinity")]
+    #[arg(long, value_name = "N")]
+    pub pin_core: Option<usize>,
+
+    /// time each stage of producing frames, like the module's `tick` and copying its frame out
+    /// of memory, and print a breakdown when the runner stops
+    #[arg(long)]
+    pub profile: bool,
+
+    /// the format `screenshot PATH` saves in, whatever PATH's extension
+    #[arg(long, value_enum, default_value_t = ScreenshotFormat::Png)]
+    pub screenshot_format: ScreenshotFormat,
+
+    /// write every frame to its own PNG, numbered by frame index through a %d or zero padded
+    /// %05d placeholder, e.g. `out/frame_%05d.png`. the directory is created if needed.
+    #[arg(long, value_name = "PATTERN")]
+    pub png_sequence: Option<String>,
+
+    /// keep thumbnails of the last `--replay-frames` frames, and if the runner stops because of
+    /// an error write them to `DIR/contact.png`, with their checksums and the error in
+    /// `DIR/replay.log`
+    #[arg(long, value_name = "DIR")]
+    pub replay_dir: Option<PathBuf>,
+
+    /// how many frames `--replay-dir` keeps
+    #[arg(long, value_name = "N", default_value_t = 16)]
+    pub replay_frames: usize,
+
+    /// write a frame to `DIR/NNNNN.png`, by frame index, whenever it differs from the last one
+    /// written, so static stretches are only captured once
+    #[arg(long, value_name = "DIR")]
+    pub capture_on_change: Option<PathBuf>,
+
+    /// the most frames `--capture-on-change` writes
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    pub max_captures: u32,
+
+    /// when the runner stops, write what the module drew through the `move_to`, `line_to`,
+    /// `stroke` and `fill` imports during its last tick to PATH as an SVG image
+    #[arg(long, value_name = "PATH")]
+    pub export_svg: Option<PathBuf>,
+
+    /// compare frame `--golden-frame`, as displayed, against this PNG and fail if they differ.
+    /// a diff image is written next to it on a mismatch.
+    #[arg(long, value_name = "PATH")]
+    pub golden: Option<PathBuf>,
+
+    /// index of the frame `--golden` checks
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub golden_frame: u64,
+
+    /// how far each channel may be off before a pixel counts as different from `--golden` or
+    /// `--verify-dir`
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub golden_tolerance: u8,
+
+    /// compare every frame, as displayed, against `DIR/NNNNN.png` with its index zero padded to
+    /// five digits, stopping once there are no more images. fails naming the first and the worst
+    /// mismatching frames, with a diff image written next to each one that doesn't match.
+    #[arg(long, value_name = "DIR")]
+    pub verify_dir: Option<PathBuf>,
+
+    /// write every frame's per channel histogram to this file, one JSON object per line
+    #[arg(long, value_name = "PATH")]
+    pub histogram_json: Option<PathBuf>,
+
+    /// print `frame_index: checksum` to stdout for every Nth frame
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub checksum_interval: Option<u64>,
+}
+
+/// parses a `WxH` size, neither of which may be 0
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected WxH, e.g. 320x240, got '{}'", s);
+    let (width, height) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+/// parses a non-negative number of milliseconds, which may have a fractional part
+pub fn parse_millis(s: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "expected a number of milliseconds of at least 0, got '{}'",
+            s
+        )
+    };
+    let ms: f64 = s.parse().map_err(|_| invalid())?;
+    if ms < 0.0 {
+        return Err(invalid());
+    }
+    // NaN, infinite and absurdly long durations
+    Duration::try_from_secs_f64(ms / 1000.0).map_err(|_| invalid())
+}
+
+/// parses an `RRGGBB` hex color, with or without a leading `#`
+pub fn parse_rgb(s: &str) -> Result<[u8; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 {
+        return Err(format!("expected a RRGGBB color, got '{}'", s));
+    }
+    let value =
+        u32::from_str_radix(hex, 16).map_err(|e| format!("invalid color '{}': {}", s, e))?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+impl Config {
+    /// the most frames the frame pool may hold under the configured policy
+    pub fn max_frames(&self) -> usize {
+        if self.auto_pool {
+            return self.max_pool_size;
+        }
+        match self.pool_policy {
+            PoolPolicy::Fixed => 0,
+            PoolPolicy::Grow => self.max_pool_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn millis_must_be_a_finite_non_negative_number() {
+        assert_eq!(parse_millis("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_millis("2.5"), Ok(Duration::from_micros(2500)));
+        for invalid in ["-1", "NaN", "inf", "1e300", "fast"] {
+            assert!(parse_millis(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[cfg(feature = "affinity")]
+    #[test]
+    fn pin_core_takes_a_core_index() {
+        let pin_core = |args: &[&str]| {
+            Config::try_parse_from(["wasm-renderer", "demo.wasm"].iter().chain(args))
+                .map(|config| config.pin_core)
+        };
+        assert_eq!(pin_core(&[]).unwrap(), None);
+        assert_eq!(pin_core(&["--pin-core", "2"]).unwrap(), Some(2));
+        assert!(pin_core(&["--pin-core", "-1"]).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeSet;
+
+use clap::ValueEnum;
+use wasmer::{Instance, Store, Value};
+
+use crate::error::RunnerError;
+use crate::format::PixelFormat;
+use crate::optional_global;
+
+/// a copy of one of the module's debug buffers, taken along with a frame
+#[derive(Clone, Debug)]
+pub struct DebugBuffer {
+    pub name: String,
+    pub format: PixelFormat,
+    pub bytes: Vec<u8>,
+}
+
+/// the names of the module's debug buffers, in the order they're exported: every i32 global
+/// named `debug_NAME`. each holds the offset of a buffer the size of the frame in the frame's
+/// memory, in the format whose index in `--pixel-format`'s list the optional
+/// `debug_NAME_format` global holds, or `rgba8` without one.
+pub fn names(instance: &Instance) -> Vec<String> {
+    instance
+        .exports
+        .iter()
+        .globals()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with("debug_") && !name.ends_with("_format"))
+        .map(|name| name["debug_".len()..].to_string())
+        .collect()
+}
+
+/// copies the debug buffers whose position in `names` is in `shown`
+pub fn read(
+    store: &mut Store,
+    instance: &Instance,
+    memory_name: &str,
+    shown: &BTreeSet<usize>,
+    (width, height): (u32, u32),
+) -> Result<Vec<DebugBuffer>, RunnerError> {
+    if shown.is_empty() {
+        return Ok(Vec::new());
+    }
+    let names = names(instance);
+    let memory = instance.exports.get_memory(memory_name)?;
+
+    let mut buffers = Vec::new();
+    for name in shown.iter().filter_map(|i| names.get(*i)) {
+        let global = format!("debug_{}", name);
+        let offset = i32_global(store, instance, &global)?.unwrap_or(0);
+        let format = match i32_global(store, instance, &format!("{}_format", global))? {
+            Some(index) => *PixelFormat::value_variants()
+                .get(index as usize)
+                .ok_or_else(|| {
+                    RunnerError::invalid_export(&global, format!("unknown format {}", index))
+                })?,
+            None => PixelFormat::Rgba8,
+        };
+
+        let mut bytes = vec![0; format.frame_len(width as usize, height as usize)];
+        memory.view(store).read(offset as u32 as u64, &mut bytes)?;
+        buffers.push(DebugBuffer {
+            name: name.clone(),
+            format,
+            bytes,
+        });
+    }
+    Ok(buffers)
+}
+
+fn i32_global(
+    store: &mut Store,
+    instance: &Instance,
+    name: &str,
+) -> Result<Option<i32>, RunnerError> {
+    let Some(global) = optional_global(store, instance, name)? else {
+        return Ok(None);
+    };
+    match global.get(store) {
+        Value::I32(value) => Ok(Some(value)),
+        other => Err(RunnerError::invalid_export(
+            name,
+            format!("expected an i32, got {:?}", other),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::Dither;
+    use crate::test_util::*;
+    use crate::Command;
+
+    #[test]
+    fn shown_debug_buffers_are_read_in_their_format() {
+        // a 4x2 frame with an alpha8 `depth` buffer at 4096 and an rgba8 `normals` one at 8192
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 4096) "\00\20\40\60\80\a0\c0\ff")
+                (global (export "debug_depth") i32 (i32.const 4096))
+                (global (export "debug_depth_format") i32 (i32.const 2))
+                (global (export "debug_normals") i32 (i32.const 8192))
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")))"#,
+            &["--internal-res", "4x2", "--max-frames", "1"],
+        );
+        assert_eq!(names(&runner.module_instance), ["depth", "normals"]);
+        runner
+            .handle_command(Command::ToggleDebugBuffer(0))
+            .unwrap();
+        let mut sink = Collect::default();
+        run_until_stopped(&mut runner, &mut sink).unwrap();
+
+        let buffers = &sink.frames[0].debug_buffers;
+        assert_eq!(buffers.len(), 1);
+        let depth = &buffers[0];
+        assert_eq!(
+            (depth.name.as_str(), depth.format),
+            ("depth", PixelFormat::Alpha8)
+        );
+        assert_eq!(
+            depth.bytes,
+            [0x00, 0x20, 0x40, 0x60, 0x80, 0xa0, 0xc0, 0xff]
+        );
+
+        let mut rgba = Vec::new();
+        depth
+            .format
+            .to_rgba8(&depth.bytes, 4, Dither::None, &[], &mut rgba);
+        let alphas: Vec<_> = rgba.chunks_exact(4).map(|pixel| pixel[3]).collect();
+        assert_eq!(alphas, depth.bytes);
+        assert!(rgba.chunks_exact(4).all(|pixel| pixel[..3] == [0xff; 3]));
+    }
+}
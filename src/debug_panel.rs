@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use druid::piet::{Text, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::widget::{Button, Controller, Flex, Label, List, Slider};
+use druid::{Color, Point, Rect, Selector, WidgetExt};
+
+use crate::ui::{self, AppState, FRAME_PUBLISHED};
+use crate::Command;
+
+/// shows or hides the debug panel, submitted by the frame view when `d` is pressed
+pub const TOGGLE_DEBUG_PANEL: Selector = Selector::new("wasm-renderer.toggle-debug-panel");
+
+// how many frame intervals the fps graph shows
+const FPS_HISTORY: usize = 120;
+const STATS_HEIGHT: f64 = 48.0;
+// the largest a wasm32 memory can be
+const MAX_WASM_MEMORY: u64 = 1 << 32;
+// how far into the module's animation the time scrub slider reaches
+const SCRUB_RANGE_MS: f64 = 60_000.0;
+
+/// the controls for the running module in one panel: its description, pause, resume and step
+/// buttons, a graph of recent frame rates, how much memory the module uses, a slider to scrub
+/// through time, its scenes and its sliders. `max_memory` is what the memory bar is measured
+/// against.
+pub fn make_debug_panel(
+    commands: Sender<Command>,
+    max_memory: Option<u64>,
+) -> impl Widget<AppState> {
+    let button = |label: &str, command: fn() -> Command| {
+        let commands = commands.clone();
+        Button::new(label).on_click(move |_ctx, _data: &mut AppState, _env| {
+            let _ = commands.send(command());
+        })
+    };
+    let buttons = Flex::row()
+        .with_child(button("pause", || Command::Pause))
+        .with_spacer(5.0)
+        .with_child(button("resume", || Command::Resume))
+        .with_spacer(5.0)
+        .with_child(button("step", || Command::Step));
+
+    let scene_commands = commands.clone();
+    let scenes = List::new(move || ui::make_scene_button(scene_commands.clone()))
+        .horizontal()
+        .with_spacing(5.0)
+        .lens(AppState::scenes);
+
+    let scrub = Flex::row()
+        .with_child(
+            Label::dynamic(|scrub: &f64, _| {
+                format!("time: {:.1} s", scrub * SCRUB_RANGE_MS / 1000.0)
+            })
+            .fix_width(160.0),
+        )
+        .with_flex_child(
+            Slider::new()
+                .controller(ScrubController {
+                    commands: commands.clone(),
+                    dragging: false,
+                })
+                .expand_width(),
+            1.0,
+        )
+        .lens(AppState::scrub);
+
+    let sliders = List::new(ui::make_slider)
+        .controller(ui::SliderController { commands })
+        .lens(AppState::sliders);
+
+    let description =
+        Label::dynamic(|data: &AppState, _| data.description.clone().unwrap_or_default());
+
+    Flex::column()
+        .cross_axis_alignment(druid::widget::CrossAxisAlignment::Start)
+        .with_child(description)
+        .with_child(buttons)
+        .with_spacer(5.0)
+        .with_child(
+            DebugStats::new(max_memory.unwrap_or(MAX_WASM_MEMORY))
+                .lens(druid::lens::Unit)
+                .fix_height(STATS_HEIGHT)
+                .expand_width(),
+        )
+        .with_spacer(5.0)
+        .with_child(scrub)
+        .with_child(scenes)
+        .with_child(sliders)
+        .padding((10.0, 0.0, 10.0, 10.0))
+}
+
+/// holds the module's clock at the scrub slider's time while it's dragged, and lets it run again
+/// from there once it's released
+struct ScrubController {
+    commands: Sender<Command>,
+    dragging: bool,
+}
+
+impl<W: Widget<f64>> Controller<f64, W> for ScrubController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut f64,
+        env: &Env,
+    ) {
+        let before = *data;
+        child.event(ctx, event, data, env);
+        let moved = match event {
+            Event::MouseDown(_) => {
+                self.dragging = true;
+                true
+            }
+            Event::MouseUp(_) if self.dragging => {
+                self.dragging = false;
+                let _ = self.commands.send(Command::ScrubTime(None));
+                return;
+            }
+            _ => *data != before,
+        };
+        if self.dragging && moved {
+            let _ = self
+                .commands
+                .send(Command::ScrubTime(Some(*data * SCRUB_RANGE_MS)));
+        }
+    }
+}
+
+/// flips whether the debug panel is shown on `TOGGLE_DEBUG_PANEL`
+pub struct DebugPanelToggle;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for DebugPanelToggle {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(TOGGLE_DEBUG_PANEL) {
+                data.debug_panel = !data.debug_panel;
+                ctx.set_handled();
+                return;
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+}
+
+/// a graph of the intervals between recently published frames and a bar of the module's memory
+/// use, both updated as frames arrive
+struct DebugStats {
+    max_memory: u64,
+    arrivals: VecDeque<Instant>,
+    memory_size: u64,
+}
+
+impl DebugStats {
+    fn new(max_memory: u64) -> Self {
+        Self {
+            max_memory,
+            arrivals: VecDeque::with_capacity(FPS_HISTORY + 1),
+            memory_size: 0,
+        }
+    }
+
+    // notes a frame of a module using `memory_size` bytes arriving `at`
+    fn record(&mut self, at: Instant, memory_size: u64) {
+        if self.arrivals.len() > FPS_HISTORY {
+            self.arrivals.pop_front();
+        }
+        self.arrivals.push_back(at);
+        self.memory_size = memory_size;
+    }
+
+    fn intervals(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.arrivals
+            .iter()
+            .zip(self.arrivals.iter().skip(1))
+            .map(|(earlier, later)| later.saturating_duration_since(*earlier))
+    }
+
+    // the average frame rate over the frames in the graph, 0 until two have arrived
+    fn fps(&self) -> f64 {
+        let count = self.intervals().count();
+        let mean = self.intervals().sum::<Duration>().as_secs_f64() / count.max(1) as f64;
+        if mean > 0.0 {
+            1.0 / mean
+        } else {
+            0.0
+        }
+    }
+
+    // how much of the bar the module's memory fills, out of 1
+    fn memory_share(&self) -> f64 {
+        (self.memory_size as f64 / self.max_memory as f64).min(1.0)
+    }
+}
+
+impl Widget<()> for DebugStats {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut (), _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some(published) = cmd.get(FRAME_PUBLISHED) {
+                self.record(Instant::now(), published.memory_size);
+                ctx.request_paint();
+            }
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &(), _env: &Env) {}
+
+    fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &(), _data: &(), _env: &Env) {}
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &(),
+        _env: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &(), _env: &Env) {
+        let size = ctx.size();
+        let graph_height = size.height - 16.0;
+        ctx.fill(size.to_rect(), &Color::rgba8(0, 0, 0, 0x60));
+
+        // one bar per frame interval, the slowest reaching the top
+        let slowest = self.intervals().max().unwrap_or_default().as_secs_f64();
+        let bar_width = size.width / FPS_HISTORY as f64;
+        for (i, interval) in self.intervals().enumerate() {
+            if slowest == 0.0 {
+                break;
+            }
+            let height = interval.as_secs_f64() / slowest * graph_height;
+            let x = i as f64 * bar_width;
+            let bar = Rect::new(x, graph_height - height, x + bar_width, graph_height);
+            ctx.fill(bar, &Color::rgb8(0x40, 0xc0, 0x40));
+        }
+
+        let memory_share = self.memory_share();
+        let memory_bar = Rect::new(0.0, graph_height + 4.0, size.width, size.height - 2.0);
+        ctx.fill(memory_bar, &Color::rgb8(0x30, 0x30, 0x30));
+        let used = memory_bar.with_size((memory_bar.width() * memory_share, memory_bar.height()));
+        ctx.fill(used, &Color::rgb8(0x40, 0x80, 0xe0));
+
+        let text = format!(
+            "{:.1} fps, memory {} KiB of {} KiB",
+            self.fps(),
+            self.memory_size / 1024,
+            self.max_memory / 1024
+        );
+        match ctx
+            .text()
+            .new_text_layout(text)
+            .text_color(Color::WHITE)
+            .build()
+        {
+            Ok(layout) => ctx.draw_text(&layout, Point::new(4.0, 2.0)),
+            Err(e) => eprintln!("failed to lay out debug stats: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_track_recent_frames_and_memory() {
+        let mut stats = DebugStats::new(1 << 20);
+        let start = Instant::now();
+        stats.record(start, 0);
+        assert_eq!(stats.fps(), 0.0);
+
+        // frames every 10 ms, more of them than the graph holds
+        for i in 1..=FPS_HISTORY as u32 * 2 {
+            stats.record(start + Duration::from_millis(10) * i, 1 << 18);
+        }
+        assert_eq!(stats.intervals().count(), FPS_HISTORY);
+        assert!((stats.fps() - 100.0).abs() < 1e-6, "{}", stats.fps());
+        assert_eq!(stats.memory_share(), 0.25);
+
+        stats.record(start + Duration::from_secs(10), 1 << 30);
+        assert_eq!(stats.memory_share(), 1.0);
+    }
+}
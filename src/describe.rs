@@ -0,0 +1,126 @@
+use wasmer::{Instance, Store};
+
+use crate::error::RunnerError;
+use crate::json::{self, Value};
+use crate::optional_function;
+
+/// the longest description read from a module, in bytes
+pub const MAX_DESCRIPTION_LEN: usize = 4096;
+
+/// what a module says about itself through `describe(buf_ptr, buf_len) -> i32`, which writes up
+/// to `buf_len` bytes of a JSON object at `buf_ptr` and returns how many it wrote. like scene
+/// names, the buffer is the start of the frame.
+///
+/// every member is optional: `name` and `author` are strings, `fps` the recommended ticks per
+/// second and `controls` an array describing the module's inputs however it likes.
+#[derive(Debug)]
+pub struct Description {
+    pub json: String,
+    value: Value,
+}
+
+impl Description {
+    /// reads and validates the module's description, if it exports `describe`
+    pub fn read(
+        store: &mut Store,
+        instance: &Instance,
+        memory_name: &str,
+    ) -> Result<Option<Self>, RunnerError> {
+        let Some(describe) = optional_function::<(i32, i32), i32>(store, instance, "describe")?
+        else {
+            return Ok(None);
+        };
+        let memory = instance.exports.get_memory(memory_name)?;
+        let capacity = MAX_DESCRIPTION_LEN.min(memory.view(store).data_size() as usize);
+
+        let len = describe.call(store, 0, capacity as i32)?;
+        let mut bytes = vec![0; (len.max(0) as usize).min(capacity)];
+        memory.view(store).read(0, &mut bytes)?;
+        let json = String::from_utf8(bytes)
+            .map_err(|e| RunnerError::invalid_export("describe", format!("not UTF-8: {}", e)))?;
+
+        let invalid = |reason: String| RunnerError::invalid_export("describe", reason);
+        let value = json::parse(&json).map_err(|e| invalid(format!("invalid JSON: {}", e)))?;
+        if !matches!(value, Value::Object(_)) {
+            return Err(invalid("expected a JSON object".to_string()));
+        }
+        for (key, valid) in [
+            (
+                "name",
+                matches!(value.get("name"), None | Some(Value::String(_))),
+            ),
+            (
+                "author",
+                matches!(value.get("author"), None | Some(Value::String(_))),
+            ),
+            (
+                "fps",
+                matches!(value.get("fps"), None | Some(Value::Number(_))),
+            ),
+            (
+                "controls",
+                matches!(value.get("controls"), None | Some(Value::Array(_))),
+            ),
+        ] {
+            if !valid {
+                return Err(invalid(format!("'{}' has the wrong type", key)));
+            }
+        }
+
+        Ok(Some(Self { json, value }))
+    }
+
+    /// one line naming the module, its author and recommended fps, whichever it gave
+    pub fn summary(&self) -> String {
+        let string = |key| match self.value.get(key) {
+            Some(Value::String(s)) => Some(s.as_str()),
+            _ => None,
+        };
+        let mut summary = string("name").unwrap_or("unnamed module").to_string();
+        if let Some(author) = string("author") {
+            summary.push_str(&format!(" by {}", author));
+        }
+        if let Some(Value::Number(fps)) = self.value.get("fps") {
+            summary.push_str(&format!(", best at {} fps", fps));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+
+    // a module describing itself with `json`
+    fn describing(json: &str) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 5)
+                (data (i32.const 0x40000) "{}")
+                (func (export "describe") (param i32 i32) (result i32)
+                    (memory.copy (local.get 0) (i32.const 0x40000) (i32.const {}))
+                    (i32.const {}))
+                (func (export "tick")))"#,
+            json.replace('"', "\\\""),
+            json.len(),
+            json.len()
+        )
+    }
+
+    #[test]
+    fn descriptions_are_read_and_validated() {
+        let json = r#"{"name": "plasma", "author": "someone", "fps": 30, "controls": []}"#;
+        let description = runner(&describing(json), &[])
+            .description()
+            .unwrap()
+            .unwrap();
+        assert_eq!(description.json, json);
+        assert_eq!(description.summary(), "plasma by someone, best at 30 fps");
+
+        for invalid in [r#"{"fps": "fast"}"#, "[1, 2]", r#"{"name": "#] {
+            let result = runner(&describing(invalid), &[]).description();
+            assert!(result.is_err(), "{}", invalid);
+        }
+        assert!(runner(EMPTY_MODULE, &[]).description().unwrap().is_none());
+    }
+}
@@ -0,0 +1,100 @@
+use crate::format::PixelFormat;
+
+/// how much `--diff` scales differences up by, so small ones are still visible
+pub const DIFF_GAIN: u8 = 16;
+
+/// running totals of the differences `--diff` found, before amplification
+#[derive(Debug, Default)]
+pub struct DiffStats {
+    max: u8,
+    total: u64,
+    samples: u64,
+    frames: u64,
+    differing_frames: u64,
+}
+
+impl DiffStats {
+    pub fn report(&self) {
+        if self.frames == 0 {
+            return;
+        }
+        let mean = self.total as f64 / self.samples.max(1) as f64;
+        println!(
+            "diff: {} of {} frames differ, max difference {}, mean {:.3}",
+            self.differing_frames, self.frames, self.max, mean
+        );
+    }
+}
+
+/// replaces `a` with the absolute difference between it and `b`, byte by byte, scaled by
+/// `DIFF_GAIN`, so identical frames come out black. `Rgba8` frames compare their color channels
+/// only and are made opaque; other formats have every byte compared, which for `Rgba16`,
+/// `Indexed8` and `Yuv420p` only shows where the frames differ rather than by how much.
+pub fn difference(a: &mut [u8], b: &[u8], format: PixelFormat, stats: &mut DiffStats) {
+    let mut frame_max = 0;
+    let mut record = |a: &mut u8, b: u8| {
+        let difference = a.abs_diff(b);
+        frame_max = frame_max.max(difference);
+        stats.total += difference as u64;
+        stats.samples += 1;
+        *a = difference.saturating_mul(DIFF_GAIN);
+    };
+
+    match format {
+        PixelFormat::Rgba8 => {
+            for (a, b) in a.chunks_exact_mut(4).zip(b.chunks_exact(4)) {
+                for channel in 0..3 {
+                    record(&mut a[channel], b[channel]);
+                }
+                a[3] = 0xff;
+            }
+        }
+        _ => {
+            for (a, b) in a.iter_mut().zip(b) {
+                record(a, *b);
+            }
+        }
+    }
+
+    stats.frames += 1;
+    stats.max = stats.max.max(frame_max);
+    if frame_max > 0 {
+        stats.differing_frames += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::WasmDemoRunner;
+
+    // paints the same few pixels every tick
+    const PAINTER: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "resize") (param i32 i32))
+        (func (export "tick")
+            (i32.store (i32.const 0) (i32.const 0xff336699))
+            (i32.store (i32.const 12) (i32.const 0x80102030))))"#;
+
+    #[test]
+    fn identical_modules_diff_to_black() {
+        // the second module needn't export `resize` to follow the first's size
+        let without_resize = PAINTER.replace(r#"(func (export "resize") (param i32 i32))"#, "");
+        for other in [PAINTER, &without_resize] {
+            let (a, b) = (module_file(PAINTER), module_file(other));
+            let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+            let mut config = config(PAINTER, &["--internal-res", "4x2", "--diff", a, b]);
+            // as `main` does
+            config.module = a.into();
+            let mut runner = WasmDemoRunner::new(&config).unwrap();
+            for _ in 0..2 {
+                let frame = runner.tick_once().unwrap();
+                assert_eq!(frame.len(), 4 * 2 * 4);
+                assert!(frame.chunks_exact(4).all(|pixel| pixel == [0, 0, 0, 0xff]));
+            }
+            assert_eq!(runner.diff_stats.frames, 2);
+            assert_eq!(runner.diff_stats.differing_frames, 0);
+            assert_eq!(runner.diff_stats.max, 0);
+        }
+    }
+}
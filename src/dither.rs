@@ -0,0 +1,109 @@
+use clap::ValueEnum;
+
+/// how quantization error is handled when downconverting 16 bit channels to 8 bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+    /// round every sample to the nearest 8 bit value
+    None,
+    /// perturb the rounding threshold using a 2x2 bayer matrix
+    Ordered,
+    /// diffuse each sample's rounding error onto its unprocessed neighbours
+    Floyd,
+}
+
+const CHANNELS: usize = 4;
+
+// the classic 2x2 bayer index matrix; thresholds are derived as (index + 0.5) / 4 so a sample
+// exactly halfway between two 8 bit levels alternates between them in a checkerboard
+const BAYER_2X2: [[u8; 2]; 2] = [[0, 2], [3, 1]];
+
+/// downconverts little endian 16 bit samples in `src` into 8 bit samples in `dst`. `width` is the
+/// number of RGBA pixels per row and is used to locate each sample in the dither pattern.
+pub fn downconvert(src: &[u8], width: usize, mode: Dither, dst: &mut [u8]) {
+    debug_assert_eq!(src.len(), dst.len() * 2);
+
+    let samples = src
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as f32 * 255.0 / 65535.0);
+    let stride = width * CHANNELS;
+
+    match mode {
+        Dither::None => {
+            for (out, sample) in dst.iter_mut().zip(samples) {
+                *out = sample.round() as u8;
+            }
+        }
+        Dither::Ordered => {
+            for (i, (out, sample)) in dst.iter_mut().zip(samples).enumerate() {
+                let x = (i % stride) / CHANNELS;
+                let y = i / stride;
+                let threshold = (BAYER_2X2[y % 2][x % 2] as f32 + 0.5) / 4.0;
+                *out = quantize(sample, threshold);
+            }
+        }
+        Dither::Floyd => {
+            // errors carried into the current and the next row, indexed by sample within the row
+            let mut current = vec![0f32; stride];
+            let mut next = vec![0f32; stride];
+
+            let rows = dst.chunks_mut(stride).zip(src.chunks(stride * 2));
+            for (out_row, src_row) in rows {
+                let row_samples = src_row
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]) as f32 * 255.0 / 65535.0);
+
+                for (i, (out, sample)) in out_row.iter_mut().zip(row_samples).enumerate() {
+                    let wanted = sample + current[i];
+                    *out = quantize(wanted, 0.5);
+                    let error = wanted - *out as f32;
+
+                    let x = i / CHANNELS;
+                    if x + 1 < width {
+                        current[i + CHANNELS] += error * 7.0 / 16.0;
+                        next[i + CHANNELS] += error * 1.0 / 16.0;
+                    }
+                    if x > 0 {
+                        next[i - CHANNELS] += error * 3.0 / 16.0;
+                    }
+                    next[i] += error * 5.0 / 16.0;
+                }
+
+                std::mem::swap(&mut current, &mut next);
+                next.iter_mut().for_each(|e| *e = 0.0);
+            }
+        }
+    }
+}
+
+// rounds `sample` up when its fractional part exceeds `threshold`, clamping to the 8 bit range
+fn quantize(sample: f32, threshold: f32) -> u8 {
+    let base = sample.floor();
+    let rounded = if sample - base > threshold {
+        base + 1.0
+    } else {
+        base
+    };
+    rounded.clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_mid_gray_is_a_checkerboard() {
+        let (width, height) = (4, 4);
+        // 32768 is just past halfway between the 8 bit levels 127 and 128
+        let src: Vec<u8> = std::iter::repeat_n(32768u16.to_le_bytes(), width * height * CHANNELS)
+            .flatten()
+            .collect();
+        let mut dst = vec![0; width * height * CHANNELS];
+        downconvert(&src, width, Dither::Ordered, &mut dst);
+
+        for (i, pixel) in dst.chunks_exact(CHANNELS).enumerate() {
+            let (x, y) = (i % width, i / width);
+            let expected = if (x + y) % 2 == 0 { 128 } else { 127 };
+            assert_eq!(pixel, [expected; CHANNELS], "pixel ({}, {})", x, y);
+        }
+    }
+}
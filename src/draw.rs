@@ -0,0 +1,365 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::blend;
+use crate::error::RunnerError;
+
+/// a point in framebuffer pixels, (0, 0) being the top left corner of the top left pixel
+type Point = (f32, f32);
+
+// how a shape's path is painted
+#[derive(Clone, Copy, Debug)]
+enum Paint {
+    Stroke { color: [u8; 4], width: f32 },
+    Fill([u8; 4]),
+}
+
+/// a path painted by `stroke` or `fill`
+#[derive(Debug)]
+pub struct Shape {
+    // each subpath starts at a `move_to`
+    subpaths: Vec<Vec<Point>>,
+    paint: Paint,
+}
+
+impl Shape {
+    // how far painted pixels can reach past the path's points
+    fn reach(&self) -> f32 {
+        match self.paint {
+            Paint::Stroke { width, .. } => stroke_half_width(width),
+            Paint::Fill(_) => 0.0,
+        }
+    }
+
+    /// the rows of a framebuffer `height` pixels high the shape paints into
+    pub fn rows(&self, height: u32) -> Range<usize> {
+        let ys = self.subpaths.iter().flatten().map(|&(_, y)| y);
+        let (top, bottom) = ys.fold((f32::INFINITY, f32::NEG_INFINITY), |(top, bottom), y| {
+            (top.min(y), bottom.max(y))
+        });
+        if top > bottom {
+            return 0..0;
+        }
+        let reach = self.reach();
+        let top = (top - reach).floor().clamp(0.0, height as f32) as usize;
+        let bottom = (bottom + reach).ceil().clamp(0.0, height as f32) as usize;
+        top..bottom.max(top)
+    }
+
+    /// composites the shape over `rgba`, 8 bit RGBA rows `width` pixels wide starting at row
+    /// `top` of the framebuffer. a pixel is painted if its center is inside the shape, so edges
+    /// are aliased.
+    pub fn rasterize(&self, rgba: &mut [u8], width: usize, top: usize) {
+        if width == 0 {
+            return;
+        }
+        let rows = rgba.len() / 4 / width;
+        // every pixel is painted once even where segments overlap, so translucent joints don't
+        // come out darker
+        let mut covered = vec![false; rows * width];
+        let color = match self.paint {
+            Paint::Stroke {
+                color,
+                width: stroke_width,
+            } => {
+                let half = stroke_half_width(stroke_width);
+                for subpath in &self.subpaths {
+                    // a lone point is drawn as a dot
+                    let segments = subpath.windows(2).map(|w| (w[0], w[1]));
+                    let dot = (subpath.len() == 1).then(|| (subpath[0], subpath[0]));
+                    for (a, b) in segments.chain(dot) {
+                        cover_segment(&mut covered, width, top, a, b, half);
+                    }
+                }
+                color
+            }
+            Paint::Fill(color) => {
+                cover_fill(&mut covered, width, top, &self.subpaths);
+                color
+            }
+        };
+
+        for (pixel, _) in rgba
+            .chunks_exact_mut(4)
+            .zip(&covered)
+            .filter(|(_, covered)| **covered)
+        {
+            let bottom = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            pixel.copy_from_slice(&blend::over(color, bottom, 1.0));
+        }
+    }
+}
+
+// lines thinner than a pixel are drawn a pixel wide so they don't vanish
+fn stroke_half_width(width: f32) -> f32 {
+    width.max(1.0) / 2.0
+}
+
+// marks the pixels whose centers are within `half` of the segment from `a` to `b`, which gives
+// round caps and joins
+fn cover_segment(covered: &mut [bool], width: usize, top: usize, a: Point, b: Point, half: f32) {
+    let rows = covered.len() / width;
+    // the pixels along one axis within reach of the segment, out of `len` starting at `start`
+    let span = |a: f32, b: f32, start: usize, len: usize| {
+        let from = (a.min(b) - half).floor().max(start as f32) as usize;
+        let to = ((a.max(b) + half).ceil().max(0.0) as usize).min(start + len);
+        from..to.max(from)
+    };
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    for y in span(a.1, b.1, top, rows) {
+        for x in span(a.0, b.0, 0, width) {
+            let (px, py) = (x as f32 + 0.5 - a.0, y as f32 + 0.5 - a.1);
+            // how far along the segment the closest point to the pixel is
+            let t = if len2 > 0.0 {
+                ((px * dx + py * dy) / len2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (ex, ey) = (px - t * dx, py - t * dy);
+            let (distance2, half2) = (ex * ex + ey * ey, half * half);
+            // pixels exactly on the edge only count above and left of the line, like the half
+            // open spans of fills, so a line along a pixel boundary isn't twice as thick
+            let on_edge = distance2 == half2 && (ey < 0.0 || (ey == 0.0 && ex < 0.0));
+            if distance2 < half2 || on_edge {
+                covered[(y - top) * width + x] = true;
+            }
+        }
+    }
+}
+
+// marks the pixels whose centers are inside `subpaths`, each closed back to its start, by the
+// even-odd rule
+fn cover_fill(covered: &mut [bool], width: usize, top: usize, subpaths: &[Vec<Point>]) {
+    let rows = covered.len() / width;
+    let mut crossings = Vec::new();
+    for row in 0..rows {
+        let y = (top + row) as f32 + 0.5;
+        crossings.clear();
+        for subpath in subpaths.iter().filter(|subpath| subpath.len() > 2) {
+            let closing = std::iter::once((subpath[subpath.len() - 1], subpath[0]));
+            let edges = subpath.windows(2).map(|w| (w[0], w[1])).chain(closing);
+            for (a, b) in edges {
+                if (a.1 <= y) != (b.1 <= y) {
+                    crossings.push(a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1));
+                }
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+
+        for pair in crossings.chunks_exact(2) {
+            // the pixels whose centers fall in [pair[0], pair[1])
+            let from = (pair[0] - 0.5).ceil().clamp(0.0, width as f32) as usize;
+            let to = (pair[1] - 0.5).ceil().clamp(0.0, width as f32) as usize;
+            for x in from..to {
+                covered[row * width + x] = true;
+            }
+        }
+    }
+}
+
+/// the most path segments a module can draw in a tick, counting each `move_to` as one, so one
+/// drawing in an endless loop can't use up the host's memory. that bounds the shapes too, since
+/// painting an empty path is ignored.
+pub const MAX_SEGMENTS: usize = 100_000;
+
+/// what a module drew through the drawing imports, `move_to`, `line_to`, `stroke` and `fill`,
+/// since the start of its last tick
+#[derive(Debug, Default)]
+pub struct Drawing {
+    // the path `move_to` and `line_to` are building, which painting it ends
+    path: Vec<Vec<Point>>,
+    shapes: Vec<Shape>,
+    // segments drawn since the last `clear`, up to `MAX_SEGMENTS`
+    segments: usize,
+}
+
+impl Drawing {
+    /// forgets everything drawn so far, at the start of a tick
+    pub fn clear(&mut self) {
+        self.path.clear();
+        self.shapes.clear();
+        self.segments = 0;
+    }
+
+    /// starts a new subpath at (`x`, `y`)
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        if self.count_segment() {
+            self.path.push(vec![(x, y)]);
+        }
+    }
+
+    /// adds a line from the end of the current subpath to (`x`, `y`). without a current
+    /// subpath, one is started there instead.
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        if self.path.is_empty() {
+            return self.move_to(x, y);
+        }
+        if self.count_segment() {
+            let last = self.path.len() - 1;
+            self.path[last].push((x, y));
+        }
+    }
+
+    // whether there's room for another segment this tick, counting it if so
+    fn count_segment(&mut self) -> bool {
+        if self.segments == MAX_SEGMENTS {
+            return false;
+        }
+        self.segments += 1;
+        if self.segments == MAX_SEGMENTS {
+            tracing::warn!(
+                "module drew {} path segments in one tick, ignoring any more",
+                MAX_SEGMENTS
+            );
+        }
+        true
+    }
+
+    /// ends the current path, stroking it `width` pixels wide. returns `None`, drawing nothing,
+    /// if the path is empty.
+    pub fn stroke(&mut self, color: [u8; 4], width: f32) -> Option<&Shape> {
+        self.paint(Paint::Stroke { color, width })
+    }
+
+    /// ends the current path, filling it, like `stroke`
+    pub fn fill(&mut self, color: [u8; 4]) -> Option<&Shape> {
+        self.paint(Paint::Fill(color))
+    }
+
+    fn paint(&mut self, paint: Paint) -> Option<&Shape> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let subpaths = std::mem::take(&mut self.path);
+        self.shapes.push(Shape { subpaths, paint });
+        self.shapes.last()
+    }
+
+    /// writes the shapes drawn so far to `path` as a `width` x `height` SVG image
+    pub fn write_svg(&self, path: &Path, width: u32, height: u32) -> Result<(), RunnerError> {
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            width, height
+        );
+        for shape in &self.shapes {
+            let mut d = String::new();
+            for subpath in &shape.subpaths {
+                for (i, (x, y)) in subpath.iter().enumerate() {
+                    let command = if i == 0 { 'M' } else { 'L' };
+                    let _ = write!(d, "{}{} {} ", command, x, y);
+                }
+                if matches!(shape.paint, Paint::Fill(_)) {
+                    d.push_str("Z ");
+                }
+            }
+            let paint = match shape.paint {
+                Paint::Stroke { color, width } => format!(
+                    r#"fill="none" {} stroke-width="{}" stroke-linecap="round" stroke-linejoin="round""#,
+                    svg_paint(color, "stroke"),
+                    width.max(1.0)
+                ),
+                Paint::Fill(color) => {
+                    format!(r#"{} fill-rule="evenodd""#, svg_paint(color, "fill"))
+                }
+            };
+            let _ = writeln!(svg, r#"  <path d="{}" {}/>"#, d.trim_end(), paint);
+        }
+        svg.push_str("</svg>\n");
+        fs::write(path, svg)?;
+        Ok(())
+    }
+}
+
+// the attributes painting `property`, `fill` or `stroke`, in an RGBA color: the color, and its
+// opacity unless it's opaque
+fn svg_paint([r, g, b, a]: [u8; 4], property: &str) -> String {
+    let mut attributes = format!(r##"{}="#{:02x}{:02x}{:02x}""##, property, r, g, b);
+    if a != 0xff {
+        let _ = write!(
+            attributes,
+            r#" {}-opacity="{:.3}""#,
+            property,
+            a as f32 / 255.0
+        );
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn shapes_are_written_as_svg_paths() {
+        let mut drawing = Drawing::default();
+        drawing.move_to(1.0, 2.0);
+        drawing.line_to(5.5, 2.0);
+        drawing.stroke([0, 0x80, 0xff, 0xff], 0.5);
+        // a triangle and a square inside it, filled half transparent
+        drawing.move_to(0.0, 0.0);
+        drawing.line_to(8.0, 0.0);
+        drawing.line_to(0.0, 8.0);
+        drawing.move_to(1.0, 1.0);
+        drawing.line_to(2.0, 1.0);
+        drawing.line_to(2.0, 2.0);
+        drawing.line_to(1.0, 2.0);
+        drawing.fill([0xff, 0, 0, 0x80]);
+
+        let path = temp_dir("svg").join("shapes.svg");
+        drawing.write_svg(&path, 8, 4).unwrap();
+        let svg = fs::read_to_string(path).unwrap();
+        assert_eq!(
+            svg.lines().collect::<Vec<_>>(),
+            [
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="4" viewBox="0 0 8 4">"#,
+                // strokes thinner than a pixel are drawn a pixel wide
+                r##"  <path d="M1 2 L5.5 2" fill="none" stroke="#0080ff" stroke-width="1" stroke-linecap="round" stroke-linejoin="round"/>"##,
+                r##"  <path d="M0 0 L8 0 L0 8 Z M1 1 L2 1 L2 2 L1 2 Z" fill="#ff0000" fill-opacity="0.502" fill-rule="evenodd"/>"##,
+                "</svg>",
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_past_the_limit_are_dropped_until_cleared() {
+        let mut drawing = Drawing::default();
+        drawing.move_to(0.0, 0.0);
+        for i in 0..MAX_SEGMENTS {
+            drawing.line_to(i as f32, 1.0);
+        }
+        drawing.move_to(0.0, 2.0);
+        let shape = drawing.stroke([0xff; 4], 1.0).unwrap();
+        assert_eq!(shape.subpaths.len(), 1);
+        assert_eq!(shape.subpaths[0].len(), MAX_SEGMENTS);
+
+        drawing.clear();
+        drawing.move_to(0.0, 0.0);
+        drawing.line_to(1.0, 1.0);
+        let shape = drawing.stroke([0xff; 4], 1.0).unwrap();
+        assert_eq!(shape.subpaths, [[(0.0, 0.0), (1.0, 1.0)]]);
+    }
+
+    #[test]
+    fn painting_in_a_loop_is_bounded_by_the_segments() {
+        let mut drawing = Drawing::default();
+        // painting nothing over and over adds no shapes
+        for _ in 0..1000 {
+            assert!(drawing.stroke([0xff; 4], 1.0).is_none());
+            assert!(drawing.fill([0xff; 4]).is_none());
+        }
+        assert!(drawing.shapes.is_empty());
+
+        // and dots stop once they've used up the tick's segments
+        for i in 0..MAX_SEGMENTS + 1000 {
+            drawing.move_to(i as f32, 0.0);
+            drawing.stroke([0xff; 4], 1.0);
+        }
+        assert_eq!(drawing.shapes.len(), MAX_SEGMENTS);
+    }
+}
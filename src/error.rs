@@ -0,0 +1,256 @@
+use std::fmt;
+
+use wasmer::{
+    CompileError, ExportError, InstantiationError, MemoryAccessError, MemoryError, RuntimeError,
+};
+
+/// everything that can go wrong while loading and running a module
+#[derive(Debug)]
+pub enum RunnerError {
+    Io(std::io::Error),
+    // boxed since these are much larger than the other variants
+    Compile(Box<CompileError>),
+    /// the module uses a WebAssembly proposal that `--wasm-features` leaves out
+    DisabledFeature(String),
+    Instantiation(Box<InstantiationError>),
+    MissingExport(ExportError),
+    /// an export exists but can't be used the way the runner needs to
+    InvalidExport {
+        name: String,
+        reason: String,
+    },
+    /// the module exports several memories and none was picked with `--memory-name`
+    AmbiguousMemory(Vec<String>),
+    /// `--check` found imports the runner can't satisfy
+    UnsupportedImports(Vec<String>),
+    /// exports named by `--require` are missing or have the wrong signature
+    UnmetRequirements(Vec<String>),
+    Memory(MemoryError),
+    /// a module's memory would need more pages than `--max-memory-pages` allows
+    MemoryLimitExceeded {
+        pages: u32,
+        limit: u32,
+    },
+    MemoryAccess(MemoryAccessError),
+    /// the module's memory ends before the frame it's supposed to hold, both in bytes
+    MemoryTooSmall {
+        have: u64,
+        need: u64,
+    },
+    Trap(RuntimeError),
+    /// the module's `tick` returned a negative status
+    ModuleFailed(i32),
+    Frame(String),
+    /// `--compare-log` found a frame that differs from the recorded run
+    FrameMismatch {
+        index: u64,
+    },
+    /// `--golden` or `assert_frame_matches` found the frame doesn't match the golden image. the
+    /// index is `None` for frames passed to `assert_frame_matches`, which doesn't know it.
+    GoldenMismatch {
+        index: Option<u64>,
+        reason: String,
+    },
+    Launch(druid::PlatformError),
+    #[cfg(feature = "audio")]
+    Audio(String),
+    /// the `--isolate` child process running the module failed, with its exit status
+    ModuleProcess(String),
+    RunnerPanicked,
+}
+
+impl RunnerError {
+    pub fn invalid_export(name: &str, reason: impl Into<String>) -> Self {
+        RunnerError::InvalidExport {
+            name: name.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    /// the process exit code reported for this error, so scripts can tell failures apart
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            // not 2, which clap exits with for usage errors
+            RunnerError::Io(_) => 13,
+            RunnerError::Compile(_) | RunnerError::DisabledFeature(_) => 3,
+            RunnerError::MissingExport(_)
+            | RunnerError::InvalidExport { .. }
+            | RunnerError::AmbiguousMemory(_)
+            | RunnerError::UnsupportedImports(_)
+            | RunnerError::UnmetRequirements(_) => 4,
+            RunnerError::Instantiation(_) => 5,
+            RunnerError::Memory(_)
+            | RunnerError::MemoryLimitExceeded { .. }
+            | RunnerError::MemoryAccess(_)
+            | RunnerError::MemoryTooSmall { .. } => 6,
+            RunnerError::Trap(_) | RunnerError::ModuleFailed(_) => 7,
+            RunnerError::Frame(_) => 8,
+            RunnerError::Launch(_) => 9,
+            RunnerError::FrameMismatch { .. } | RunnerError::GoldenMismatch { .. } => 11,
+            RunnerError::ModuleProcess(_) => 12,
+            #[cfg(feature = "audio")]
+            RunnerError::Audio(_) => 10,
+            // same as an uncaught panic on the main thread
+            RunnerError::RunnerPanicked => 101,
+        }
+    }
+}
+
+impl fmt::Display for RunnerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunnerError::Io(e) => write!(f, "io error: {}", e),
+            RunnerError::Compile(e) => write!(f, "failed to compile module: {}", e),
+            RunnerError::DisabledFeature(e) => write!(
+                f,
+                "module uses a proposal that isn't enabled, see --wasm-features: {}",
+                e
+            ),
+            RunnerError::Instantiation(e) => write!(f, "failed to instantiate module: {}", e),
+            RunnerError::MissingExport(e) => write!(f, "missing export: {}", e),
+            RunnerError::InvalidExport { name, reason } => {
+                write!(f, "invalid export '{}': {}", name, reason)
+            }
+            RunnerError::AmbiguousMemory(names) => write!(
+                f,
+                "module exports several memories ({}), pick one with --memory-name",
+                names.join(", ")
+            ),
+            RunnerError::UnsupportedImports(names) => write!(
+                f,
+                "module needs imports the runner doesn't provide: {}",
+                names.join(", ")
+            ),
+            RunnerError::UnmetRequirements(unmet) => {
+                write!(
+                    f,
+                    "module doesn't have the required exports: {}",
+                    unmet.join(", ")
+                )
+            }
+            RunnerError::Memory(e) => write!(f, "memory error: {}", e),
+            RunnerError::MemoryLimitExceeded { pages, limit } => write!(
+                f,
+                "module memory needs {} pages, more than the limit of {}",
+                pages, limit
+            ),
+            RunnerError::MemoryAccess(e) => write!(f, "memory access error: {}", e),
+            RunnerError::MemoryTooSmall { have, need } => write!(
+                f,
+                "module memory is {} bytes but the frame needs {}",
+                have, need
+            ),
+            RunnerError::Trap(e) => write!(f, "module trapped: {}", e),
+            RunnerError::ModuleFailed(status) => {
+                write!(f, "module's tick returned error status {}", status)
+            }
+            RunnerError::Frame(e) => write!(f, "frame error: {}", e),
+            RunnerError::FrameMismatch { index } => {
+                write!(f, "frame {} differs from the recorded log", index)
+            }
+            RunnerError::GoldenMismatch {
+                index: Some(index),
+                reason,
+            } => {
+                write!(
+                    f,
+                    "frame {} doesn't match the golden image: {}",
+                    index, reason
+                )
+            }
+            RunnerError::GoldenMismatch {
+                index: None,
+                reason,
+            } => write!(f, "frame doesn't match the golden image: {}", reason),
+            RunnerError::Launch(e) => write!(f, "failed to launch window: {}", e),
+            #[cfg(feature = "audio")]
+            RunnerError::Audio(e) => write!(f, "failed to play audio: {}", e),
+            RunnerError::ModuleProcess(status) => write!(f, "module process failed: {}", status),
+            RunnerError::RunnerPanicked => write!(f, "wasm runner thread panicked"),
+        }
+    }
+}
+
+impl std::error::Error for RunnerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunnerError::Io(e) => Some(e),
+            RunnerError::Compile(e) => Some(e.as_ref()),
+            RunnerError::Instantiation(e) => Some(e.as_ref()),
+            RunnerError::MissingExport(e) => Some(e),
+            RunnerError::Memory(e) => Some(e),
+            RunnerError::MemoryAccess(e) => Some(e),
+            RunnerError::Trap(e) => Some(e),
+            RunnerError::Launch(e) => Some(e),
+            RunnerError::InvalidExport { .. }
+            | RunnerError::DisabledFeature(_)
+            | RunnerError::AmbiguousMemory(_)
+            | RunnerError::UnsupportedImports(_)
+            | RunnerError::UnmetRequirements(_)
+            | RunnerError::MemoryLimitExceeded { .. }
+            | RunnerError::MemoryTooSmall { .. }
+            | RunnerError::ModuleFailed(_)
+            | RunnerError::Frame(_)
+            | RunnerError::FrameMismatch { .. }
+            | RunnerError::GoldenMismatch { .. }
+            | RunnerError::ModuleProcess(_)
+            | RunnerError::RunnerPanicked => None,
+            #[cfg(feature = "audio")]
+            RunnerError::Audio(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RunnerError {
+    fn from(e: std::io::Error) -> Self {
+        RunnerError::Io(e)
+    }
+}
+
+impl From<CompileError> for RunnerError {
+    fn from(e: CompileError) -> Self {
+        RunnerError::Compile(Box::new(e))
+    }
+}
+
+impl From<InstantiationError> for RunnerError {
+    fn from(e: InstantiationError) -> Self {
+        RunnerError::Instantiation(Box::new(e))
+    }
+}
+
+impl From<ExportError> for RunnerError {
+    fn from(e: ExportError) -> Self {
+        RunnerError::MissingExport(e)
+    }
+}
+
+impl From<MemoryError> for RunnerError {
+    fn from(e: MemoryError) -> Self {
+        RunnerError::Memory(e)
+    }
+}
+
+impl From<MemoryAccessError> for RunnerError {
+    fn from(e: MemoryAccessError) -> Self {
+        RunnerError::MemoryAccess(e)
+    }
+}
+
+impl From<RuntimeError> for RunnerError {
+    fn from(e: RuntimeError) -> Self {
+        RunnerError::Trap(e)
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for RunnerError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        RunnerError::Frame(e.to_string())
+    }
+}
+
+impl From<druid::PlatformError> for RunnerError {
+    fn from(e: druid::PlatformError) -> Self {
+        RunnerError::Launch(e)
+    }
+}
@@ -0,0 +1,110 @@
+use crate::format::PixelFormat;
+use crate::histogram::{self, Histogram};
+
+/// average display luminance, out of 1, that frames are scaled towards
+const TARGET_LUMINANCE: f32 = 0.5;
+/// how far the scale moves towards the one the current frame wants on every frame, so a sudden
+/// change in brightness fades in over a few frames instead of flickering
+const ADAPTATION_RATE: f32 = 0.1;
+/// bounds on the scale so an all black or all white frame doesn't blow it up
+const MIN_SCALE: f32 = 1.0 / 16.0;
+const MAX_SCALE: f32 = 16.0;
+
+/// scales the color channels of every frame so its average luminance stays near
+/// `TARGET_LUMINANCE`, smoothing the scale over time
+#[derive(Debug)]
+pub struct AutoExposure {
+    scale: f32,
+}
+
+impl AutoExposure {
+    pub fn new() -> Self {
+        Self { scale: 1.0 }
+    }
+
+    /// forgets the adapted scale, e.g. because the frame format changed
+    pub fn reset(&mut self) {
+        self.scale = 1.0;
+    }
+
+    /// adapts the scale to `frame` and applies it. `Alpha8` and `Indexed8` frames have no color
+    /// channels to scale and are left alone.
+    pub fn expose(&mut self, frame: &mut [u8], format: PixelFormat) {
+        if !matches!(format, PixelFormat::Rgba8 | PixelFormat::Rgba16) {
+            return;
+        }
+
+        // RGBA conversions without dithering don't depend on row boundaries, so the whole frame
+        // can pass for a single row
+        let row = frame.len() / format.bytes_per_pixel();
+        let histogram = histogram::compute(frame, row, format, &[]);
+        let wanted = (TARGET_LUMINANCE / mean_luminance(&histogram)).clamp(MIN_SCALE, MAX_SCALE);
+        self.scale += (wanted - self.scale) * ADAPTATION_RATE;
+        let scale = self.scale;
+
+        match format {
+            PixelFormat::Rgba8 => {
+                for pixel in frame.chunks_exact_mut(4) {
+                    for channel in &mut pixel[..3] {
+                        *channel = (*channel as f32 * scale).min(255.0).round() as u8;
+                    }
+                }
+            }
+            PixelFormat::Rgba16 => {
+                for pixel in frame.chunks_exact_mut(8) {
+                    for channel in pixel[..6].chunks_exact_mut(2) {
+                        let value = u16::from_le_bytes([channel[0], channel[1]]) as f32 * scale;
+                        channel.copy_from_slice(&(value.min(65535.0).round() as u16).to_le_bytes());
+                    }
+                }
+            }
+            PixelFormat::Alpha8 | PixelFormat::Indexed8 | PixelFormat::Yuv420p => {}
+        }
+    }
+}
+
+// average luminance of the frame out of 1, never quite zero so it can be divided by
+fn mean_luminance(histogram: &Histogram) -> f32 {
+    let [r, g, b, _] = histogram.map(|channel| {
+        let (count, total) = channel
+            .iter()
+            .enumerate()
+            .fold((0u64, 0u64), |(count, total), (value, n)| {
+                (count + *n as u64, total + value as u64 * *n as u64)
+            });
+        total as f32 / count.max(1) as f32 / 255.0
+    });
+    (r * 0.299 + g * 0.587 + b * 0.114).max(1.0 / 255.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // exposes 16 bit frames of `value` in every channel until the scale settles, returning
+    // what the last one's red channel came out as
+    fn settle(value: u16) -> u16 {
+        let pixel = [value, value, value, u16::MAX]
+            .map(u16::to_le_bytes)
+            .concat();
+        let mut exposure = AutoExposure::new();
+        let mut frame = Vec::new();
+        for _ in 0..100 {
+            frame = pixel.repeat(16);
+            exposure.expose(&mut frame, PixelFormat::Rgba16);
+        }
+        u16::from_le_bytes([frame[0], frame[1]])
+    }
+
+    #[test]
+    fn dark_frames_are_scaled_brighter_than_bright_ones() {
+        let (dark, bright) = (0x1000, 0xe000);
+        let (exposed_dark, exposed_bright) = (settle(dark), settle(bright));
+        assert!(exposed_dark > dark * 4, "{:#x}", exposed_dark);
+        assert!(exposed_bright < bright, "{:#x}", exposed_bright);
+        // both end up near the target luminance
+        for exposed in [exposed_dark, exposed_bright] {
+            assert!((exposed as f32 / 65535.0 - TARGET_LUMINANCE).abs() < 0.02);
+        }
+    }
+}
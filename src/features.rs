@@ -0,0 +1,73 @@
+use clap::ValueEnum;
+use wasmer::{CompileError, Cranelift, Engine, EngineBuilder, Features};
+
+/// a WebAssembly proposal modules can be compiled with, for `--wasm-features`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WasmFeature {
+    Threads,
+    ReferenceTypes,
+    Simd,
+    BulkMemory,
+    MultiValue,
+    TailCall,
+    MultiMemory,
+    Memory64,
+    ExtendedConst,
+}
+
+/// an engine that compiles modules with exactly the proposals in `enabled`, and none of the
+/// others wasmer turns on by default
+pub fn engine(enabled: &[WasmFeature]) -> Engine {
+    let enabled = |feature| enabled.contains(&feature);
+    let features = Features {
+        threads: enabled(WasmFeature::Threads),
+        reference_types: enabled(WasmFeature::ReferenceTypes),
+        simd: enabled(WasmFeature::Simd),
+        bulk_memory: enabled(WasmFeature::BulkMemory),
+        multi_value: enabled(WasmFeature::MultiValue),
+        tail_call: enabled(WasmFeature::TailCall),
+        multi_memory: enabled(WasmFeature::MultiMemory),
+        memory64: enabled(WasmFeature::Memory64),
+        extended_const: enabled(WasmFeature::ExtendedConst),
+        ..Features::new()
+    };
+    EngineBuilder::new(Cranelift::default())
+        .set_features(Some(features))
+        .engine()
+        .into()
+}
+
+/// whether compiling failed because the module uses a proposal that isn't enabled. the
+/// validator reports these as e.g. "SIMD support is not enabled".
+pub fn is_disabled_feature(error: &CompileError) -> bool {
+    error.to_string().contains("not enabled")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::RunnerError;
+    use crate::test_util::*;
+    use crate::WasmDemoRunner;
+
+    // adds two vectors of bytes into the first pixels of the frame
+    const SIMD_MODULE: &str = r#"(module
+        (memory (export "memory") 4)
+        (func (export "tick")
+            (v128.store (i32.const 0)
+                (i8x16.add
+                    (v128.const i8x16 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16)
+                    (v128.const i8x16 16 16 16 16 16 16 16 16 16 16 16 16 16 16 16 16)))))"#;
+
+    #[test]
+    fn simd_modules_load_with_simd_enabled() {
+        let mut runner = runner(SIMD_MODULE, &["--wasm-features", "simd,bulk-memory"]);
+        let frame = runner.tick_once().unwrap();
+        let expected: Vec<u8> = (17..=32).collect();
+        assert_eq!(frame[..16], expected);
+
+        match WasmDemoRunner::new(&config(SIMD_MODULE, &["--wasm-features", "bulk-memory"])) {
+            Err(RunnerError::DisabledFeature(_)) => {}
+            other => panic!("expected DisabledFeature, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use clap::ValueEnum;
+
+use crate::dither::{self, Dither};
+
+/// layout of the pixels a module writes into its image buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PixelFormat {
+    /// 8 bits per channel, RGBA order
+    Rgba8,
+    /// 16 bits per channel (little endian), RGBA order
+    Rgba16,
+    /// a single 8 bit coverage value per pixel, displayed as white with that alpha
+    Alpha8,
+    /// an 8 bit index per pixel into a palette of RGBA colors in the module's memory, found
+    /// through its `palette` i32 global. only the first `palette_size` entries are used if the
+    /// module exports that global, otherwise all 256.
+    Indexed8,
+    /// planar BT.601 YUV 4:2:0 in limited range: a full size Y plane followed by U and V planes
+    /// at half the width and height, rounded up for odd sizes
+    Yuv420p,
+}
+
+/// the most entries an `Indexed8` palette can have
+pub const MAX_PALETTE_SIZE: usize = 256;
+
+impl PixelFormat {
+    /// for `Yuv420p` this is the size of a pixel in the Y plane only, see `frame_len`
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgba8 => 4,
+            PixelFormat::Rgba16 => 8,
+            PixelFormat::Alpha8 | PixelFormat::Indexed8 | PixelFormat::Yuv420p => 1,
+        }
+    }
+
+    /// the size in bytes of a whole `width` x `height` frame in this format
+    pub fn frame_len(self, width: usize, height: usize) -> usize {
+        match self {
+            PixelFormat::Yuv420p => width * height + 2 * width.div_ceil(2) * height.div_ceil(2),
+            _ => width * height * self.bytes_per_pixel(),
+        }
+    }
+
+    /// writes a single 8 bit RGBA color into `dst`, one pixel in this format. for `Alpha8` the
+    /// color's luminance, scaled by its alpha, becomes the coverage value. `Indexed8` can't
+    /// represent arbitrary colors so it stores the same value as an index, which looks right
+    /// with a grayscale palette. `Yuv420p` shares chroma between pixels, so only the pixel's
+    /// luma is written, into its place in the Y plane.
+    pub fn write_rgba8(self, rgba: [u8; 4], dst: &mut [u8]) {
+        match self {
+            PixelFormat::Yuv420p => {
+                let [r, g, b, _] = rgba.map(|c| c as i32);
+                dst[0] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            }
+            PixelFormat::Alpha8 | PixelFormat::Indexed8 => {
+                let [r, g, b, a] = rgba.map(|c| c as u32);
+                let luma = (r * 299 + g * 587 + b * 114) / 1000;
+                dst[0] = (luma * a / 255) as u8;
+            }
+            PixelFormat::Rgba8 => dst.copy_from_slice(&rgba),
+            PixelFormat::Rgba16 => {
+                for (channel, out) in rgba.iter().zip(dst.chunks_exact_mut(2)) {
+                    // 257 maps 0xff exactly onto 0xffff
+                    out.copy_from_slice(&(*channel as u16 * 257).to_le_bytes());
+                }
+            }
+        }
+    }
+
+    /// converts a frame in this format into the 8 bit RGBA buffer druid expects, reusing `dst`.
+    /// `palette` is only used by `Indexed8`, which clamps indices past its end to its last
+    /// entry and displays black if it's empty.
+    pub fn to_rgba8(
+        self,
+        src: &[u8],
+        width: usize,
+        dither: Dither,
+        palette: &[[u8; 4]],
+        dst: &mut Vec<u8>,
+    ) {
+        if self == PixelFormat::Yuv420p {
+            return yuv420p_to_rgba8(src, width, dst);
+        }
+        let pixels = src.len() / self.bytes_per_pixel();
+        dst.resize(pixels * 4, 0);
+
+        match self {
+            PixelFormat::Rgba8 => dst.copy_from_slice(src),
+            PixelFormat::Rgba16 => dither::downconvert(src, width, dither, dst),
+            PixelFormat::Alpha8 => {
+                for (out, alpha) in dst.chunks_exact_mut(4).zip(src) {
+                    out.copy_from_slice(&[0xff, 0xff, 0xff, *alpha]);
+                }
+            }
+            PixelFormat::Indexed8 => {
+                let last = palette.len().saturating_sub(1);
+                for (out, index) in dst.chunks_exact_mut(4).zip(src) {
+                    let color = palette.get((*index as usize).min(last));
+                    out.copy_from_slice(color.unwrap_or(&[0, 0, 0, 0xff]));
+                }
+            }
+            PixelFormat::Yuv420p => {}
+        }
+    }
+}
+
+// the height of a `Yuv420p` frame of `len` bytes and the given width: the Y plane takes `width`
+// bytes per row and the chroma planes `width / 2` between them, rounded up, with odd heights
+// getting an extra chroma row
+fn yuv420p_height(len: usize, width: usize) -> usize {
+    let chroma_width = width.div_ceil(2);
+    let even = len / (width + chroma_width);
+    if PixelFormat::Yuv420p.frame_len(width, even + 1) <= len {
+        even + 1
+    } else {
+        even
+    }
+}
+
+fn yuv420p_to_rgba8(src: &[u8], width: usize, dst: &mut Vec<u8>) {
+    if width == 0 {
+        dst.clear();
+        return;
+    }
+    let height = yuv420p_height(src.len(), width);
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let (luma, chroma) = src.split_at(width * height);
+    let (u, v) = chroma.split_at(chroma_width * chroma_height);
+    dst.resize(width * height * 4, 0);
+
+    for (i, out) in dst.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (i % width, i / width);
+        let chroma_index = (y / 2) * chroma_width + x / 2;
+        let c = luma[i] as i32 - 16;
+        let d = u[chroma_index] as i32 - 128;
+        let e = v[chroma_index] as i32 - 128;
+        let channel = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+        out.copy_from_slice(&[
+            channel(298 * c + 409 * e),
+            channel(298 * c - 100 * d - 208 * e),
+            channel(298 * c + 516 * d),
+            0xff,
+        ]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blend;
+
+    #[test]
+    fn alpha8_mask_blends_over_the_clear_color() {
+        let mask = [0x00, 0x80, 0xff];
+        let mut rgba = Vec::new();
+        PixelFormat::Alpha8.to_rgba8(&mask, 3, Dither::None, &[], &mut rgba);
+
+        let clear = [0x20, 0x40, 0x60, 0xff];
+        let shown: Vec<_> = rgba
+            .chunks_exact(4)
+            .map(|pixel| blend::over([pixel[0], pixel[1], pixel[2], pixel[3]], clear, 1.0))
+            .collect();
+        // white over the clear color, half of it for the half opaque pixel
+        assert_eq!(shown, [clear, [0x90, 0xa0, 0xb0, 0xff], [0xff; 4]]);
+        assert_eq!(PixelFormat::Alpha8.frame_len(4, 2), 8);
+    }
+
+    #[test]
+    fn yuv420p_converts_to_bt601_rgb() {
+        // 3x3, so the 2x2 chroma planes are rounded up: red on the top left, blue on the top
+        // right, and no color along the bottom row
+        #[rustfmt::skip]
+        let yuv = [
+            81, 81, 41,
+            81, 81, 41,
+            235, 16, 126,
+            // U
+            90, 240,
+            128, 128,
+            // V
+            240, 110,
+            128, 128,
+        ];
+        assert_eq!(PixelFormat::Yuv420p.frame_len(3, 3), yuv.len());
+        let mut rgba = Vec::new();
+        PixelFormat::Yuv420p.to_rgba8(&yuv, 3, Dither::None, &[], &mut rgba);
+
+        let (red, blue) = ([0xff, 0, 0, 0xff], [0, 0, 0xff, 0xff]);
+        let (white, black, gray) = ([0xff; 4], [0, 0, 0, 0xff], [0x80, 0x80, 0x80, 0xff]);
+        let pixels: Vec<[u8; 4]> = rgba
+            .chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect();
+        assert_eq!(pixels, [red, red, blue, red, red, blue, white, black, gray]);
+    }
+}
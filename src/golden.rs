@@ -0,0 +1,414 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::blend;
+use crate::dither::Dither;
+use crate::error::RunnerError;
+use crate::screenshot;
+use crate::ui::PublishedFrame;
+use crate::Frame;
+
+/// compares `frame`, 8 bit RGBA such as `WasmDemoRunner::tick_once` returns with the default
+/// `--pixel-format`, against the PNG at `golden`, allowing every channel of every pixel to differ
+/// by up to `tolerance`. the frame is taken to be the golden's width and height, since a `Frame`
+/// doesn't know its own. on a mismatch a diff image is written next to the golden, e.g.
+/// `golden.diff.png` for `golden.png`, showing differing pixels in red over a dimmed copy of the
+/// golden.
+pub fn assert_frame_matches(
+    frame: &Frame,
+    golden: &Path,
+    tolerance: u8,
+) -> Result<(), RunnerError> {
+    let (expected, width, height) = read_png(golden)?;
+    let mismatch = if frame.len() != expected.len() {
+        Some(Mismatch {
+            pixels: (width * height) as usize,
+            reason: format!(
+                "frame is {} bytes but {} is {}x{}, {} bytes of 8 bit RGBA",
+                frame.len(),
+                golden.display(),
+                width,
+                height,
+                expected.len()
+            ),
+        })
+    } else {
+        compare_pixels(frame, &expected, width, height, golden, tolerance)?
+    };
+    match mismatch {
+        Some(mismatch) => Err(RunnerError::GoldenMismatch {
+            index: None,
+            reason: mismatch.reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// `--golden`: `assert_frame_matches` for a published frame in any format, as displayed
+pub fn assert_published_matches(
+    published: &PublishedFrame,
+    dither: Dither,
+    golden: &Path,
+    tolerance: u8,
+) -> Result<(), RunnerError> {
+    match compare(published, dither, golden, tolerance)? {
+        Some(mismatch) => Err(RunnerError::GoldenMismatch {
+            index: Some(published.index),
+            reason: mismatch.reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// how a frame differs from its golden image
+#[derive(Debug)]
+pub struct Mismatch {
+    /// how many pixels differ, or all of them if the sizes don't match
+    pub pixels: usize,
+    pub reason: String,
+}
+
+// `assert_published_matches` without failing on a mismatch
+fn compare(
+    published: &PublishedFrame,
+    dither: Dither,
+    golden: &Path,
+    tolerance: u8,
+) -> Result<Option<Mismatch>, RunnerError> {
+    let mut actual = Vec::new();
+    published.format.to_rgba8(
+        &published.frame,
+        published.width as usize,
+        dither,
+        &published.palette,
+        &mut actual,
+    );
+    // goldens are PNGs, so straight alpha
+    if published.premultiplied {
+        blend::unpremultiply(&mut actual);
+    }
+    let (expected, width, height) = read_png(golden)?;
+    if (width, height) != (published.width, published.height) {
+        return Ok(Some(Mismatch {
+            pixels: (published.width * published.height) as usize,
+            reason: format!(
+                "frame is {}x{} but {} is {}x{}",
+                published.width,
+                published.height,
+                golden.display(),
+                width,
+                height
+            ),
+        }));
+    }
+    compare_pixels(&actual, &expected, width, height, golden, tolerance)
+}
+
+// compares two 8 bit RGBA images of the same size, writing the diff image if they differ
+fn compare_pixels(
+    actual: &[u8],
+    expected: &[u8],
+    width: u32,
+    height: u32,
+    golden: &Path,
+    tolerance: u8,
+) -> Result<Option<Mismatch>, RunnerError> {
+    let mut diff = Vec::with_capacity(expected.len());
+    let mut mismatched = 0;
+    for (actual, expected) in actual.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        let matches = actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| a.abs_diff(*e) <= tolerance);
+        if matches {
+            let [r, g, b] = [expected[0], expected[1], expected[2]].map(|c| c as u32);
+            let dimmed = ((r * 299 + g * 587 + b * 114) / 4000) as u8;
+            diff.extend_from_slice(&[dimmed, dimmed, dimmed, 0xff]);
+        } else {
+            mismatched += 1;
+            diff.extend_from_slice(&[0xff, 0, 0, 0xff]);
+        }
+    }
+    if mismatched == 0 {
+        return Ok(None);
+    }
+
+    let diff_path = golden.with_extension("diff.png");
+    screenshot::save_png(&diff_path, &diff, width, height)?;
+    Ok(Some(Mismatch {
+        pixels: mismatched,
+        reason: format!(
+            "{} pixels differ from {} by more than {}, see {}",
+            mismatched,
+            golden.display(),
+            tolerance,
+            diff_path.display()
+        ),
+    }))
+}
+
+/// `--verify-dir`: compares frame N against `NNNNN.png` in a directory of golden images, for as
+/// many consecutively numbered images as the directory has, counting from `00000.png`
+pub struct VerifyDir {
+    dir: PathBuf,
+    frames: u64,
+    tolerance: u8,
+    compared: u64,
+    failed: u64,
+    // the first frame that didn't match and the one with the most differing pixels
+    first: Option<(u64, Mismatch)>,
+    worst: Option<(u64, usize)>,
+}
+
+impl VerifyDir {
+    pub fn open(dir: &Path, tolerance: u8) -> Result<Self, RunnerError> {
+        let frames = (0..)
+            .take_while(|index| Self::golden_path(dir, *index).is_file())
+            .count() as u64;
+        if frames == 0 {
+            return Err(RunnerError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} has no 00000.png to verify against", dir.display()),
+            )));
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            frames,
+            tolerance,
+            compared: 0,
+            failed: 0,
+            first: None,
+            worst: None,
+        })
+    }
+
+    fn golden_path(dir: &Path, index: u64) -> PathBuf {
+        dir.join(format!("{:05}.png", index))
+    }
+
+    /// compares `published` against its golden image, if the directory has one
+    pub fn check(&mut self, published: &PublishedFrame, dither: Dither) -> Result<(), RunnerError> {
+        let index = published.index;
+        if index >= self.frames {
+            return Ok(());
+        }
+        let golden = Self::golden_path(&self.dir, index);
+        self.compared += 1;
+        let Some(mismatch) = compare(published, dither, &golden, self.tolerance)? else {
+            return Ok(());
+        };
+        self.failed += 1;
+        if self
+            .worst
+            .is_none_or(|(_, pixels)| mismatch.pixels > pixels)
+        {
+            self.worst = Some((index, mismatch.pixels));
+        }
+        if self.first.is_none() {
+            self.first = Some((index, mismatch));
+        }
+        Ok(())
+    }
+
+    /// whether every frame with a golden image has been rendered
+    pub fn is_complete(&self, ticks: u64) -> bool {
+        ticks >= self.frames
+    }
+
+    /// fails if any frame didn't match, otherwise returns how many frames were compared
+    pub fn finish(&self) -> Result<u64, RunnerError> {
+        if let (Some((index, first)), Some((worst, pixels))) = (&self.first, self.worst) {
+            return Err(RunnerError::GoldenMismatch {
+                index: Some(*index),
+                reason: format!(
+                    "{}. {} of {} frames differ, the worst being frame {} with {} pixels",
+                    first.reason, self.failed, self.compared, worst, pixels
+                ),
+            });
+        }
+        if self.compared < self.frames {
+            return Err(RunnerError::GoldenMismatch {
+                index: Some(self.compared),
+                reason: format!(
+                    "the run stopped after {} of the {} frames in {}",
+                    self.compared,
+                    self.frames,
+                    self.dir.display()
+                ),
+            });
+        }
+        Ok(self.compared)
+    }
+}
+
+// decodes any 8 or 16 bit PNG into 8 bit RGBA, returning it along with its width and height
+fn read_png(path: &Path) -> Result<(Vec<u8>, u32, u32), RunnerError> {
+    let png_error = |e: png::DecodingError| RunnerError::Frame(e.to_string());
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(png_error)?;
+    buf.truncate(info.buffer_size());
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|c| [c[0], c[1], c[2], 0xff])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|c| [c[0], c[0], c[0], c[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|c| [*c, *c, *c, 0xff]).collect(),
+        // expanded into one of the above by `normalize_to_color8`
+        png::ColorType::Indexed => {
+            return Err(RunnerError::Frame(format!(
+                "{}: unexpected indexed PNG",
+                path.display()
+            )))
+        }
+    };
+    Ok((rgba, info.width, info.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    // a 2x2 frame of gray levels
+    const GRAYS: &str = r#"(module
+        (memory (export "memory") 1)
+        (data (i32.const 0) "\10\10\10\ff\20\20\20\ff\30\30\30\ff\40\40\40\ff")
+        (func (export "resize") (param i32 i32))
+        (func (export "tick")))"#;
+
+    #[test]
+    fn frames_match_goldens_within_the_tolerance() {
+        let frame = runner(GRAYS, &["--internal-res", "2x2"])
+            .tick_once()
+            .unwrap();
+
+        let dir = temp_dir("golden");
+        let golden = dir.join("golden.png");
+        let mut expected = frame.to_vec();
+        expected[4] += 3;
+        screenshot::save_png(&golden, &expected, 2, 2).unwrap();
+
+        assert!(assert_frame_matches(&frame, &golden, 3).is_ok());
+        assert!(!dir.join("golden.diff.png").exists());
+        match assert_frame_matches(&frame, &golden, 2) {
+            Err(RunnerError::GoldenMismatch {
+                index: None,
+                reason,
+            }) => {
+                assert!(reason.starts_with("1 pixels differ"), "{}", reason)
+            }
+            other => panic!("{:?}", other),
+        }
+        // the differing pixel is red in the diff image, the others a dimmed gray
+        let (diff, _, _) = read_png(&dir.join("golden.diff.png")).unwrap();
+        assert_eq!(&diff[4..8], &[0xff, 0, 0, 0xff]);
+        assert_eq!(&diff[..4], &[4, 4, 4, 0xff]);
+
+        // a frame of another size never matches
+        let larger = runner(GRAYS, &["--internal-res", "2x3"])
+            .tick_once()
+            .unwrap();
+        match assert_frame_matches(&larger, &golden, 0xff) {
+            Err(RunnerError::GoldenMismatch { reason, .. }) => {
+                assert!(reason.starts_with("frame is 24 bytes"), "{}", reason)
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn published_frames_are_compared_as_displayed() {
+        let mut sink = Collect::default();
+        let args = ["--internal-res", "2x2", "--max-frames", "1", "--quiet"];
+        run_until_stopped(&mut runner(GRAYS, &args), &mut sink).unwrap();
+        let published = &sink.frames[0];
+
+        let dir = temp_dir("golden-published");
+        let golden = dir.join("golden.png");
+        screenshot::save_png(&golden, &published.frame, 2, 2).unwrap();
+        assert!(assert_published_matches(published, Dither::None, &golden, 0).is_ok());
+
+        let mut expected = published.frame.to_vec();
+        expected[0] += 1;
+        screenshot::save_png(&golden, &expected, 2, 2).unwrap();
+        // these know which frame it was
+        match assert_published_matches(published, Dither::None, &golden, 0) {
+            Err(RunnerError::GoldenMismatch { index: Some(0), .. }) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_dirs_report_the_first_and_worst_mismatch() {
+        // a 2x2 frame whose pixels all count the ticks
+        let counter = r#"(module
+            (memory (export "memory") 1)
+            (global $ticks (mut i32) (i32.const 0))
+            (func (export "resize") (param i32 i32))
+            (func (export "tick")
+                (i32.store (i32.const 0) (i32.or (global.get $ticks) (i32.const 0xff000000)))
+                (i32.store (i32.const 4) (i32.or (global.get $ticks) (i32.const 0xff000000)))
+                (i32.store (i32.const 8) (i32.or (global.get $ticks) (i32.const 0xff000000)))
+                (i32.store (i32.const 12) (i32.or (global.get $ticks) (i32.const 0xff000000)))
+                (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))))"#;
+        let args = ["--internal-res", "2x2", "--quiet", "--pool-policy", "grow"];
+        let mut sink = Collect::default();
+        let mut recording = runner(counter, &[&args[..], &["--max-frames", "3"]].concat());
+        run_until_stopped(&mut recording, &mut sink).unwrap();
+        let frames: Vec<_> = sink.frames.iter().map(|p| p.frame.to_vec()).collect();
+
+        let verify = |dir: &Path| {
+            let dir = dir.to_str().unwrap();
+            let mut runner = runner(counter, &[&args[..], &["--verify-dir", dir]].concat());
+            let mut sink = Collect::default();
+            let result = run_until_stopped(&mut runner, &mut sink);
+            (result, sink.frames.len())
+        };
+
+        let matching = temp_dir("verify-matching");
+        for (index, frame) in frames.iter().enumerate() {
+            let path = matching.join(format!("{:05}.png", index));
+            screenshot::save_png(&path, frame, 2, 2).unwrap();
+        }
+        let (result, rendered) = verify(&matching);
+        assert!(result.is_ok(), "{:?}", result);
+        // the run stops once the last golden image has been checked
+        assert_eq!(rendered, 3);
+
+        // frame 1 has a pixel off, frame 2 all four
+        let mismatching = temp_dir("verify-mismatching");
+        for (index, frame) in frames.iter().enumerate() {
+            let mut frame = frame.clone();
+            let off = [0, 1, 4][index];
+            for pixel in frame.chunks_exact_mut(4).take(off) {
+                pixel[1] = 0x80;
+            }
+            let path = mismatching.join(format!("{:05}.png", index));
+            screenshot::save_png(&path, &frame, 2, 2).unwrap();
+        }
+        match verify(&mismatching).0 {
+            Err(RunnerError::GoldenMismatch {
+                index: Some(1),
+                reason,
+            }) => {
+                assert!(reason.starts_with("1 pixels differ"), "{}", reason);
+                assert!(
+                    reason.ends_with("2 of 3 frames differ, the worst being frame 2 with 4 pixels"),
+                    "{}",
+                    reason
+                );
+            }
+            other => panic!("expected GoldenMismatch, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::RunnerError;
+
+/// appends a `frame_index,checksum` line for every frame, checksums in the same hex form
+/// `--checksum-interval` prints
+pub struct HashLog {
+    writer: BufWriter<File>,
+}
+
+impl HashLog {
+    pub fn open(path: &Path) -> Result<Self, RunnerError> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, index: u64, checksum: u64) -> Result<(), RunnerError> {
+        writeln!(self.writer, "{},{:016x}", index, checksum)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), RunnerError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// checks frames against a log written by `HashLog`, remembering the first that differs
+pub struct HashComparison {
+    expected: HashMap<u64, u64>,
+    compared: u64,
+    first_mismatch: Option<u64>,
+}
+
+impl HashComparison {
+    pub fn load(path: &Path) -> Result<Self, RunnerError> {
+        let log = fs::read_to_string(path)?;
+        let mut expected = HashMap::new();
+        for (number, line) in log.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let entry = line.split_once(',').and_then(|(index, checksum)| {
+                Some((
+                    index.trim().parse().ok()?,
+                    u64::from_str_radix(checksum.trim(), 16).ok()?,
+                ))
+            });
+            let Some((index, checksum)) = entry else {
+                return Err(RunnerError::Frame(format!(
+                    "{}:{}: expected frame_index,checksum",
+                    path.display(),
+                    number + 1
+                )));
+            };
+            // a log appended to by several runs compares against the latest
+            expected.insert(index, checksum);
+        }
+
+        Ok(Self {
+            expected,
+            compared: 0,
+            first_mismatch: None,
+        })
+    }
+
+    /// compares one frame, reporting it if it's the first to diverge. frames missing from the
+    /// log aren't compared.
+    pub fn compare(&mut self, index: u64, checksum: u64) {
+        let Some(&expected) = self.expected.get(&index) else {
+            return;
+        };
+        self.compared += 1;
+        if expected != checksum && self.first_mismatch.is_none() {
+            eprintln!(
+                "frame {} diverged: expected {:016x}, got {:016x}",
+                index, expected, checksum
+            );
+            self.first_mismatch = Some(index);
+        }
+    }
+
+    /// fails if any frame diverged, otherwise returns how many frames were compared
+    pub fn finish(&self) -> Result<u64, RunnerError> {
+        match self.first_mismatch {
+            Some(index) => Err(RunnerError::FrameMismatch { index }),
+            None => Ok(self.compared),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn reports_the_first_frame_to_diverge() {
+        let path = temp_dir("hashlog").join("frames.log");
+        let mut log = HashLog::open(&path).unwrap();
+        for index in 0..12 {
+            log.record(index, index * 3).unwrap();
+        }
+        log.flush().unwrap();
+
+        let mut comparison = HashComparison::load(&path).unwrap();
+        for index in 0..12 {
+            // frames from 7 on changed
+            let checksum = if index < 7 { index * 3 } else { index };
+            comparison.compare(index, checksum);
+        }
+        match comparison.finish() {
+            Err(RunnerError::FrameMismatch { index }) => assert_eq!(index, 7),
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+
+        let mut comparison = HashComparison::load(&path).unwrap();
+        // frames past the end of the log aren't compared
+        for index in 0..20 {
+            comparison.compare(index, index * 3);
+        }
+        assert_eq!(comparison.finish().unwrap(), 12);
+    }
+}
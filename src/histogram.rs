@@ -0,0 +1,81 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::dither::Dither;
+use crate::error::RunnerError;
+use crate::format::PixelFormat;
+
+/// how many pixels have each 8 bit value, for the red, green, blue and alpha channels in order
+pub type Histogram = [[u32; 256]; 4];
+
+/// computes the histogram of a frame in `format` as it's displayed: 16 bit channels are counted
+/// by their 8 bit display value, `Alpha8` as white with that alpha, and `Indexed8` by the
+/// palette colors its indices select.
+pub fn compute(src: &[u8], width: usize, format: PixelFormat, palette: &[[u8; 4]]) -> Histogram {
+    let mut rgba = Vec::new();
+    format.to_rgba8(src, width, Dither::None, palette, &mut rgba);
+
+    let mut histogram = [[0; 256]; 4];
+    for pixel in rgba.chunks_exact(4) {
+        for (channel, value) in histogram.iter_mut().zip(pixel) {
+            channel[*value as usize] += 1;
+        }
+    }
+    histogram
+}
+
+/// writes every frame's histogram as one line of JSON, `{"frame": N, "r": [...], "g": [...],
+/// "b": [...], "a": [...]}` with 256 counts per channel
+pub struct HistogramLog {
+    writer: BufWriter<File>,
+}
+
+impl HistogramLog {
+    pub fn create(path: &Path) -> Result<Self, RunnerError> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn record(&mut self, index: u64, histogram: &Histogram) -> Result<(), RunnerError> {
+        write!(self.writer, "{{\"frame\": {}", index)?;
+        for (name, channel) in ["r", "g", "b", "a"].iter().zip(histogram) {
+            let counts: Vec<String> = channel.iter().map(|count| count.to_string()).collect();
+            write!(self.writer, ", \"{}\": [{}]", name, counts.join(", "))?;
+        }
+        writeln!(self.writer, "}}")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), RunnerError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_count_display_values() {
+        let rgba = [[10, 20, 30, 0xff], [10, 0, 0, 0xff], [0xff, 20, 0, 0x80]].concat();
+        let histogram = compute(&rgba, 3, PixelFormat::Rgba8, &[]);
+        let counts = |channel: usize| -> Vec<(usize, u32)> {
+            let counts = histogram[channel].iter().enumerate();
+            counts
+                .filter(|(_, &count)| count > 0)
+                .map(|(value, &count)| (value, count))
+                .collect()
+        };
+        assert_eq!(counts(0), [(10, 2), (0xff, 1)]);
+        assert_eq!(counts(1), [(0, 1), (20, 2)]);
+        assert_eq!(counts(2), [(0, 2), (30, 1)]);
+        assert_eq!(counts(3), [(0x80, 1), (0xff, 2)]);
+
+        // coverage is counted as white with that alpha
+        let histogram = compute(&[0, 0x40, 0x40], 3, PixelFormat::Alpha8, &[]);
+        assert_eq!(histogram[0][0xff], 3);
+        assert_eq!((histogram[3][0], histogram[3][0x40]), (1, 2));
+    }
+}
@@ -0,0 +1,92 @@
+use wasmer::{Instance, Store};
+
+use crate::error::RunnerError;
+use crate::optional_function;
+
+// like scene names, state is passed through the start of the frame, which the next tick
+// overwrites anyway
+const STATE_OFFSET: i32 = 0;
+
+/// asks the module for the state it wants to keep across a `--watch` reload, if it exports
+/// `serialize_state(buf_ptr) -> i32`, which writes its state at `buf_ptr` and returns its length
+pub fn save(
+    store: &mut Store,
+    instance: &Instance,
+    memory_name: &str,
+) -> Result<Option<Vec<u8>>, RunnerError> {
+    let Some(serialize) = optional_function::<i32, i32>(store, instance, "serialize_state")? else {
+        return Ok(None);
+    };
+    let len = serialize.call(store, STATE_OFFSET)?;
+    let memory = instance.exports.get_memory(memory_name)?;
+    let view = memory.view(store);
+    if len < 0 || STATE_OFFSET as u64 + len as u64 > view.data_size() {
+        return Err(RunnerError::invalid_export(
+            "serialize_state",
+            format!("returned a length of {}, past the end of memory", len),
+        ));
+    }
+    let mut state = vec![0; len as usize];
+    view.read(STATE_OFFSET as u64, &mut state)?;
+    Ok(Some(state))
+}
+
+/// hands `state` saved from the previous instance to the reloaded module's
+/// `deserialize_state(buf_ptr, len)`. returns false if it doesn't export one, which drops the
+/// state.
+pub fn restore(
+    store: &mut Store,
+    instance: &Instance,
+    memory_name: &str,
+    state: &[u8],
+) -> Result<bool, RunnerError> {
+    let Some(deserialize) =
+        optional_function::<(i32, i32), ()>(store, instance, "deserialize_state")?
+    else {
+        return Ok(false);
+    };
+    let memory = instance.exports.get_memory(memory_name)?;
+    // fails if the reloaded module's memory is too small to hold the state
+    memory.view(store).write(STATE_OFFSET as u64, state)?;
+    deserialize.call(store, STATE_OFFSET, state.len() as i32)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+
+    // counts its ticks into the first byte of the frame
+    const COUNTER: &str = r#"(module
+        (memory (export "memory") 4)
+        (global $ticks (mut i32) (i32.const 0))
+        (func (export "tick")
+            (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))
+            (i32.store8 (i32.const 0) (global.get $ticks)))
+        (func (export "serialize_state") (param $buf i32) (result i32)
+            (i32.store (local.get $buf) (global.get $ticks))
+            (i32.const 4))
+        (func (export "deserialize_state") (param $buf i32) (param $len i32)
+            (global.set $ticks (i32.load (local.get $buf)))))"#;
+
+    #[test]
+    fn state_survives_a_reload() {
+        let mut kept = runner(COUNTER, &[]);
+        for _ in 0..3 {
+            kept.tick_once().unwrap();
+        }
+        kept.reload().unwrap();
+        let frame = kept.tick_once().unwrap();
+        assert_eq!(frame.read(|buf| buf[0]), 4);
+
+        // without deserialize_state the reloaded module starts over
+        let forgetful = COUNTER.replace("\"deserialize_state\"", "\"ignored\"");
+        let mut forgot = runner(&forgetful, &[]);
+        for _ in 0..3 {
+            forgot.tick_once().unwrap();
+        }
+        forgot.reload().unwrap();
+        let frame = forgot.tick_once().unwrap();
+        assert_eq!(frame.read(|buf| buf[0]), 1);
+    }
+}
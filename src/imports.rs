@@ -0,0 +1,507 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use wasmer::{
+    AsStoreRef, ExternType, Function, FunctionEnv, FunctionEnvMut, Imports, Memory, Module, Store,
+};
+
+#[cfg(feature = "audio")]
+use crate::audio::AudioClock;
+use crate::draw::{Drawing, Shape};
+#[cfg(feature = "audio")]
+use crate::spectrum::Spectrum;
+use crate::time::{ModuleTime, TimeSource};
+
+/// host state shared by the functions imported into the module
+pub struct HostEnv {
+    // set once the module has been instantiated since its memory doesn't exist before then
+    pub memory: Option<Memory>,
+    // the framebuffer at the start of `memory`, kept up to date by the runner
+    pub frame: FrameLayout,
+    // what `now_ms` reports
+    pub time: ModuleTime,
+    // what the drawing imports drew during the current tick, cleared by the runner before each
+    pub drawing: Drawing,
+    rng_state: u64,
+    // drives `audio_time` when an `--audio` file is playing
+    #[cfg(feature = "audio")]
+    pub audio: Option<AudioClock>,
+    // refreshed by the runner before every tick
+    #[cfg(feature = "audio")]
+    pub spectrum: Spectrum,
+}
+
+impl HostEnv {
+    fn new(time_source: TimeSource) -> Self {
+        // xorshift state must never be zero
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+            | 1;
+
+        Self {
+            memory: None,
+            frame: FrameLayout::default(),
+            time: ModuleTime::new(time_source),
+            drawing: Drawing::default(),
+            rng_state: seed,
+            #[cfg(feature = "audio")]
+            audio: None,
+            #[cfg(feature = "audio")]
+            spectrum: Spectrum::default(),
+        }
+    }
+}
+
+impl HostEnv {
+    // milliseconds since the runner started, as far as the module is concerned
+    fn elapsed_ms(&self) -> f64 {
+        #[cfg(feature = "audio")]
+        let audio_ms = self
+            .audio
+            .as_ref()
+            .map(|clock| clock.position().as_secs_f64() * 1000.0);
+        #[cfg(not(feature = "audio"))]
+        let audio_ms = None;
+        self.time.now_ms(audio_ms)
+    }
+}
+
+/// size and pixel size of the framebuffer a module renders into, at the start of its memory. left
+/// empty for instances the runner doesn't read frames from, which makes drawing imports no-ops.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameLayout {
+    pub width: u32,
+    pub height: u32,
+    pub bytes_per_pixel: u32,
+}
+
+/// builds the host functions made available to modules under `namespace`
+pub fn host_imports(
+    store: &mut Store,
+    namespace: &str,
+    time_source: TimeSource,
+) -> (Imports, FunctionEnv<HostEnv>) {
+    let env = FunctionEnv::new(store, HostEnv::new(time_source));
+
+    let mut imports = Imports::new();
+    imports.define(
+        namespace,
+        "log",
+        Function::new_typed_with_env(store, &env, log),
+    );
+    imports.define(
+        namespace,
+        "now_ms",
+        Function::new_typed_with_env(store, &env, now_ms),
+    );
+    imports.define(
+        namespace,
+        "random",
+        Function::new_typed_with_env(store, &env, random),
+    );
+    imports.define(
+        namespace,
+        "audio_time",
+        Function::new_typed_with_env(store, &env, audio_time),
+    );
+    imports.define(
+        namespace,
+        "spectrum",
+        Function::new_typed_with_env(store, &env, spectrum),
+    );
+    imports.define(
+        namespace,
+        "blit_tile",
+        Function::new_typed_with_env(store, &env, blit_tile),
+    );
+    imports.define(
+        namespace,
+        "move_to",
+        Function::new_typed_with_env(store, &env, move_to),
+    );
+    imports.define(
+        namespace,
+        "line_to",
+        Function::new_typed_with_env(store, &env, line_to),
+    );
+    imports.define(
+        namespace,
+        "stroke",
+        Function::new_typed_with_env(store, &env, stroke),
+    );
+    imports.define(
+        namespace,
+        "fill",
+        Function::new_typed_with_env(store, &env, fill),
+    );
+
+    (imports, env)
+}
+
+/// an import declared by a module, and why the runner can't satisfy it if it can't
+pub struct ImportCheck {
+    pub namespace: String,
+    pub name: String,
+    pub ty: ExternType,
+    pub problem: Option<String>,
+}
+
+/// compares what `module` imports against the host functions provided under `namespace`
+pub fn check_imports(store: &mut Store, module: &Module, namespace: &str) -> Vec<ImportCheck> {
+    // only the imports' types matter here, not what they report
+    let (imports, _) = host_imports(store, namespace, TimeSource::RealTime);
+    module
+        .imports()
+        .map(|import| {
+            let problem = match imports.get_export(import.module(), import.name()) {
+                None => Some("not provided by the runner".to_string()),
+                Some(provided) => {
+                    let provided = provided.ty(store);
+                    if provided.is_compatible_with(import.ty(), None) {
+                        None
+                    } else {
+                        Some(format!("the runner provides {}", describe(&provided)))
+                    }
+                }
+            };
+            ImportCheck {
+                namespace: import.module().to_string(),
+                name: import.name().to_string(),
+                ty: import.ty().clone(),
+                problem,
+            }
+        })
+        .collect()
+}
+
+/// a readable description of an import or export's type
+pub fn describe(ty: &ExternType) -> String {
+    match ty {
+        ExternType::Function(ty) => format!("function {}", ty),
+        ExternType::Global(ty) => format!("global {}", ty),
+        ExternType::Table(ty) => format!("table {}", ty),
+        ExternType::Memory(ty) => format!("memory {}", ty),
+    }
+}
+
+// logs the utf-8 string of `len` bytes found at `ptr` in the module's memory
+fn log(env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32) {
+    let Some(memory) = &env.data().memory else {
+        tracing::warn!("module called log before its memory was available");
+        return;
+    };
+
+    let view = memory.view(&env);
+    let (ptr, len) = (ptr as u32 as u64, len as u32 as u64);
+    // checked before allocating so a bogus length can't make us allocate gigabytes
+    if ptr + len > view.data_size() {
+        tracing::warn!(
+            "module called log with a string of {} bytes at {}, past the end of its memory",
+            len,
+            ptr
+        );
+        return;
+    }
+    let mut buf = vec![0; len as usize];
+    if let Err(e) = view.read(ptr, &mut buf) {
+        tracing::warn!("module called log with an invalid string: {}", e);
+        return;
+    }
+    tracing::info!("module: {}", String::from_utf8_lossy(&buf));
+}
+
+// milliseconds elapsed since the runner started, or the time scrubbed to
+fn now_ms(env: FunctionEnvMut<HostEnv>) -> f64 {
+    env.data().elapsed_ms()
+}
+
+// seconds into the `--audio` file being played. without one, or in builds without the `audio`
+// feature, this falls back to the seconds elapsed since the runner started so modules keep
+// animating.
+fn audio_time(env: FunctionEnvMut<HostEnv>) -> f64 {
+    #[cfg(feature = "audio")]
+    if let Some(clock) = &env.data().audio {
+        return clock.position().as_secs_f64();
+    }
+    env.data().elapsed_ms() / 1000.0
+}
+
+// magnitude of one of the 64 bins of the `--audio` spectrum at the start of this tick, see
+// `Spectrum` for the frequencies they cover. always 0.0 when no audio is playing.
+#[cfg(feature = "audio")]
+fn spectrum(env: FunctionEnvMut<HostEnv>, bin: i32) -> f32 {
+    env.data().spectrum.bin(bin)
+}
+
+#[cfg(not(feature = "audio"))]
+fn spectrum(_env: FunctionEnvMut<HostEnv>, _bin: i32) -> f32 {
+    0.0
+}
+
+// copies tile `tile_index` of the tileset at `tileset_ptr` into the framebuffer with its top left
+// corner at (`dst_x`, `dst_y`), clipping whatever falls outside. tiles are `tile_w` x `tile_h`
+// pixels in the framebuffer's pixel format, stored one after another with rows top to bottom.
+fn blit_tile(
+    env: FunctionEnvMut<HostEnv>,
+    tileset_ptr: i32,
+    tile_index: i32,
+    dst_x: i32,
+    dst_y: i32,
+    tile_w: i32,
+    tile_h: i32,
+) {
+    let Some(memory) = &env.data().memory else {
+        return;
+    };
+    let frame = env.data().frame;
+    if tile_w <= 0 || tile_h <= 0 || tile_index < 0 {
+        return;
+    }
+    let (tile_w, tile_h) = (tile_w as i64, tile_h as i64);
+    let bpp = frame.bytes_per_pixel as i64;
+
+    // the part of the tile that lands inside the framebuffer
+    let left = (-(dst_x as i64)).max(0);
+    let top = (-(dst_y as i64)).max(0);
+    let right = tile_w.min(frame.width as i64 - dst_x as i64);
+    let bottom = tile_h.min(frame.height as i64 - dst_y as i64);
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    // huge tile indices and sizes can't be anywhere in memory
+    let view = memory.view(&env);
+    let tile_start = (tile_index as i64)
+        .checked_mul(tile_w)
+        .and_then(|offset| offset.checked_mul(tile_h))
+        .and_then(|offset| offset.checked_mul(bpp))
+        .and_then(|offset| offset.checked_add(tileset_ptr as u32 as i64))
+        .filter(|&start| start as u64 <= view.data_size());
+    let Some(tile_start) = tile_start else {
+        tracing::warn!("module called blit_tile with a tile outside its memory");
+        return;
+    };
+    let mut row = vec![0; ((right - left) * bpp) as usize];
+    for y in top..bottom {
+        let src = tile_start + (y * tile_w + left) * bpp;
+        let dst = ((dst_y as i64 + y) * frame.width as i64 + dst_x as i64 + left) * bpp;
+        let result = view
+            .read(src as u64, &mut row)
+            .and_then(|()| view.write(dst as u64, &row));
+        if let Err(e) = result {
+            tracing::warn!(
+                "module called blit_tile with a tile outside its memory: {}",
+                e
+            );
+            return;
+        }
+    }
+}
+
+// starts a new subpath of the path being drawn at (`x`, `y`), in framebuffer pixels
+fn move_to(mut env: FunctionEnvMut<HostEnv>, x: f32, y: f32) {
+    if finite("move_to", &[x, y]) {
+        env.data_mut().drawing.move_to(x, y);
+    }
+}
+
+// adds a line to (`x`, `y`) to the path being drawn
+fn line_to(mut env: FunctionEnvMut<HostEnv>, x: f32, y: f32) {
+    if finite("line_to", &[x, y]) {
+        env.data_mut().drawing.line_to(x, y);
+    }
+}
+
+// whether the drawing import `name` was passed only finite `values`. calls with NaNs or
+// infinities are ignored, since there's nothing to draw and they'd end up in `--export-svg`.
+fn finite(name: &str, values: &[f32]) -> bool {
+    let finite = values.iter().all(|value| value.is_finite());
+    if !finite {
+        tracing::warn!("module called {} with {:?}, ignoring it", name, values);
+    }
+    finite
+}
+
+// draws the path's lines `width` pixels wide into the framebuffer in `color`, an RGBA color with
+// red in its lowest byte, and starts a new path
+fn stroke(mut env: FunctionEnvMut<HostEnv>, color: i32, width: f32) {
+    if !finite("stroke", &[width]) {
+        return;
+    }
+    let (host, store) = env.data_and_store_mut();
+    if let Some(shape) = host.drawing.stroke(color.to_le_bytes(), width) {
+        rasterize(host.memory.as_ref(), &store, host.frame, shape);
+    }
+}
+
+// fills the area the path encloses, each subpath closed back to where it started, in `color`
+// and starts a new path
+fn fill(mut env: FunctionEnvMut<HostEnv>, color: i32) {
+    let (host, store) = env.data_and_store_mut();
+    if let Some(shape) = host.drawing.fill(color.to_le_bytes()) {
+        rasterize(host.memory.as_ref(), &store, host.frame, shape);
+    }
+}
+
+// composites `shape` over the framebuffer. only `Rgba8` framebuffers are drawn into, shapes
+// drawn over others only make it into `--export-svg`.
+fn rasterize(memory: Option<&Memory>, store: &impl AsStoreRef, frame: FrameLayout, shape: &Shape) {
+    let Some(memory) = memory else {
+        return;
+    };
+    if frame.bytes_per_pixel != 4 {
+        return;
+    }
+    let rows = shape.rows(frame.height);
+    if rows.is_empty() {
+        return;
+    }
+
+    let row_len = frame.width as usize * 4;
+    let offset = (rows.start * row_len) as u64;
+    let mut rgba = vec![0; rows.len() * row_len];
+    let view = memory.view(store);
+    if let Err(e) = view.read(offset, &mut rgba) {
+        tracing::warn!("the framebuffer is outside the module's memory: {}", e);
+        return;
+    }
+    shape.rasterize(&mut rgba, frame.width as usize, rows.start);
+    if let Err(e) = view.write(offset, &rgba) {
+        tracing::warn!("the framebuffer is outside the module's memory: {}", e);
+    }
+}
+
+// uniformly distributed in [0, 1), using xorshift64*
+fn random(mut env: FunctionEnvMut<HostEnv>) -> f64 {
+    let state = &mut env.data_mut().rng_state;
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    let value = state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::{Command, WasmDemoRunner};
+
+    // logs "hi" from the start of memory, then a string running past its end
+    const LOGGER: &str = r#"(module
+        (import "host" "log" (func $log (param i32 i32)))
+        (memory (export "memory") 4)
+        (data (i32.const 0) "hi")
+        (func (export "tick")
+            (call $log (i32.const 0) (i32.const 2))
+            (call $log (i32.const 16) (i32.const -1))))"#;
+
+    #[test]
+    fn blit_tile_copies_and_clips_tiles() {
+        // two 2x2 tiles, each pixel filled with its tile and index in the tile
+        let mut runner = runner(
+            r#"(module
+                (import "env" "blit_tile" (func $blit (param i32 i32 i32 i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 4096) "\10\10\10\10\11\11\11\11\12\12\12\12\13\13\13\13")
+                (data (i32.const 4112) "\20\20\20\20\21\21\21\21\22\22\22\22\23\23\23\23")
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")
+                    (call $blit (i32.const 4096) (i32.const 1) (i32.const 1) (i32.const 1) (i32.const 2) (i32.const 2))
+                    ;; hanging off the top right corner
+                    (call $blit (i32.const 4096) (i32.const 0) (i32.const 3) (i32.const -1) (i32.const 2) (i32.const 2))
+                    ;; so far out of memory the offset overflows
+                    (call $blit (i32.const 4096) (i32.const 0x7fffffff) (i32.const 0x80000000) (i32.const 0)
+                        (i32.const 0x7fffffff) (i32.const 0x7fffffff))))"#,
+            &["--internal-res", "4x4"],
+        );
+        let frame = runner.tick_once().unwrap();
+        let pixel = |x: usize, y: usize| frame[(y * 4 + x) * 4];
+        for (x, y, expected) in [
+            (1, 1, 0x20),
+            (2, 1, 0x21),
+            (1, 2, 0x22),
+            (2, 2, 0x23),
+            (3, 0, 0x12),
+        ] {
+            assert_eq!(pixel(x, y), expected, "pixel ({}, {})", x, y);
+        }
+        let blitted = frame.chunks_exact(4).filter(|pixel| pixel[0] != 0).count();
+        assert_eq!(blitted, 5);
+    }
+
+    #[test]
+    fn imports_come_from_the_configured_namespace() {
+        let mut runner = runner(LOGGER, &["--import-namespace", "host"]);
+        runner.tick_once().unwrap();
+        // nothing is imported from `host` by default
+        assert!(WasmDemoRunner::new(&config(LOGGER, &[])).is_err());
+    }
+
+    #[test]
+    fn scrubbing_sets_what_now_ms_returns() {
+        let mut runner = runner(
+            r#"(module
+                (import "env" "now_ms" (func $now_ms (result f64)))
+                (memory (export "memory") 4)
+                (func (export "tick") (f64.store (i32.const 0) (call $now_ms))))"#,
+            &[],
+        );
+        let now_ms = |runner: &mut WasmDemoRunner| {
+            let frame = runner.tick_once().unwrap();
+            f64::from_le_bytes(frame[..8].try_into().unwrap())
+        };
+        runner
+            .handle_command(Command::ScrubTime(Some(42_000.0)))
+            .unwrap();
+        assert_eq!(now_ms(&mut runner), 42_000.0);
+        assert_eq!(now_ms(&mut runner), 42_000.0);
+
+        // real time picks up from the scrubbed time
+        runner.handle_command(Command::ScrubTime(None)).unwrap();
+        let resumed = now_ms(&mut runner);
+        assert!((42_000.0..43_000.0).contains(&resumed), "{}", resumed);
+    }
+
+    #[test]
+    fn drawn_lines_are_exported_as_svg_paths() {
+        let dir = temp_dir("export-svg");
+        let svg = dir.join("drawing.svg");
+        // draws the same red line every tick, plus one with a NaN that's left out. shaded per
+        // pixel, so each tick goes through the optional `tick` rather than `tick_and_copy`.
+        let mut drawing = runner(
+            r#"(module
+                (import "env" "move_to" (func $move_to (param f32 f32)))
+                (import "env" "line_to" (func $line_to (param f32 f32)))
+                (import "env" "stroke" (func $stroke (param i32 f32)))
+                (memory (export "memory") 1)
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")
+                    (call $move_to (f32.const 0.5) (f32.const 1))
+                    (call $line_to (f32.const 3.5) (f32.const 1))
+                    (call $line_to (f32.const nan) (f32.const 2))
+                    (call $stroke (i32.const 0xff0000ff) (f32.const 2)))
+                (func (export "shade") (param i32 i32) (result i32) (i32.const 0xff000000)))"#,
+            &[
+                "--per-pixel",
+                "--export-svg",
+                svg.to_str().unwrap(),
+                "--frames",
+                "3",
+                "--internal-res",
+                "4x4",
+                "--quiet",
+            ],
+        );
+        run_until_stopped(&mut drawing, &mut Collect::default()).unwrap();
+
+        let svg = std::fs::read_to_string(svg).unwrap();
+        let paths: Vec<_> = svg.lines().filter(|line| line.contains("<path")).collect();
+        assert_eq!(
+            paths,
+            [
+                r##"  <path d="M0.5 1 L3.5 1" fill="none" stroke="#ff0000" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"/>"##
+            ]
+        );
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::process::{self, Child};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::ValueEnum;
+
+use crate::error::RunnerError;
+use crate::format::PixelFormat;
+use crate::ui::PublishedFrame;
+use crate::{stdin, Command, Frame, FrameSink};
+
+// how often to check whether the child died while waiting for it to connect
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// how long a connection gets to send the token before it's dropped
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(1);
+
+// the child's token, in its environment rather than its arguments, since other users can see
+// those
+const TOKEN_VAR: &str = "WASM_RENDERER_ISOLATE_TOKEN";
+
+// frames wider or taller than this are rejected before the parent allocates room for them
+const MAX_FRAME_SIDE: u32 = 8192;
+
+// message tags sent by the child, each followed by its payload
+const MESSAGE_FRAME: u8 = 0;
+const MESSAGE_CLOSE: u8 = 1;
+
+/// `--isolate`: a copy of this executable running the module, started with the same arguments
+/// plus `--isolated-child`. it connects back over a localhost socket and streams its frames to
+/// the parent, which sends back the UI's commands as lines of text, so the module crashing only
+/// takes down the child.
+///
+/// the child first sends the random token the parent gave it in `TOKEN_VAR`, and other
+/// connections are dropped. each frame is sent as a `MESSAGE_FRAME` byte followed by, little
+/// endian, its u64 index, u32 width and height, u8 format, u16 palette length, the palette's RGBA
+/// entries, the color key as 4 bytes, a u8 that's 1 if there is one and its RGB, a u8 that's 1 if
+/// the frame is premultiplied, a u8 that's 1 if a status follows, the i32 status, sent either
+/// way, the u64 memory size, u32 length and finally its bytes. a `MESSAGE_CLOSE` byte asks the
+/// parent to close the window.
+pub struct IsolatedChild {
+    child: Child,
+    stream: TcpStream,
+}
+
+impl IsolatedChild {
+    pub fn spawn() -> Result<Self, RunnerError> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let address = listener.local_addr()?;
+        let token = random_token();
+        let mut child = process::Command::new(env::current_exe()?)
+            .args(env::args_os().skip(1))
+            .arg("--isolated-child")
+            .arg(address.to_string())
+            .env(TOKEN_VAR, &token)
+            .spawn()?;
+        let stream = accept(&listener, &mut child, &token)?;
+        Ok(Self { child, stream })
+    }
+
+    /// forwards `commands` to the child and publishes its frames to `sink` until either side
+    /// goes away. the sink is only closed if the child asks for it, so when the module crashes
+    /// its last frame stays on screen; the crash is returned as an error once the child is gone.
+    pub fn run(
+        mut self,
+        sink: &mut dyn FrameSink,
+        commands: Receiver<Command>,
+    ) -> Result<(), RunnerError> {
+        let mut writer = self.stream.try_clone()?;
+        thread::spawn(move || {
+            for command in commands {
+                if writeln!(writer, "{}", encode(&command)).is_err() {
+                    return;
+                }
+            }
+            // the UI is gone, which tells the child to stop too
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+
+        let mut reader = BufReader::new(self.stream);
+        loop {
+            match read_message(&mut reader) {
+                Ok(Some(published)) => {
+                    if !sink.publish(published) {
+                        break;
+                    }
+                }
+                Ok(None) => {
+                    sink.close();
+                    break;
+                }
+                // the child exited or crashed
+                Err(_) => break,
+            }
+        }
+
+        let status = self.child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            let error = RunnerError::ModuleProcess(status.to_string());
+            sink.report_error(error.to_string());
+            Err(error)
+        }
+    }
+}
+
+// waits for `child` to connect and send `token`, dropping any other connections
+fn accept(
+    listener: &TcpListener,
+    child: &mut Child,
+    token: &str,
+) -> Result<TcpStream, RunnerError> {
+    // polled so a child that fails before connecting doesn't leave us waiting forever
+    listener.set_nonblocking(true)?;
+    loop {
+        match listener.accept() {
+            Ok((mut stream, peer)) => {
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(TOKEN_TIMEOUT))?;
+                let mut sent = vec![0; token.len()];
+                if stream.read_exact(&mut sent).is_ok() && sent == token.as_bytes() {
+                    stream.set_read_timeout(None)?;
+                    return Ok(stream);
+                }
+                tracing::warn!(
+                    "dropped a connection from {} that didn't send the token",
+                    peer
+                );
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if let Some(status) = child.try_wait()? {
+                    return Err(RunnerError::ModuleProcess(status.to_string()));
+                }
+                thread::sleep(CONNECT_POLL_INTERVAL);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// 128 bits no other process can guess. std has no random number generator, but the keys of a
+// `RandomState` come from the operating system's.
+fn random_token() -> String {
+    let state = RandomState::new();
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let half = |n: u8| {
+        let mut hasher = state.build_hasher();
+        hasher.write_u8(n);
+        hasher.write_u128(nanos);
+        hasher.write_u32(process::id());
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(0), half(1))
+}
+
+/// the child's end: connects to the parent at `address`, returning the sink to publish frames
+/// through and the commands the parent forwards. the receiver disconnects once the parent does.
+pub fn connect(address: SocketAddr) -> Result<(ParentSink, Receiver<Command>), RunnerError> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.write_all(env::var(TOKEN_VAR).unwrap_or_default().as_bytes())?;
+    let reader = BufReader::new(stream.try_clone()?);
+
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            match decode(&line) {
+                Ok(command) => {
+                    if sender.send(command).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => eprintln!("isolate: {}", e),
+            }
+        }
+    });
+
+    let sink = ParentSink {
+        writer: BufWriter::new(stream),
+    };
+    Ok((sink, commands))
+}
+
+/// publishes frames from the child to the parent process
+pub struct ParentSink {
+    writer: BufWriter<TcpStream>,
+}
+
+impl ParentSink {
+    fn write_frame(&mut self, published: &PublishedFrame) -> io::Result<()> {
+        let format = PixelFormat::value_variants()
+            .iter()
+            .position(|format| *format == published.format)
+            .unwrap_or_default() as u8;
+
+        self.writer.write_all(&[MESSAGE_FRAME])?;
+        self.writer.write_all(&published.index.to_le_bytes())?;
+        self.writer.write_all(&published.width.to_le_bytes())?;
+        self.writer.write_all(&published.height.to_le_bytes())?;
+        self.writer.write_all(&[format])?;
+        self.writer
+            .write_all(&(published.palette.len() as u16).to_le_bytes())?;
+        for entry in published.palette.iter() {
+            self.writer.write_all(entry)?;
+        }
+        match published.color_key {
+            Some([r, g, b]) => self.writer.write_all(&[1, r, g, b])?,
+            None => self.writer.write_all(&[0; 4])?,
+        }
+        self.writer.write_all(&[published.premultiplied as u8])?;
+        self.writer.write_all(&[published.status.is_some() as u8])?;
+        self.writer
+            .write_all(&published.status.unwrap_or_default().to_le_bytes())?;
+        self.writer
+            .write_all(&published.memory_size.to_le_bytes())?;
+        self.writer
+            .write_all(&(published.frame.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&published.frame)?;
+        self.writer.flush()
+    }
+}
+
+impl FrameSink for ParentSink {
+    fn publish(&mut self, published: PublishedFrame) -> bool {
+        // the parent is gone, nothing will display frames anymore
+        self.write_frame(&published).is_ok()
+    }
+
+    fn close(&mut self) {
+        let _ = self.writer.write_all(&[MESSAGE_CLOSE]);
+        let _ = self.writer.flush();
+    }
+}
+
+// reads the next message from the child, returning None if it asked to close
+fn read_message(reader: &mut impl Read) -> io::Result<Option<PublishedFrame>> {
+    match read_array::<1>(reader)?[0] {
+        MESSAGE_FRAME => {}
+        MESSAGE_CLOSE => return Ok(None),
+        tag => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown message {}", tag),
+            ))
+        }
+    }
+
+    let index = u64::from_le_bytes(read_array(reader)?);
+    let width = u32::from_le_bytes(read_array(reader)?);
+    let height = u32::from_le_bytes(read_array(reader)?);
+    if width > MAX_FRAME_SIDE || height > MAX_FRAME_SIDE {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{}x{} frames are too large", width, height),
+        ));
+    }
+    let format = PixelFormat::value_variants()
+        .get(read_array::<1>(reader)?[0] as usize)
+        .copied()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "unknown pixel format"))?;
+    let palette_len = u16::from_le_bytes(read_array(reader)?);
+    let palette = (0..palette_len)
+        .map(|_| read_array(reader))
+        .collect::<io::Result<Arc<[[u8; 4]]>>>()?;
+    let [has_color_key, r, g, b] = read_array(reader)?;
+    let color_key = (has_color_key == 1).then_some([r, g, b]);
+    let [premultiplied] = read_array(reader)?;
+    let [has_status] = read_array(reader)?;
+    let status = i32::from_le_bytes(read_array(reader)?);
+    let status = (has_status == 1).then_some(status);
+    let memory_size = u64::from_le_bytes(read_array(reader)?);
+    let len = u32::from_le_bytes(read_array(reader)?) as usize;
+    if len != format.frame_len(width as usize, height as usize) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} bytes is the wrong size for a {}x{} frame",
+                len, width, height
+            ),
+        ));
+    }
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+
+    Ok(Some(PublishedFrame {
+        index,
+        frame: Frame::from_vec(buf),
+        width,
+        height,
+        format,
+        palette,
+        color_key,
+        premultiplied: premultiplied == 1,
+        status,
+        memory_size,
+        // the child's clock isn't this process', so latency is measured from arrival here
+        // debug buffers only make it into the parent's window when running in one process
+        debug_buffers: Arc::new([]),
+        published_at: Instant::now(),
+    }))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+// commands travel in the same form `--stdin-commands` reads them, plus the ones only the UI sends
+fn encode(command: &Command) -> String {
+    match command {
+        Command::Resize { width, height } => format!("resize {} {}", width, height),
+        Command::SetGlobal { name, value } => format!("set {} {}", name, value),
+        Command::Pause => "pause".to_string(),
+        Command::Resume => "resume".to_string(),
+        Command::Step => "step".to_string(),
+        Command::ScrubTime(Some(ms)) => format!("time {}", ms),
+        Command::ScrubTime(None) => "time real".to_string(),
+        Command::NextModule => "next".to_string(),
+        Command::PreviousModule => "prev".to_string(),
+        // quoted, since a path can contain spaces or newlines that would split it
+        Command::Screenshot(path) => format!("screenshot {}", quote(&path.to_string_lossy())),
+        Command::SetFps(fps) => format!("setfps {}", fps),
+        Command::Vblank => "vblank".to_string(),
+        Command::SetScene(index) => format!("scene {}", index + 1),
+        Command::ToggleDebugBuffer(index) => format!("debug {}", index + 1),
+        Command::Click { x, y, button } => format!("click {} {} {}", x, y, button),
+        Command::SetPixelFormat(format) => match format.to_possible_value() {
+            Some(value) => format!("format {}", value.get_name()),
+            None => String::new(),
+        },
+    }
+}
+
+fn decode(line: &str) -> Result<Command, String> {
+    if let Some(path) = line.strip_prefix("screenshot \"") {
+        return Ok(Command::Screenshot(unquote(path)?.into()));
+    }
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["resize", width, height] => Ok(Command::Resize {
+            width: width
+                .parse()
+                .map_err(|e| format!("invalid width '{}': {}", width, e))?,
+            height: height
+                .parse()
+                .map_err(|e| format!("invalid height '{}': {}", height, e))?,
+        }),
+        ["vblank"] => Ok(Command::Vblank),
+        _ => stdin::parse(line),
+    }
+}
+
+// `s` in double quotes, with backslashes, quotes and newlines escaped by a backslash
+fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// undoes `quote` for what follows its opening quote
+fn unquote(s: &str) -> Result<String, String> {
+    let mut unquoted = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.as_str().is_empty() => return Ok(unquoted),
+            '\\' => match chars.next() {
+                Some('n') => unquoted.push('\n'),
+                Some(c @ ('\\' | '"')) => unquoted.push(c),
+                other => return Err(format!("invalid escape in '{}': {:?}", s, other)),
+            },
+            '"' => return Err(format!("text after the closing quote in '{}'", s)),
+            c => unquoted.push(c),
+        }
+    }
+    Err(format!("missing closing quote in '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn a_crashing_child_leaves_the_parent_running() {
+        let mut frames = Collect::default();
+        let mut runner = runner(EMPTY_MODULE, &["--max-frames", "1", "--quiet"]);
+        run_until_stopped(&mut runner, &mut frames).unwrap();
+        let published = frames.frames.pop().unwrap();
+
+        // the child's end, which sends a frame before its process dies to a signal
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut sink = ParentSink {
+            writer: BufWriter::new(TcpStream::connect(listener.local_addr().unwrap()).unwrap()),
+        };
+        let (stream, _) = listener.accept().unwrap();
+        let child = process::Command::new("sh")
+            .args(["-c", "kill -SEGV $$"])
+            .spawn()
+            .unwrap();
+        assert!(sink.publish(published.clone()));
+        drop(sink);
+
+        let mut parent = Collect::default();
+        let (_command_sender, commands) = mpsc::channel();
+        let result = IsolatedChild { child, stream }.run(&mut parent, commands);
+        assert!(
+            matches!(result, Err(RunnerError::ModuleProcess(_))),
+            "{:?}",
+            result
+        );
+        // the last frame stays up along with the error
+        assert_eq!(parent.frames.len(), 1);
+        assert_eq!(parent.frames[0].index, published.index);
+        assert_eq!(&parent.frames[0].frame[..], &published.frame[..]);
+        assert!(!parent.closed);
+        assert_eq!(parent.errors.len(), 1);
+    }
+
+    #[test]
+    fn only_connections_with_the_token_are_accepted() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut child = process::Command::new("sleep").arg("10").spawn().unwrap();
+        let token = random_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, random_token());
+
+        let mut stranger = TcpStream::connect(address).unwrap();
+        stranger.write_all(&[b'0'; 32]).unwrap();
+        let mut real = TcpStream::connect(address).unwrap();
+        real.write_all(token.as_bytes()).unwrap();
+        real.write_all(&[MESSAGE_CLOSE]).unwrap();
+
+        let mut stream = accept(&listener, &mut child, &token).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        // what follows the token is the real child's first message
+        assert!(matches!(read_message(&mut stream), Ok(None)));
+        // and the stranger was hung up on
+        let mut byte = [0];
+        assert!(matches!(stranger.read(&mut byte), Ok(0) | Err(_)));
+    }
+
+    // a `MESSAGE_FRAME` for an rgba8 frame of `width` x `height` whose length is `len`
+    fn frame_message(width: u32, height: u32, len: u32) -> Vec<u8> {
+        let mut message = vec![MESSAGE_FRAME];
+        message.extend(7u64.to_le_bytes());
+        message.extend(width.to_le_bytes());
+        message.extend(height.to_le_bytes());
+        message.push(0);
+        message.extend(0u16.to_le_bytes());
+        message.extend([0; 4 + 1 + 1 + 4]);
+        message.extend(0u64.to_le_bytes());
+        message.extend(len.to_le_bytes());
+        message.resize(message.len() + len as usize, 0xff);
+        message
+    }
+
+    #[test]
+    fn frames_must_be_the_size_they_claim() {
+        assert_eq!(PixelFormat::value_variants()[0], PixelFormat::Rgba8);
+        let published = read_message(&mut &frame_message(2, 3, 24)[..])
+            .unwrap()
+            .unwrap();
+        assert_eq!((published.index, published.frame.len()), (7, 24));
+
+        for message in [frame_message(2, 3, 23), frame_message(2, 3, 28)] {
+            let error = read_message(&mut &message[..]).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+        // too large to allocate, whatever length follows
+        let huge = frame_message(MAX_FRAME_SIDE + 1, 1, 0);
+        let error = read_message(&mut &huge[..]).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn screenshot_paths_survive_encoding() {
+        for path in [
+            "shot.png",
+            "my shots/frame 1.png",
+            "a \"quoted\"\\path\n.png",
+        ] {
+            let line = encode(&Command::Screenshot(PathBuf::from(path)));
+            assert!(!line.contains('\n'), "{}", line);
+            match decode(&line) {
+                Ok(Command::Screenshot(decoded)) => assert_eq!(decoded, PathBuf::from(path)),
+                other => panic!("{} decoded to {:?}", line, other),
+            }
+        }
+        assert!(decode("screenshot \"unterminated").is_err());
+        assert!(decode("screenshot \"a\" b").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+/// how a module arranges the pixels of its frame in memory
+///
+/// `Tiled(block)` frames are made of `block` x `block` tiles stored one after another in row
+/// major order, left to right and then top to bottom, with each tile's pixels in row major order
+/// too. tiles along the right and bottom edges of frames whose size isn't a multiple of `block`
+/// only hold the pixels inside the frame, so a tiled frame is exactly as large as a linear one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    Linear,
+    Tiled(usize),
+}
+
+impl Layout {
+    /// rearranges a `width` x `height` frame in this layout into linear order in place, using
+    /// `scratch` as a temporary copy
+    pub fn to_linear(
+        self,
+        frame: &mut [u8],
+        width: usize,
+        height: usize,
+        bytes_per_pixel: usize,
+        scratch: &mut Vec<u8>,
+    ) {
+        let Layout::Tiled(block) = self else {
+            return;
+        };
+        let len = width * height * bytes_per_pixel;
+        scratch.clear();
+        scratch.extend_from_slice(&frame[..len]);
+
+        let mut src = scratch.chunks_exact(bytes_per_pixel);
+        for tile_y in (0..height).step_by(block) {
+            let tile_height = block.min(height - tile_y);
+            for tile_x in (0..width).step_by(block) {
+                let tile_width = block.min(width - tile_x);
+                for y in tile_y..tile_y + tile_height {
+                    for x in tile_x..tile_x + tile_width {
+                        // there are exactly as many source pixels as destination ones
+                        let Some(pixel) = src.next() else {
+                            return;
+                        };
+                        let offset = (y * width + x) * bytes_per_pixel;
+                        frame[offset..offset + bytes_per_pixel].copy_from_slice(pixel);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// parses `linear` or `tiled:BLOCK`, where `BLOCK` is the tile size in pixels
+pub fn parse_layout(s: &str) -> Result<Layout, String> {
+    if s == "linear" {
+        return Ok(Layout::Linear);
+    }
+    let block = s
+        .strip_prefix("tiled:")
+        .ok_or_else(|| format!("expected linear or tiled:BLOCK, got '{}'", s))?;
+    match block.parse() {
+        Ok(0) => Err("tiles must be at least 1 pixel wide".to_string()),
+        Ok(block) => Ok(Layout::Tiled(block)),
+        Err(e) => Err(format!("invalid block size '{}': {}", block, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiled_frames_are_rearranged_into_rows() {
+        // a 3x3 frame of 2x2 tiles, the right and bottom ones cut short, numbered in the order
+        // they're stored in
+        let mut frame: Vec<u8> = (0..9).collect();
+        let layout = parse_layout("tiled:2").unwrap();
+        layout.to_linear(&mut frame, 3, 3, 1, &mut Vec::new());
+        assert_eq!(frame, [0, 1, 4, 2, 3, 5, 6, 7, 8]);
+
+        // two byte pixels move together
+        let mut frame: Vec<u8> = (0..16).collect();
+        Layout::Tiled(2).to_linear(&mut frame, 4, 2, 2, &mut Vec::new());
+        let pixels: Vec<u8> = frame.chunks_exact(2).map(|pixel| pixel[0] / 2).collect();
+        assert_eq!(pixels, [0, 1, 4, 5, 2, 3, 6, 7]);
+        assert!(frame.chunks_exact(2).all(|pixel| pixel[1] == pixel[0] + 1));
+
+        assert_eq!(parse_layout("linear"), Ok(Layout::Linear));
+        assert!(parse_layout("tiled:0").is_err());
+    }
+}
@@ -0,0 +1,3574 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::atomic;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use accumulate::Accumulator;
+use adaptive::AdaptiveFps;
+use clap::ValueEnum;
+use druid::{AppLauncher, ExtEventSink, Target, WindowDesc};
+use exposure::AutoExposure;
+use wasmer::{
+    AsStoreRef, CompileError, Engine, ExportError, ExternType, FunctionEnv, FunctionType, Global,
+    GlobalType, Instance, Memory, MemoryType, MemoryView, Module, Mutability, Pages, RuntimeError,
+    Store, TableType, TypedFunction, Value, WasmError, WasmTypeList,
+};
+
+mod accumulate;
+mod adaptive;
+#[cfg(feature = "affinity")]
+mod affinity;
+#[cfg(feature = "audio")]
+mod audio;
+mod blend;
+mod builtins;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod clock;
+mod compose;
+mod config;
+mod debug_buffers;
+mod debug_panel;
+mod describe;
+mod diff;
+mod dither;
+mod draw;
+mod error;
+mod exposure;
+mod features;
+mod format;
+mod golden;
+mod hashlog;
+mod histogram;
+mod hot_reload;
+mod imports;
+mod isolate;
+mod json;
+mod layout;
+mod limits;
+mod logging;
+mod metadata;
+mod metrics;
+mod params;
+mod pattern;
+mod profile;
+mod replay;
+mod require;
+mod rotate;
+mod scenes;
+mod screenshot;
+mod shade;
+mod sliders;
+#[cfg(feature = "audio")]
+mod spectrum;
+mod stdin;
+mod stereo;
+mod term;
+#[cfg(test)]
+mod test_util;
+mod time;
+mod ui;
+
+use clock::{Clock, ExternalClock, Pulse, RealClock};
+use compose::PostPass;
+pub use config::Config;
+use describe::Description;
+use diff::DiffStats;
+pub use error::RunnerError;
+use format::PixelFormat;
+pub use golden::assert_frame_matches;
+use golden::VerifyDir;
+use hashlog::{HashComparison, HashLog};
+use histogram::{Histogram, HistogramLog};
+use imports::HostEnv;
+use limits::GrowthWarning;
+use metrics::Metrics;
+use profile::Profile;
+use replay::Replay;
+use scenes::Scene;
+use screenshot::{ChangeCapture, PngSequence};
+use shade::ShadePool;
+use sliders::GlobalSlider;
+use ui::{AppState, PublishedFrame, FRAME_PUBLISHED, RUNNER_FAILED};
+
+const TICK_INTERVAL: Duration = Duration::from_millis(10);
+// how often `--watch` checks the module file for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+// how long `--external-clock` waits for a pulse before handling commands again, short enough
+// that they aren't noticeably delayed while no pulses arrive
+const CLOCK_POLL_INTERVAL: Duration = Duration::from_millis(20);
+// editors that write files in several steps can leave a module half written when the change is
+// first noticed, so reloads are retried a few times with a doubling delay
+const RELOAD_ATTEMPTS: u32 = 4;
+const RELOAD_BACKOFF: Duration = Duration::from_millis(50);
+// the name the memory the runner makes for modules that don't export one is added to their
+// exports under, replacing any function or global of the same name
+const HOST_MEMORY_NAME: &str = "host_framebuffer";
+// the most sub-frames a module's `subframes` export can ask for
+const MAX_SUBFRAMES: u32 = 64;
+// how many ticks `--auto-pool` watches before sizing the pool
+const AUTO_POOL_WARMUP_TICKS: u64 = 300;
+
+// a compiled and instantiated module, with its memory ready for frames
+struct LoadedModule {
+    store: Store,
+    instance: Instance,
+    host_env: FunctionEnv<HostEnv>,
+    memory_name: String,
+    // only used for `--per-pixel` with more than one thread
+    shade_pool: Option<ShadePool>,
+}
+
+impl LoadedModule {
+    // loads the configured module and grows its memory to hold `bytes_required` bytes
+    fn load(
+        config: &Config,
+        bytes_required: u64,
+        show_metadata: bool,
+    ) -> Result<Self, RunnerError> {
+        let mut store = new_store(config);
+        let start = Instant::now();
+        let (wasm, module) = load_main_module(config, &store)?;
+        tracing::info!(
+            module = %config.module.display(),
+            builtin = config.builtin.as_deref(),
+            bytes = wasm.len(),
+            compile_time = ?start.elapsed(),
+            "compiled module"
+        );
+        tracing::debug!(
+            "exports: {}",
+            module
+                .exports()
+                .map(|e| format!("{} ({})", e.name(), imports::describe(e.ty())))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if show_metadata {
+            print_metadata(&wasm, &module);
+        }
+        if let Some(requirements) = &config.require {
+            require::check(&module, requirements)?;
+        }
+        if let Some(limit) = config.max_memory_pages {
+            check_memory_limit(&module, limit)?;
+        }
+        let (import_object, host_env) =
+            imports::host_imports(&mut store, &config.import_namespace, config.time_source);
+        let mut instance = Instance::new(&mut store, &module, &import_object)?;
+        let memory_name = match resolve_memory_name(&instance, config.memory_name.as_deref()) {
+            // modules that only `shade` pixels or draw through the host imports don't need a
+            // memory of their own, so frames are kept in one the runner makes for them
+            Err(RunnerError::MissingExport(_)) if config.memory_name.is_none() => {
+                let memory = Memory::new(&mut store, MemoryType::new(0, None, false))?;
+                instance.exports.insert(HOST_MEMORY_NAME, memory);
+                tracing::debug!("module exports no memory, using one kept by the runner");
+                HOST_MEMORY_NAME.to_string()
+            }
+            result => result?,
+        };
+        let memory = instance.exports.get_memory(&memory_name)?;
+        host_env.as_mut(&mut store).memory = Some(memory.clone());
+
+        for (name, value) in &config.params {
+            params::set_global(&mut store, &instance, name, value)?;
+        }
+
+        // `--reserve-pages` grows memory once here so neither the runner nor the module has to
+        // while ticking, unless it needs even more
+        let reserved = config.reserve_pages.unwrap_or(0) as u64 * wasmer::WASM_PAGE_SIZE as u64;
+        // the runner's own memory is always grown, `--no-grow` is about what modules declare
+        grow_memory(
+            memory,
+            &mut store,
+            bytes_required.max(reserved),
+            config.max_memory_pages,
+            !config.no_grow || memory_name == HOST_MEMORY_NAME,
+        )?;
+
+        let shade_pool = if config.per_pixel && config.per_pixel_threads > 1 {
+            Some(ShadePool::new(
+                &module,
+                store.engine(),
+                &config.import_namespace,
+                &memory_name,
+                &config.params,
+                config.time_source,
+                config.per_pixel_threads,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            store,
+            instance,
+            host_env,
+            memory_name,
+            shade_pool,
+        })
+    }
+}
+
+/// one of a module's exports, with its type, see `WasmDemoRunner::export_kind`
+#[derive(Clone, Debug, PartialEq)]
+enum ExportKind {
+    Function(FunctionType),
+    Global(GlobalType),
+    Memory(MemoryType),
+    Table(TableType),
+}
+
+impl std::fmt::Display for ExportKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExportKind::Function(ty) => write!(f, "function {}", ty),
+            ExportKind::Global(ty) => write!(f, "global {}", ty),
+            ExportKind::Memory(ty) => write!(f, "memory {}", ty),
+            ExportKind::Table(ty) => write!(f, "table {}", ty),
+        }
+    }
+}
+
+// see `WasmDemoRunner::set_frame_callback`
+type FrameCallback = Box<dyn FnMut(&Frame) + Send>;
+
+/// runs a module and publishes the frames it renders, see `run` and `tick_once`
+pub struct WasmDemoRunner {
+    config: Config,
+
+    wasm_store: Store,
+    module_instance: Instance,
+    // state shared with the host functions the module imports
+    host_env: FunctionEnv<HostEnv>,
+    // the exported memory frames are read from
+    memory_name: String,
+
+    // the size last requested by the UI, reapplied when the module is reloaded
+    view_size: Option<(u32, u32)>,
+    width: u32,
+    height: u32,
+    pixel_format: PixelFormat,
+    bytes_required: u64,
+
+    frame_manager: FrameManager,
+    // only used for `--per-pixel` with more than one thread
+    shade_pool: Option<ShadePool>,
+    // the second module of `--diff`, its latest frame and how the two compared so far
+    diff_module: Option<LoadedModule>,
+    diff_frame: Vec<u8>,
+    diff_stats: DiffStats,
+    // `--post`
+    post_pass: Option<PostPass>,
+    // lives outside the frame pool since it persists across frames
+    accumulator: Option<Accumulator>,
+    // averages the sub-frames of modules that export `subframes`
+    subframe_average: Accumulator,
+    auto_exposure: Option<AutoExposure>,
+    profile: Option<Profile>,
+    growth_warning: GrowthWarning,
+    // what the last `tick` returned, for modules whose `tick` returns an i32
+    tick_status: Option<i32>,
+    // the partially complete frame in `--scanlines` mode
+    scanline_canvas: Vec<u8>,
+    // reused by `--layout tiled` to rearrange frames
+    layout_scratch: Vec<u8>,
+    metrics: Metrics,
+    // which of the module's debug buffers are published along with frames, by index
+    shown_debug_buffers: BTreeSet<usize>,
+    // read from the module every tick in `Indexed8`
+    palette: Arc<[[u8; 4]]>,
+    hash_log: Option<HashLog>,
+    hash_comparison: Option<HashComparison>,
+    verify_dir: Option<VerifyDir>,
+    histogram_log: Option<HistogramLog>,
+    png_sequence: Option<PngSequence>,
+    change_capture: Option<ChangeCapture>,
+    replay: Option<Replay>,
+    // number of ticks completed so far
+    ticks: u64,
+    tick_interval: Duration,
+    // `--adaptive-fps`, moving `tick_interval` with how long ticks take
+    adaptive_fps: Option<AdaptiveFps>,
+    // `--external-clock`, pacing ticks instead of `tick_interval`
+    external_clock: Option<ExternalClock>,
+    // under `--frame-limit-fps-from-audio`, the frame of the track the last tick rendered
+    #[cfg(feature = "audio")]
+    audio_frame: Option<u64>,
+    // what the run loop's pacing reads the time from and sleeps on
+    clock: Box<dyn Clock>,
+    // called with every frame as it's published, see `set_frame_callback`
+    frame_callback: Option<FrameCallback>,
+    // set by `Command::Step` to run one tick while paused
+    step_requested: bool,
+    // set by `Command::Vblank`, cleared by the tick it lets through
+    vblank_pending: bool,
+    // clicks received since the last tick, handed to the module's `on_click` before the next
+    pending_clicks: Vec<(i32, i32, i32)>,
+    // the first module followed by the rest of the playlist, and which of them is running
+    playlist: Vec<PathBuf>,
+    playlist_position: usize,
+    // `--watch` reloads the module when its file's modification time changes from this
+    watched_modified: Option<SystemTime>,
+    last_watch_check: Instant,
+
+    state: State,
+}
+
+/// where the runner sends the frames it renders
+trait FrameSink {
+    /// hands over a finished frame. returns false once frames can't be delivered anymore, which
+    /// stops the runner.
+    fn publish(&mut self, published: PublishedFrame) -> bool;
+    /// asks whatever displays frames to close, e.g. because the module is done
+    fn close(&mut self);
+    /// tells whatever displays frames that the runner stopped because of `message`. the error
+    /// is printed to stderr as well, so sinks without anywhere better to show it can ignore it.
+    fn report_error(&mut self, _message: String) {}
+}
+
+impl FrameSink for ExtEventSink {
+    fn publish(&mut self, published: PublishedFrame) -> bool {
+        // the only way submitting fails is if the app has shut down
+        self.submit_command(FRAME_PUBLISHED, published, Target::Auto)
+            .is_ok()
+    }
+
+    fn close(&mut self) {
+        let _ = self.submit_command(druid::commands::QUIT_APP, (), Target::Global);
+    }
+
+    fn report_error(&mut self, message: String) {
+        let _ = self.submit_command(RUNNER_FAILED, message, Target::Auto);
+    }
+}
+
+/// requests sent from the UI to the runner thread, handled between ticks
+#[derive(Debug)]
+pub enum Command {
+    /// the frame view changed size; only honored by modules exporting `resize`
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    /// a slider bound to one of the module's `f32` globals moved
+    SetGlobal {
+        name: String,
+        value: f32,
+    },
+    /// stop ticking, while still handling commands
+    Pause,
+    Resume,
+    /// run a single tick while paused
+    Step,
+    /// save the most recent frame as a PNG
+    Screenshot(PathBuf),
+    /// change how many ticks are run per second
+    SetFps(f64),
+    /// the display refreshed; under `--vsync` this is what paces ticks
+    Vblank,
+    /// switch the format frames are read in
+    SetPixelFormat(PixelFormat),
+    /// switch to one of the module's scenes, by index
+    SetScene(i32),
+    /// show or hide one of the module's debug buffers, by index
+    ToggleDebugBuffer(usize),
+    /// hold the time `now_ms` reports at this many milliseconds, or with `None` let it run again
+    /// from there
+    ScrubTime(Option<f64>),
+    /// switch to the next or previous module of the playlist, wrapping around at either end
+    NextModule,
+    PreviousModule,
+    /// a mouse button went down over the frame, at these frame pixel coordinates. `button` is 0
+    /// for the left button, 1 for the middle and 2 for the right, like the DOM.
+    Click {
+        x: i32,
+        y: i32,
+        button: i32,
+    },
+}
+
+#[derive(Debug)]
+enum State {
+    Idle,
+    Running,
+}
+
+/// what `FrameManager` does when every frame in its pool is in use
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PoolPolicy {
+    /// fail to produce a frame
+    Fixed,
+    /// allocate another frame, up to `--max-pool-size`
+    Grow,
+}
+
+#[derive(Debug)]
+struct FrameManager {
+    size: usize,
+    max_frames: usize,
+    frames: Vec<Frame>,
+    last_updated: Option<Frame>,
+}
+
+impl FrameManager {
+    // starts out with five frames; `max_frames` only matters when it's larger than that
+    fn new(size: usize, max_frames: usize) -> Self {
+        Self {
+            size,
+            max_frames,
+            last_updated: None,
+            frames: vec![
+                Frame::new(size),
+                Frame::new(size),
+                Frame::new(size),
+                Frame::new(size),
+                Frame::new(size),
+            ],
+        }
+    }
+
+    // frames held outside the pool, by the UI or the runner
+    fn in_use(&self) -> usize {
+        self.frames.iter().filter(|f| Frame::count(f) > 1).count()
+    }
+
+    // drops free frames until at most `size` are left, keeping those in use
+    fn trim(&mut self, size: usize) {
+        let mut excess = self.frames.len().saturating_sub(size);
+        self.frames.retain(|f| {
+            let drop = excess > 0 && Frame::count(f) == 1;
+            excess -= drop as usize;
+            !drop
+        });
+    }
+
+    fn get_free_frame(&mut self) -> std::result::Result<Frame, Box<dyn std::error::Error>> {
+        if let Some(frame) = self.frames.iter().find(|f| Frame::count(f) == 1) {
+            return Ok(frame.clone());
+        }
+
+        if self.frames.len() >= self.max_frames {
+            return Err("couldn't find free frame".into());
+        }
+        let frame = Frame::new(self.size);
+        self.frames.push(frame.clone());
+        Ok(frame)
+    }
+}
+
+/// a frame of pixels in the runner's pixel format, shared between the runner, which reuses it
+/// once it's the last one holding it, and whatever it was published to
+#[derive(Debug)]
+pub struct Frame {
+    ptr: NonNull<InnerFrame>,
+    phantom: PhantomData<InnerFrame>,
+}
+
+// following the rustinomicon guide for implementing Arc: https://doc.rust-lang.org/nomicon/arc-mutex/arc-base.html
+//
+// the goal is to satisfy the constraints on image::Handle::from_pixels:
+//      impl AsRef<[u8]> + Send + Sync + 'static,
+// unfortunately I can't just wrap a Vec<u8> in Arc<Mutex<T>> because of the AsRef<[u8]> constraint
+// and I haven't been able to figure out how to return &[u8] from a type protected by Arc<Mutex<T>>
+//
+// this is ultimately intended to serve the purpose of not allocating a new Vec<u8> every time i
+// want to pass a wasm-generated pixel buffer to the iced library
+impl Frame {
+    fn new(size: usize) -> Self {
+        Self::from_vec(vec![0; size])
+    }
+
+    // builds a frame holding a copy of `bytes`, for synthetic frames that don't come from a
+    // module's memory
+    #[cfg(test)]
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self::from_vec(bytes.to_vec())
+    }
+
+    fn from_vec(buf: Vec<u8>) -> Self {
+        let boxed = Box::new(InnerFrame {
+            // the reference count starts here at 1 since this is the first pointer to this new
+            // data
+            rc: atomic::AtomicUsize::new(1),
+            seq: atomic::AtomicUsize::new(0),
+            buf,
+            lock: Mutex::new(()),
+        });
+
+        Self {
+            // `.unwrap()` is okay here since the pointer returned by `Box::into_raw` is guaranteed
+            // not to be null
+            ptr: NonNull::new(Box::into_raw(boxed)).unwrap(),
+            phantom: PhantomData,
+        }
+    }
+
+    fn count(this: &Self) -> usize {
+        this.inner().rc.load(Ordering::Acquire)
+    }
+
+    // 64 bit FNV-1a over the frame's bytes. cheap enough to run every frame and stable across
+    // runs, which is all that's needed to spot nondeterminism or drift.
+    fn checksum(&self) -> u64 {
+        self.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+
+    // per channel counts of the frame's display values, see `histogram::compute`
+    fn histogram(&self, width: u32, format: PixelFormat, palette: &[[u8; 4]]) -> Histogram {
+        histogram::compute(self, width as usize, format, palette)
+    }
+
+    // composites this 8 bit RGBA frame over `bottom` into `out`, see `blend::blend_over`. `out`
+    // may not be either of the other two, since modifying it locks its buffer
+    fn blend_over(
+        &self,
+        bottom: &Frame,
+        alpha: f32,
+        out: &mut Frame,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        out.modify(|buf| blend::blend_over(self, bottom, alpha, buf))
+    }
+
+    fn inner(&self) -> &InnerFrame {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    // gives exclusive access to the frame's buffer, e.g. for post-processing after a copy. the
+    // sequence count is odd for as long as `f` runs, see `read`
+    fn modify<R>(
+        &mut self,
+        f: impl FnOnce(&mut [u8]) -> R,
+    ) -> std::result::Result<R, Box<dyn std::error::Error>> {
+        let inner = unsafe { self.ptr.as_mut() };
+        let _guard = inner.lock.lock()?;
+        inner.seq.fetch_add(1, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+        let result = f(inner.buf.as_mut_slice());
+        inner.seq.fetch_add(1, Ordering::Release);
+        Ok(result)
+    }
+
+    // copies the frame out of the module's memory, starting `offset` bytes in. rows of
+    // `row_len` bytes start `pitch` bytes apart in memory, and are packed together in the frame.
+    fn copy_from_memory(
+        &mut self,
+        view: MemoryView,
+        offset: u64,
+        row_len: usize,
+        pitch: usize,
+    ) -> Result<(), RunnerError> {
+        if pitch == row_len || row_len == 0 {
+            let need = offset + self.len() as u64;
+            let have = view.data_size();
+            if have < need {
+                return Err(RunnerError::MemoryTooSmall { have, need });
+            }
+            self.modify(|buf| view.read(offset, buf))??;
+            return Ok(());
+        }
+
+        let rows = self.len() / row_len;
+        let need = offset + (pitch * rows.saturating_sub(1) + row_len) as u64;
+        let have = view.data_size();
+        if have < need {
+            return Err(RunnerError::MemoryTooSmall { have, need });
+        }
+        self.modify(|buf| {
+            buf.chunks_exact_mut(row_len)
+                .enumerate()
+                .try_for_each(|(y, row)| view.read(offset + (y * pitch) as u64, row))
+        })??;
+        Ok(())
+    }
+
+    // replaces the frame's bytes with `src`, for frames that don't come from a module's memory.
+    // `src` must be exactly as long as the frame.
+    fn copy_from_slice(
+        &mut self,
+        src: &[u8],
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if src.len() != self.len() {
+            return Err(format!(
+                "can't copy {} bytes into a frame of {} bytes",
+                src.len(),
+                self.len()
+            )
+            .into());
+        }
+        self.modify(|buf| buf.copy_from_slice(src))
+    }
+
+    // runs `f` on the frame's bytes without blocking the writer, running it again until the
+    // frame wasn't written to while it ran, so `f`'s result never comes from a torn frame. the
+    // pool only hands out frames nothing else holds so in practice that never happens; this
+    // catches it if it ever does.
+    fn read<R>(&self, mut f: impl FnMut(&[u8]) -> R) -> R {
+        let inner = self.inner();
+        loop {
+            let before = inner.seq.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let result = f(inner.buf.as_slice());
+            atomic::fence(Ordering::Acquire);
+            if inner.seq.load(Ordering::Relaxed) == before {
+                return result;
+            }
+            tracing::debug!("frame was written while being read, reading it again");
+        }
+    }
+}
+
+// Frame is Send because access to mutable state is enforced internally with an atomic reference
+// count.
+unsafe impl Send for Frame {}
+// Frame is Sync because we ensure nothing stored in a &Frame can be written to while that same
+// thing could be read or written to from another &Frame -- enforced using atomic reference count.
+unsafe impl Sync for Frame {}
+
+impl Deref for Frame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        let inner = unsafe { self.ptr.as_ref() };
+        &inner.buf.as_slice()
+    }
+}
+
+impl Clone for Frame {
+    fn clone(&self) -> Self {
+        let inner = unsafe { self.ptr.as_ref() };
+
+        // relaxed ordering is okay here since we don't need to modify or access the inner data and
+        // therefore don't need atomic synchronization
+        let old_rc = inner.rc.fetch_add(1, Ordering::Relaxed);
+
+        if old_rc >= isize::MAX as usize {
+            std::process::abort();
+        }
+
+        Self {
+            ptr: self.ptr,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        let inner = unsafe { self.ptr.as_ref() };
+        if inner.rc.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+        unsafe { Box::from_raw(self.ptr.as_ptr()) };
+    }
+}
+
+impl AsRef<[u8]> for Frame {
+    fn as_ref(&self) -> &[u8] {
+        &self
+    }
+}
+
+#[derive(Debug)]
+struct InnerFrame {
+    lock: Mutex<()>,
+    rc: atomic::AtomicUsize,
+    // a seqlock: incremented before and after every write to `buf`
+    seq: atomic::AtomicUsize,
+    buf: Vec<u8>,
+}
+
+impl WasmDemoRunner {
+    /// loads and instantiates the module `config` names, ready to tick
+    pub fn new(config: &Config) -> Result<Self, RunnerError> {
+        let (width, height) = default_size(config);
+        let pixel_format = config.pixel_format;
+        let bytes_required = pixel_format.frame_len(width as usize, height as usize) as u64;
+        let loaded = LoadedModule::load(config, bytes_required, config.show_metadata)?;
+        let adaptive_fps = config
+            .adaptive_fps
+            .map(|range| AdaptiveFps::new(range, 1.0 / TICK_INTERVAL.as_secs_f64()));
+
+        let mut runner = Self {
+            config: config.clone(),
+            wasm_store: loaded.store,
+            module_instance: loaded.instance,
+            host_env: loaded.host_env,
+            memory_name: loaded.memory_name,
+            view_size: None,
+            width,
+            height,
+            pixel_format,
+            bytes_required,
+            frame_manager: FrameManager::new(bytes_required as usize, config.max_frames()),
+            shade_pool: loaded.shade_pool,
+            diff_module: match config.diff.get(1) {
+                Some(path) => {
+                    let config = Config {
+                        module: path.clone(),
+                        ..config.clone()
+                    };
+                    Some(LoadedModule::load(&config, bytes_required, false)?)
+                }
+                None => None,
+            },
+            diff_frame: Vec::new(),
+            diff_stats: DiffStats::default(),
+            post_pass: config
+                .post
+                .as_deref()
+                .map(|path| PostPass::load(config, path))
+                .transpose()?,
+            accumulator: config.accumulate.map(Accumulator::new),
+            subframe_average: Accumulator::new(1),
+            auto_exposure: config.auto_exposure.then(AutoExposure::new),
+            profile: config.profile.then(Profile::default),
+            growth_warning: GrowthWarning::default(),
+            tick_status: None,
+            scanline_canvas: Vec::new(),
+            layout_scratch: Vec::new(),
+            metrics: Metrics::new(config.jitter_threshold),
+            shown_debug_buffers: BTreeSet::new(),
+            palette: Arc::new([]),
+            hash_log: config.hash_log.as_deref().map(HashLog::open).transpose()?,
+            hash_comparison: config
+                .compare_log
+                .as_deref()
+                .map(HashComparison::load)
+                .transpose()?,
+            verify_dir: config
+                .verify_dir
+                .as_deref()
+                .map(|dir| VerifyDir::open(dir, config.golden_tolerance))
+                .transpose()?,
+            histogram_log: config
+                .histogram_json
+                .as_deref()
+                .map(HistogramLog::create)
+                .transpose()?,
+            png_sequence: config
+                .png_sequence
+                .as_deref()
+                .map(PngSequence::new)
+                .transpose()?,
+            replay: config
+                .replay_dir
+                .as_deref()
+                .map(|dir| Replay::new(dir, config.replay_frames)),
+            change_capture: config
+                .capture_on_change
+                .as_deref()
+                .map(|dir| ChangeCapture::new(dir, config.max_captures))
+                .transpose()?,
+            ticks: 0,
+            // the default rate may be outside the adaptive range
+            tick_interval: adaptive_fps
+                .as_ref()
+                .map_or(TICK_INTERVAL, AdaptiveFps::interval),
+            adaptive_fps,
+            external_clock: config.external_clock.map(ExternalClock::bind).transpose()?,
+            #[cfg(feature = "audio")]
+            audio_frame: None,
+            clock: Box::new(RealClock),
+            frame_callback: None,
+            step_requested: false,
+            vblank_pending: false,
+            pending_clicks: Vec::new(),
+            playlist: std::iter::once(config.module.clone())
+                .chain(config.playlist.iter().cloned())
+                .collect(),
+            playlist_position: 0,
+            watched_modified: module_modified(config),
+            last_watch_check: Instant::now(),
+            state: State::Running,
+        };
+
+        runner.update_frame_layout();
+        // without a window nothing else asks for the internal resolution
+        if let Some((width, height)) = config.internal_res {
+            runner.resize(width, height)?;
+        }
+        Ok(runner)
+    }
+
+    fn run(
+        &mut self,
+        sink: &mut dyn FrameSink,
+        commands: Receiver<Command>,
+        stdin_commands: Option<Receiver<Command>>,
+    ) -> Result<(), RunnerError> {
+        // whichever thread calls this produces the frames
+        #[cfg(feature = "affinity")]
+        if let Some(core) = self.config.pin_core {
+            match affinity::pin_current_thread(core) {
+                Ok(()) => tracing::info!("pinned the runner thread to core {}", core),
+                Err(e) => eprintln!("failed to pin the runner thread: {}", e),
+            }
+        }
+
+        #[cfg(feature = "audio")]
+        if let Some(clock) = &self.host_env.as_ref(&self.wasm_store).audio {
+            clock.start();
+        }
+
+        let mut result = self.run_loop(sink, &commands, stdin_commands.as_ref());
+        if let Err(e) = &result {
+            eprintln!("wasm runner stopped: {}", e);
+            sink.report_error(e.to_string());
+            if let Some(replay) = &self.replay {
+                match replay.dump(&e.to_string()) {
+                    Ok(dir) => eprintln!("wrote the frames before the error to {}", dir.display()),
+                    Err(e) => eprintln!("failed to write the replay: {}", e),
+                }
+            }
+        }
+        if let Some(log) = &mut self.hash_log {
+            if let Err(e) = log.flush() {
+                eprintln!("failed to write hash log: {}", e);
+            }
+        }
+        if let Some(log) = &mut self.histogram_log {
+            if let Err(e) = log.flush() {
+                eprintln!("failed to write histograms: {}", e);
+            }
+        }
+        if let Some(path) = &self.config.export_svg {
+            let drawing = &self.host_env.as_ref(&self.wasm_store).drawing;
+            if let Err(e) = drawing.write_svg(path, self.width, self.height) {
+                eprintln!("failed to write {}: {}", path.display(), e);
+            }
+        }
+        if let Some(comparison) = &self.hash_comparison {
+            result = result.and_then(|()| {
+                let compared = comparison.finish()?;
+                if !self.config.quiet {
+                    println!("all {} compared frames matched", compared);
+                }
+                Ok(())
+            });
+        }
+        if let Some(verify) = &self.verify_dir {
+            result = result.and_then(|()| {
+                let compared = verify.finish()?;
+                if !self.config.quiet {
+                    println!("all {} frames match their golden images", compared);
+                }
+                Ok(())
+            });
+        }
+        if !self.config.quiet {
+            self.metrics.report();
+            self.diff_stats.report();
+        }
+        if let Some(profile) = &self.profile {
+            profile.report();
+        }
+        self.shutdown();
+        result
+    }
+
+    fn run_loop(
+        &mut self,
+        sink: &mut dyn FrameSink,
+        commands: &Receiver<Command>,
+        stdin_commands: Option<&Receiver<Command>>,
+    ) -> Result<(), RunnerError> {
+        // with an external clock even the first tick waits for its pulse
+        if self.external_clock.is_some() && !self.wait(commands)? {
+            return Ok(());
+        }
+        loop {
+            loop {
+                match commands.try_recv() {
+                    Ok(command) => self.handle_command(command)?,
+                    Err(TryRecvError::Empty) => break,
+                    // the UI has gone away
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+            // stdin gets a channel of its own since the thread reading it can't be stopped, so
+            // it would otherwise keep the runner alive after the UI had gone away. closing stdin
+            // doesn't stop the runner.
+            if let Some(stdin_commands) = stdin_commands {
+                while let Ok(command) = stdin_commands.try_recv() {
+                    self.handle_command(command)?;
+                }
+            }
+
+            if self.config.watch {
+                self.watch();
+            }
+
+            let step = std::mem::take(&mut self.step_requested);
+            if matches!(self.state, State::Idle) && !step {
+                if !self.wait(commands)? {
+                    return Ok(());
+                }
+                continue;
+            }
+
+            let started = self.clock.now();
+            let frame = self.tick_once()?;
+            self.adapt_fps(self.clock.now().saturating_duration_since(started));
+
+            if let Some(path) = &self.config.dump_memory {
+                if self.ticks == self.config.dump_after_tick {
+                    if let Err(e) = self.dump_memory(path) {
+                        eprintln!("failed to dump memory to {}: {}", path.display(), e);
+                    }
+                }
+            }
+
+            let frame_index = self.ticks - 1;
+            let checksum = frame.checksum();
+            if let Some(interval) = self.config.checksum_interval {
+                if frame_index.is_multiple_of(interval) {
+                    println!("{}: {:016x}", frame_index, checksum);
+                }
+            }
+            if let Some(log) = &mut self.hash_log {
+                log.record(frame_index, checksum)?;
+            }
+            if let Some(comparison) = &mut self.hash_comparison {
+                comparison.compare(frame_index, checksum);
+            }
+            if let Some(log) = &mut self.histogram_log {
+                log.record(
+                    frame_index,
+                    &frame.histogram(self.width, self.pixel_format, &self.palette),
+                )?;
+            }
+
+            let published = PublishedFrame {
+                index: frame_index,
+                frame,
+                width: self.width,
+                height: self.height,
+                format: self.pixel_format,
+                palette: self.palette.clone(),
+                color_key: self.color_key()?,
+                premultiplied: self.config.premultiplied,
+                status: self.tick_status,
+                memory_size: self
+                    .module_instance
+                    .exports
+                    .get_memory(&self.memory_name)?
+                    .view(&self.wasm_store)
+                    .data_size(),
+                debug_buffers: debug_buffers::read(
+                    &mut self.wasm_store,
+                    &self.module_instance,
+                    &self.memory_name,
+                    &self.shown_debug_buffers,
+                    (self.width, self.height),
+                )?
+                .into(),
+                published_at: Instant::now(),
+            };
+            if let Some(replay) = &mut self.replay {
+                replay.record(&published, checksum, self.config.dither);
+            }
+            if let Some(capture) = &mut self.change_capture {
+                capture.record(&published, checksum, self.config.dither)?;
+            }
+            if let Some(sequence) = &mut self.png_sequence {
+                sequence.write(&published, self.config.dither)?;
+            }
+            if let Some(path) = &self.config.golden {
+                if frame_index == self.config.golden_frame {
+                    golden::assert_published_matches(
+                        &published,
+                        self.config.dither,
+                        path,
+                        self.config.golden_tolerance,
+                    )?;
+                    if !self.config.quiet {
+                        println!("frame {} matches {}", frame_index, path.display());
+                    }
+                }
+            }
+            if let Some(verify) = &mut self.verify_dir {
+                verify.check(&published, self.config.dither)?;
+            }
+            if let Some(callback) = &mut self.frame_callback {
+                callback(&published.frame);
+            }
+            if !sink.publish(published) {
+                return Ok(());
+            }
+            self.metrics.record_frame(self.clock.now());
+
+            // a hard cap so runaway modules can't keep CI jobs alive, and `--verify-dir` has
+            // nothing left to check once it runs out of images
+            let verified = self
+                .verify_dir
+                .as_ref()
+                .is_some_and(|verify| verify.is_complete(self.ticks));
+            if verified
+                || self
+                    .config
+                    .frame_limit
+                    .is_some_and(|limit| self.ticks >= limit)
+            {
+                sink.close();
+                return Ok(());
+            }
+
+            // a finished module hands over to the next one in the playlist, if there is one
+            if self.is_done()? && !self.advance_playlist() {
+                if self.config.close_on_done {
+                    sink.close();
+                }
+                return Ok(());
+            }
+
+            if !self.wait(commands)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // under `--adaptive-fps`, moves the tick rate with how long the last tick took
+    fn adapt_fps(&mut self, frame_time: Duration) {
+        let Some(adaptive) = &mut self.adaptive_fps else {
+            return;
+        };
+        if let Some(fps) = adaptive.record(frame_time) {
+            tracing::debug!("ticks took {:?}, now ticking at {:.1} fps", frame_time, fps);
+            self.tick_interval = adaptive.interval();
+        }
+    }
+
+    // waits until the next tick is due: for the tick interval, under `--vsync` until the UI
+    // reports a display refresh, or under `--external-clock` until the next pulse, handling other
+    // commands as they arrive. returns false if the UI or the clock has gone away in the meantime.
+    fn wait(&mut self, commands: &Receiver<Command>) -> Result<bool, RunnerError> {
+        if self.external_clock.is_some() {
+            return self.wait_for_pulse(commands);
+        }
+        #[cfg(feature = "audio")]
+        if self.config.frame_limit_fps_from_audio {
+            return self.wait_for_audio(commands);
+        }
+        if !self.config.vsync {
+            self.clock.sleep(self.tick_interval);
+            return Ok(true);
+        }
+        // refreshes that arrive while a tick is running collapse into one
+        while !std::mem::take(&mut self.vblank_pending) {
+            match commands.recv() {
+                Ok(command) => self.handle_command(command)?,
+                Err(_) => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    fn wait_for_pulse(&mut self, commands: &Receiver<Command>) -> Result<bool, RunnerError> {
+        loop {
+            loop {
+                match commands.try_recv() {
+                    Ok(command) => self.handle_command(command)?,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(false),
+                }
+            }
+            let Some(clock) = &self.external_clock else {
+                return Ok(true);
+            };
+            match clock.wait(CLOCK_POLL_INTERVAL) {
+                Pulse::Received => return Ok(true),
+                Pulse::TimedOut => {}
+                Pulse::Stopped => return Ok(false),
+            }
+        }
+    }
+
+    // waits until playback reaches a frame past the one last rendered, so the frame shown always
+    // follows the track. frames it went past while the last tick ran are skipped.
+    #[cfg(feature = "audio")]
+    fn wait_for_audio(&mut self, commands: &Receiver<Command>) -> Result<bool, RunnerError> {
+        loop {
+            loop {
+                match commands.try_recv() {
+                    Ok(command) => self.handle_command(command)?,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(false),
+                }
+            }
+            let Some(clock) = &self.host_env.as_ref(&self.wasm_store).audio else {
+                self.clock.sleep(self.tick_interval);
+                return Ok(true);
+            };
+            let fps = 1.0 / self.tick_interval.as_secs_f64();
+            let position = clock.position();
+            let due = audio::AudioClock::frame_at(position, fps);
+            match self.audio_frame {
+                Some(last) if due <= last => {
+                    let next_frame = Duration::from_secs_f64((last + 1) as f64 / fps);
+                    let until_due = next_frame.saturating_sub(position);
+                    self.clock.sleep(until_due.min(CLOCK_POLL_INTERVAL));
+                }
+                last => {
+                    if let Some(last) = last.filter(|last| due > last + 1) {
+                        tracing::debug!(
+                            "skipping {} frames to keep up with the audio",
+                            due - last - 1
+                        );
+                    }
+                    self.audio_frame = Some(due);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    // fn title(&self) -> String {
+    //     String::from("WebAssembly Demo Runner")
+    // }
+
+    // fn view(&self) -> Element<Self::Message> {
+    //     let center: Element<Self::Message> = match &self.frame_manager.last_updated {
+    //         Some(frame) => {
+    //             let image_handle =
+    //                 image::Handle::from_pixels(self.width, self.height, frame.clone());
+    //             image::Viewer::new(image_handle)
+    //                 .width(self.width as f32)
+    //                 .height(self.height as f32)
+    //                 .into()
+    //         }
+    //         None => text("missing frame!").into(),
+    //     };
+    //     let c = column![text("hello"), center, text("meow")];
+    //     container(c).center_x().center_y().into()
+    // }
+
+    // fn subscription(&self) -> Subscription<Self::Message> {
+    //     match self.state {
+    //         State::Running => time::every(Duration::from_millis(10)).map(Message::Tick),
+    //         State::Idle => Subscription::none(),
+    //     }
+    // }
+}
+
+impl WasmDemoRunner {
+    // calls the module's optional `shutdown` export; called once after the run loop exits so
+    // modules can release resources. failures are logged since there's nothing left to stop.
+    fn shutdown(&mut self) {
+        let shutdown = match self.optional_function::<(), ()>("shutdown") {
+            Ok(Some(shutdown)) => shutdown,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("module exports an unusable shutdown function: {}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = shutdown.call(&mut self.wasm_store) {
+            tracing::warn!("module trapped during shutdown: {}", e);
+        }
+    }
+
+    // paces the run loop by `clock` instead of the real time, e.g. a `MockClock` to step through
+    // it without waiting. should be set before running since the time it last checked for
+    // changes to the module file is kept.
+    #[cfg(test)]
+    fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.last_watch_check = clock.now();
+        self.clock = Box::new(clock);
+    }
+
+    /// calls `callback` with every frame the runner publishes, right before it goes to the sink,
+    /// for handling frames some other way than displaying them, e.g. sending or encoding them
+    pub fn set_frame_callback(&mut self, callback: impl FnMut(&Frame) + Send + 'static) {
+        self.frame_callback = Some(Box::new(callback));
+    }
+
+    // makes `audio_time` follow `clock` rather than the wall clock
+    #[cfg(feature = "audio")]
+    fn attach_audio(&mut self, clock: audio::AudioClock) {
+        self.host_env.as_mut(&mut self.wasm_store).audio = Some(clock);
+    }
+
+    // sliders for the module's `ui_` globals
+    fn ui_sliders(&mut self) -> Vec<GlobalSlider> {
+        sliders::discover(&mut self.wasm_store, &self.module_instance)
+    }
+
+    fn description(&mut self) -> Result<Option<Description>, RunnerError> {
+        Description::read(
+            &mut self.wasm_store,
+            &self.module_instance,
+            &self.memory_name,
+        )
+    }
+
+    fn scenes(&mut self) -> Result<Vec<Scene>, RunnerError> {
+        scenes::discover(
+            &mut self.wasm_store,
+            &self.module_instance,
+            &self.memory_name,
+        )
+    }
+
+    // what the module exports under `name` and its type, or `None` if it exports nothing by that
+    // name
+    fn export_kind(&self, name: &str) -> Option<ExportKind> {
+        export_kind(&self.wasm_store, &self.module_instance, name)
+    }
+
+    // looks up an export the module may choose not to provide
+    fn optional_function<Args, Rets>(
+        &self,
+        name: &str,
+    ) -> Result<Option<TypedFunction<Args, Rets>>, RunnerError>
+    where
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        optional_function(&self.wasm_store, &self.module_instance, name)
+    }
+
+    fn memory_pages(&self) -> Result<Pages, RunnerError> {
+        Ok(self
+            .module_instance
+            .exports
+            .get_memory(&self.memory_name)?
+            .view(&self.wasm_store)
+            .size())
+    }
+
+    // finite modules export `is_done` and return nonzero from it once they've finished
+    fn is_done(&mut self) -> Result<bool, RunnerError> {
+        match self.optional_function::<(), i32>("is_done")? {
+            Some(is_done) => Ok(is_done.call(&mut self.wasm_store)? != 0),
+            None => Ok(false),
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) -> Result<(), RunnerError> {
+        match command {
+            Command::Resize { width, height } => self.resize(width, height),
+            Command::SetGlobal { name, value } => {
+                match params::set_f32_global(
+                    &mut self.wasm_store,
+                    &self.module_instance,
+                    &name,
+                    value,
+                ) {
+                    // the sliders are those of the first module in the playlist, which later
+                    // ones needn't share
+                    Err(RunnerError::MissingExport(e)) if self.playlist.len() > 1 => {
+                        tracing::debug!("ignoring slider for {}: {}", name, e);
+                        Ok(())
+                    }
+                    result => result,
+                }
+            }
+            Command::Pause => {
+                self.state = State::Idle;
+                Ok(())
+            }
+            Command::Resume => {
+                self.state = State::Running;
+                Ok(())
+            }
+            Command::Step => {
+                self.step_requested = true;
+                Ok(())
+            }
+            Command::Screenshot(path) => {
+                // a failed screenshot isn't worth stopping the module for
+                if let Err(e) = self.screenshot(&path) {
+                    eprintln!("failed to save screenshot to {}: {}", path.display(), e);
+                }
+                Ok(())
+            }
+            Command::SetFps(fps) => {
+                self.tick_interval = match &mut self.adaptive_fps {
+                    Some(adaptive) => {
+                        adaptive.set_fps(fps);
+                        adaptive.interval()
+                    }
+                    None => Duration::from_secs_f64(1.0 / fps),
+                };
+                Ok(())
+            }
+            Command::Vblank => {
+                self.vblank_pending = true;
+                Ok(())
+            }
+            Command::SetPixelFormat(format) => self.set_pixel_format(format),
+            Command::SetScene(index) => self.set_scene(index),
+            Command::ToggleDebugBuffer(index) => {
+                if !self.shown_debug_buffers.remove(&index) {
+                    self.shown_debug_buffers.insert(index);
+                }
+                Ok(())
+            }
+            Command::ScrubTime(ms) => {
+                let time = &mut self.host_env.as_mut(&mut self.wasm_store).time;
+                match ms {
+                    Some(ms) => time.scrub_to(ms),
+                    None => time.resume(),
+                }
+                Ok(())
+            }
+            Command::NextModule => {
+                self.switch_module(1);
+                Ok(())
+            }
+            Command::Click { x, y, button } => {
+                self.pending_clicks.push((x, y, button));
+                Ok(())
+            }
+            Command::PreviousModule => {
+                self.switch_module(self.playlist.len() - 1);
+                Ok(())
+            }
+        }
+    }
+
+    // moves on to the next module of the playlist once the current one is done. returns false if
+    // it was the last one, or the next one couldn't be loaded.
+    fn advance_playlist(&mut self) -> bool {
+        if self.playlist_position + 1 >= self.playlist.len() {
+            return false;
+        }
+        self.switch_module(1)
+    }
+
+    // replaces the running module with the one `offset` places further along the playlist,
+    // wrapping around. like `--watch`, a module that fails to load is logged and the previous one
+    // keeps running. returns whether the switch happened.
+    fn switch_module(&mut self, offset: usize) -> bool {
+        if self.playlist.len() < 2 {
+            return false;
+        }
+        let position = (self.playlist_position + offset) % self.playlist.len();
+        let previous = std::mem::replace(&mut self.config.module, self.playlist[position].clone());
+
+        let result = self.load_at_default_size().and_then(|loaded| {
+            // unlike a reload, the module being replaced is done for good
+            self.shutdown();
+            self.replace_instance(loaded)
+        });
+        match result {
+            Ok(()) => {
+                self.playlist_position = position;
+                self.watched_modified = module_modified(&self.config);
+                self.tick_status = None;
+                if let Some(exposure) = &mut self.auto_exposure {
+                    exposure.reset();
+                }
+                tracing::info!("switched to {}", self.config.module.display());
+                true
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "failed to load {}, keeping the previous module: {}",
+                    self.config.module.display(),
+                    e
+                );
+                self.config.module = previous;
+                false
+            }
+        }
+    }
+
+    // calls the module's `on_click(x, y, button)` for every click since the last tick, in the
+    // order they happened. modules without it don't get them.
+    fn deliver_clicks(&mut self) -> Result<(), RunnerError> {
+        if self.pending_clicks.is_empty() {
+            return Ok(());
+        }
+        let clicks = std::mem::take(&mut self.pending_clicks);
+        let Some(on_click) = self.optional_function::<(i32, i32, i32), ()>("on_click")? else {
+            return Ok(());
+        };
+        for (x, y, button) in clicks {
+            on_click.call(&mut self.wasm_store, x, y, button)?;
+        }
+        Ok(())
+    }
+
+    // scenes the module doesn't have are ignored, like number keys past the last one
+    fn set_scene(&mut self, index: i32) -> Result<(), RunnerError> {
+        let Some(set_scene) = self.optional_function::<i32, ()>("set_scene")? else {
+            return Ok(());
+        };
+        let count = match self.optional_function::<(), i32>("scene_count")? {
+            Some(count) => count.call(&mut self.wasm_store)?,
+            None => return Ok(()),
+        };
+        if !(0..count).contains(&index) {
+            return Ok(());
+        }
+        set_scene.call(&mut self.wasm_store, index)?;
+        // frames from the previous scene shouldn't bleed into the new one
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.reset();
+        }
+        Ok(())
+    }
+
+    // modules that don't export `resize` keep rendering at their fixed size and the UI letterboxes
+    // their frames into the window
+    // stereo modules are told the size of a single eye
+    // with `--internal-res` the module renders at that size whatever the view's, and the UI scales
+    // its frames up or down to fit
+    fn resize(&mut self, view_width: u32, height: u32) -> Result<(), RunnerError> {
+        let (view_width, height) = self.config.internal_res.unwrap_or((view_width, height));
+        self.view_size = Some((view_width, height));
+        let (eye_width, width) = match self.config.stereo {
+            Some(mode) => (mode.eye_width(view_width), mode.eye_width(view_width) * 2),
+            None => (view_width, view_width),
+        };
+        if width == 0 || height == 0 || (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+
+        let Some(resize) = self.optional_function::<(i32, i32), ()>("resize")? else {
+            return Ok(());
+        };
+        resize.call(&mut self.wasm_store, eye_width as i32, height as i32)?;
+        // the second `--diff` module may not export `resize` either, and then keeps its size
+        if let Some(other) = &mut self.diff_module {
+            let resize =
+                optional_function::<(i32, i32), ()>(&other.store, &other.instance, "resize")?;
+            if let Some(resize) = resize {
+                resize.call(&mut other.store, eye_width as i32, height as i32)?;
+            }
+        }
+        if let Some(post_pass) = &mut self.post_pass {
+            post_pass.resize(width, height)?;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.reallocate_frames()
+    }
+
+    // switches the format frames are read in. the module is expected to follow along, e.g.
+    // through a `--param` or slider, since it isn't told about the change.
+    fn set_pixel_format(&mut self, format: PixelFormat) -> Result<(), RunnerError> {
+        if format == self.pixel_format {
+            return Ok(());
+        }
+        self.pixel_format = format;
+        self.palette = Arc::new([]);
+        // none of the partial image, the running average or the adapted exposure carry over to
+        // the new format
+        self.scanline_canvas.clear();
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.reset();
+        }
+        if let Some(exposure) = &mut self.auto_exposure {
+            exposure.reset();
+        }
+        self.reallocate_frames()
+    }
+
+    // sizes memory and the frame pool for the current frame size and format
+    fn reallocate_frames(&mut self) -> Result<(), RunnerError> {
+        self.bytes_required =
+            self.pixel_format
+                .frame_len(self.width as usize, self.height as usize) as u64;
+
+        let memory = self.module_instance.exports.get_memory(&self.memory_name)?;
+        grow_memory(
+            memory,
+            &mut self.wasm_store,
+            self.bytes_required,
+            self.config.max_memory_pages,
+            !self.config.no_grow || self.memory_name == HOST_MEMORY_NAME,
+        )?;
+        if let Some(other) = &mut self.diff_module {
+            let memory = other.instance.exports.get_memory(&other.memory_name)?;
+            grow_memory(
+                memory,
+                &mut other.store,
+                self.bytes_required,
+                self.config.max_memory_pages,
+                !self.config.no_grow || other.memory_name == HOST_MEMORY_NAME,
+            )?;
+        }
+
+        // frames still held by the UI keep the old pool's buffers alive until they're dropped.
+        // every published frame carries its own size and format so those are still displayed
+        // correctly and old and new frames never mix.
+        self.frame_manager =
+            FrameManager::new(self.bytes_required as usize, self.config.max_frames());
+        self.update_frame_layout();
+        Ok(())
+    }
+
+    // tells the host functions where the framebuffer is, for imports like `blit_tile`
+    fn update_frame_layout(&mut self) {
+        self.host_env.as_mut(&mut self.wasm_store).frame = imports::FrameLayout {
+            width: self.width,
+            height: self.height,
+            bytes_per_pixel: self.pixel_format.bytes_per_pixel() as u32,
+        };
+    }
+
+    // reloads the module if its file changed since it was last loaded. a module that fails to
+    // reload is logged and the previous one keeps running, so a broken save doesn't end the run.
+    fn watch(&mut self) {
+        let now = self.clock.now();
+        if now.saturating_duration_since(self.last_watch_check) < WATCH_INTERVAL {
+            return;
+        }
+        self.last_watch_check = now;
+
+        let modified = module_modified(&self.config);
+        if modified.is_none() || modified == self.watched_modified {
+            return;
+        }
+        self.watched_modified = modified;
+
+        let result = self.reload_with_retry();
+        let path = self.config.module.display();
+        match result {
+            Ok(()) => tracing::info!("reloaded {}", path),
+            Err(e) => tracing::warn!(
+                "failed to reload {}, keeping the previous module: {}",
+                path,
+                e
+            ),
+        }
+    }
+
+    fn reload_with_retry(&mut self) -> Result<(), RunnerError> {
+        retry_reload(|| self.reload())
+    }
+
+    // replaces the running instance with a fresh one of the module's current file, starting over
+    // at the default size and then reapplying the size last requested by the UI
+    // modules exporting `serialize_state` and `deserialize_state` carry their state over, see
+    // `hot_reload`. a module that fails to hand it over is started from scratch instead.
+    fn reload(&mut self) -> Result<(), RunnerError> {
+        let state = hot_reload::save(
+            &mut self.wasm_store,
+            &self.module_instance,
+            &self.memory_name,
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                "failed to save the module's state, reloading without it: {}",
+                e
+            );
+            None
+        });
+
+        let loaded = self.load_at_default_size()?;
+        self.replace_instance(loaded)?;
+
+        if let Some(state) = state {
+            match hot_reload::restore(
+                &mut self.wasm_store,
+                &self.module_instance,
+                &self.memory_name,
+                &state,
+            ) {
+                Ok(true) => tracing::debug!("restored {} bytes of module state", state.len()),
+                Ok(false) => tracing::debug!("reloaded module doesn't export deserialize_state"),
+                Err(e) => tracing::warn!("failed to restore the module's state: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    fn load_at_default_size(&self) -> Result<LoadedModule, RunnerError> {
+        let (width, height) = default_size(&self.config);
+        let bytes_required = self.pixel_format.frame_len(width as usize, height as usize) as u64;
+        LoadedModule::load(&self.config, bytes_required, false)
+    }
+
+    fn replace_instance(&mut self, loaded: LoadedModule) -> Result<(), RunnerError> {
+        let (width, height) = default_size(&self.config);
+        let bytes_required = self.pixel_format.frame_len(width as usize, height as usize) as u64;
+
+        // the audio keeps playing across reloads
+        #[cfg(feature = "audio")]
+        let audio = self.host_env.as_ref(&self.wasm_store).audio.clone();
+
+        self.wasm_store = loaded.store;
+        self.module_instance = loaded.instance;
+        self.host_env = loaded.host_env;
+        #[cfg(feature = "audio")]
+        {
+            self.host_env.as_mut(&mut self.wasm_store).audio = audio;
+        }
+        self.memory_name = loaded.memory_name;
+        self.shade_pool = loaded.shade_pool;
+        self.width = width;
+        self.height = height;
+        self.bytes_required = bytes_required;
+        self.frame_manager = FrameManager::new(bytes_required as usize, self.config.max_frames());
+        self.update_frame_layout();
+        if let Some(accumulator) = &mut self.accumulator {
+            accumulator.reset();
+        }
+
+        match self.view_size {
+            Some((view_width, view_height)) => self.resize(view_width, view_height),
+            None => Ok(()),
+        }
+    }
+
+    // saves the most recent frame to `path` as a PNG, as the UI displays it before any stereo
+    // compositing
+    fn screenshot(&self, path: &Path) -> Result<(), RunnerError> {
+        let Some(frame) = &self.frame_manager.last_updated else {
+            return Err(RunnerError::Frame(
+                "no frame has been rendered yet".to_string(),
+            ));
+        };
+        let mut rgba = Vec::new();
+        self.pixel_format.to_rgba8(
+            frame,
+            self.width as usize,
+            self.config.dither,
+            &self.palette,
+            &mut rgba,
+        );
+        if self.config.premultiplied {
+            blend::unpremultiply(&mut rgba);
+        }
+        self.config
+            .screenshot_format
+            .save(path, &rgba, self.width, self.height)
+    }
+
+    // writes the exported memory to `path`, or only the framebuffer region if so configured
+    fn dump_memory(&self, path: &Path) -> Result<(), RunnerError> {
+        let view = self
+            .module_instance
+            .exports
+            .get_memory(&self.memory_name)?
+            .view(&self.wasm_store);
+
+        let len = if self.config.dump_framebuffer_only {
+            self.bytes_required
+        } else {
+            view.data_size()
+        };
+        let mut buf = vec![0; len as usize];
+        view.read(0, &mut buf)?;
+        fs::write(path, &buf)?;
+
+        if !self.config.quiet {
+            println!(
+                "memory dump: tick {}, {} bytes of {} total, written to {}",
+                self.ticks,
+                len,
+                view.data_size(),
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    // reads the `Indexed8` palette pointed to by the module's `palette` global, with as many
+    // entries as its optional `palette_size` global says
+    fn read_palette(&mut self) -> Result<Arc<[[u8; 4]]>, RunnerError> {
+        let ptr = self.i32_global("palette")?;
+        let size = match self.export_kind("palette_size") {
+            Some(_) => self.i32_global("palette_size")?,
+            None => format::MAX_PALETTE_SIZE as i32,
+        };
+        if !(1..=format::MAX_PALETTE_SIZE as i32).contains(&size) {
+            return Err(RunnerError::invalid_export(
+                "palette_size",
+                format!("expected 1 to {}, got {}", format::MAX_PALETTE_SIZE, size),
+            ));
+        }
+
+        let mut bytes = vec![0; size as usize * 4];
+        self.module_instance
+            .exports
+            .get_memory(&self.memory_name)?
+            .view(&self.wasm_store)
+            .read(ptr as u32 as u64, &mut bytes)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|c| [c[0], c[1], c[2], c[3]])
+            .collect())
+    }
+
+    // `--color-key`, or else the module's optional `color_key` i32 global, holding an RGBA color
+    // laid out like its pixels. its alpha is ignored.
+    fn color_key(&mut self) -> Result<Option<[u8; 3]>, RunnerError> {
+        if let Some(key) = self.config.color_key {
+            return Ok(Some(key));
+        }
+        if self.export_kind("color_key").is_none() {
+            return Ok(None);
+        }
+        let [r, g, b, _] = self.i32_global("color_key")?.to_le_bytes();
+        Ok(Some([r, g, b]))
+    }
+
+    // the module's optional `frame_pitch` global, the bytes from the start of one row of its
+    // frame to the next for modules that pad their rows, or else `row_len`
+    fn frame_pitch(&mut self, row_len: usize) -> Result<usize, RunnerError> {
+        if self.export_kind("frame_pitch").is_none() {
+            return Ok(row_len);
+        }
+        let pitch = self.i32_global("frame_pitch")?;
+        if pitch < 0 || (pitch as usize) < row_len {
+            return Err(RunnerError::invalid_export(
+                "frame_pitch",
+                format!(
+                    "expected at least the row length of {}, got {}",
+                    row_len, pitch
+                ),
+            ));
+        }
+        // the chroma planes' rows are narrower, so one pitch can't describe them all
+        if self.pixel_format == PixelFormat::Yuv420p && pitch as usize != row_len {
+            return Err(RunnerError::invalid_export(
+                "frame_pitch",
+                "padded rows aren't supported with yuv420p",
+            ));
+        }
+        Ok(pitch as usize)
+    }
+
+    fn i32_global(&mut self, name: &str) -> Result<i32, RunnerError> {
+        let global = self.module_instance.exports.get_global(name)?;
+        match global.get(&mut self.wasm_store) {
+            Value::I32(value) => Ok(value),
+            other => Err(RunnerError::invalid_export(
+                name,
+                format!("expected an i32, got {:?}", other),
+            )),
+        }
+    }
+
+    // reads and clears the module's optional `dirty` global, which modules set when a change in
+    // camera or inputs invalidates accumulated frames
+    fn take_dirty_flag(&mut self) -> Result<bool, RunnerError> {
+        let Some(dirty) = optional_global(&self.wasm_store, &self.module_instance, "dirty")? else {
+            return Ok(false);
+        };
+
+        let is_dirty = match dirty.get(&mut self.wasm_store) {
+            Value::I32(value) => value != 0,
+            other => {
+                return Err(RunnerError::invalid_export(
+                    "dirty",
+                    format!("expected an i32, got {:?}", other),
+                ))
+            }
+        };
+        if is_dirty && dirty.ty(&self.wasm_store).mutability == Mutability::Var {
+            dirty
+                .set(&mut self.wasm_store, Value::I32(0))
+                .map_err(|e| RunnerError::invalid_export("dirty", e.message()))?;
+        }
+        Ok(is_dirty)
+    }
+
+    // fills `frame` by calling the module's `shade` export once per pixel. `tick` is optional in
+    // this mode but called first if present so modules can still advance their state.
+    fn shade(&mut self, frame: &mut Frame) -> Result<(), RunnerError> {
+        let (width, height, format) =
+            (self.width as usize, self.height as usize, self.pixel_format);
+        if let Some(pool) = &self.shade_pool {
+            return frame.modify(|buf| pool.shade(buf, width, height, format))?;
+        }
+
+        self.optional_tick()?;
+        let exports = &self.module_instance.exports;
+        let shade = exports.get_typed_function::<(i32, i32), i32>(&self.wasm_store, "shade")?;
+
+        let store = &mut self.wasm_store;
+        frame.modify(|buf| {
+            let bpp = format.bytes_per_pixel();
+            let len = width * height * bpp;
+            for (i, pixel) in buf[..len].chunks_exact_mut(bpp).enumerate() {
+                let (x, y) = (i % width, i / width);
+                let color = shade.call(&mut *store, x as i32, y as i32)?;
+                format.write_rgba8(color.to_le_bytes(), pixel);
+            }
+            // neutral chroma, like test patterns, so `Yuv420p` shaders come out in grayscale
+            buf[len..].fill(0x80);
+            Ok::<(), RuntimeError>(())
+        })??;
+        Ok(())
+    }
+
+    // analyzes the audio once per tick rather than on every `spectrum` call
+    #[cfg(feature = "audio")]
+    fn update_spectrum(&mut self) {
+        let env = self.host_env.as_mut(&mut self.wasm_store);
+        if let Some(clock) = &env.audio {
+            env.spectrum = clock.spectrum();
+        }
+    }
+
+    // calls `tick` in modes where exporting it is optional
+    fn optional_tick(&mut self) -> Result<(), RunnerError> {
+        // like `tick_and_copy`, shade and scanline modes start each tick with nothing drawn
+        self.host_env.as_mut(&mut self.wasm_store).drawing.clear();
+        // called untyped, so whatever the module's `tick` returns is ignored
+        if self.export_kind("tick").is_some() {
+            let tick = self.module_instance.exports.get_function("tick")?;
+            tick.call(&mut self.wasm_store, &[])?;
+        }
+        Ok(())
+    }
+
+    // calls the module's `tick` and copies the frame it rendered out of its memory
+    fn tick_and_copy(&mut self, frame: &mut Frame) -> Result<(), RunnerError> {
+        let start = Instant::now();
+        let tick = self.module_instance.exports.get_function("tick")?;
+
+        self.host_env.as_mut(&mut self.wasm_store).drawing.clear();
+        let results = tick.call(&mut self.wasm_store, vec![].as_slice())?;
+        self.profile("tick", start);
+        let mut offset = 0;
+        if self.config.tick_returns_ptr {
+            offset = match results.first() {
+                Some(Value::I32(ptr)) => *ptr as u32 as u64,
+                _ => {
+                    return Err(RunnerError::invalid_export(
+                        "tick",
+                        "must return the frame's offset with --tick-returns-ptr",
+                    ))
+                }
+            };
+        } else {
+            self.tick_status = match results.first() {
+                Some(Value::I32(status)) if *status < 0 => {
+                    return Err(RunnerError::ModuleFailed(*status))
+                }
+                Some(Value::I32(status)) => Some(*status),
+                _ => None,
+            };
+        }
+
+        let start = Instant::now();
+        let row_len = self.width as usize * self.pixel_format.bytes_per_pixel();
+        let pitch = self.frame_pitch(row_len)?;
+        let view = self
+            .module_instance
+            .exports
+            .get_memory(&self.memory_name)?
+            .view(&self.wasm_store);
+        frame.copy_from_memory(view, offset, row_len, pitch)?;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (layout, bpp) = (self.config.layout, self.pixel_format.bytes_per_pixel());
+        let scratch = &mut self.layout_scratch;
+        frame.modify(|buf| layout.to_linear(buf, width, height, bpp, scratch))?;
+        self.profile("copy_from_memory", start);
+        Ok(())
+    }
+
+    // the module's optional `subframes() -> i32` export, how many times it's ticked for every
+    // displayed frame. anything below 1 counts as 1.
+    fn subframes(&mut self) -> Result<u32, RunnerError> {
+        let Some(subframes) = self.optional_function::<(), i32>("subframes")? else {
+            return Ok(1);
+        };
+        let count = subframes.call(&mut self.wasm_store)?;
+        Ok(count.clamp(1, MAX_SUBFRAMES as i32) as u32)
+    }
+
+    // ticks the module `subframes` times, with `now_ms` stepping evenly through the tick
+    // interval leading up to now, and averages the frames it renders into `frame` for motion
+    // blur. `Indexed8` frames can't be averaged, so the last sub-frame is shown.
+    fn render_subframes(&mut self, frame: &mut Frame, subframes: u32) -> Result<(), RunnerError> {
+        let step_ms = self.tick_interval.as_secs_f64() * 1000.0 / subframes as f64;
+        self.subframe_average.set_limit(subframes);
+        let mut result = Ok(());
+        for subframe in 0..subframes {
+            let time = &mut self.host_env.as_mut(&mut self.wasm_store).time;
+            time.offset_ms = -((subframes - 1 - subframe) as f64 * step_ms);
+            result = self.tick_and_copy(frame);
+            if result.is_err() {
+                break;
+            }
+            let (average, format) = (&mut self.subframe_average, self.pixel_format);
+            frame.modify(|buf| average.accumulate(buf, format))?;
+        }
+        self.host_env.as_mut(&mut self.wasm_store).time.offset_ms = 0.0;
+        result
+    }
+
+    // ticks the second `--diff` module and replaces `frame` with how its frame differs from it
+    fn diff(&mut self, frame: &mut Frame) -> Result<(), RunnerError> {
+        let Some(other) = &mut self.diff_module else {
+            return Ok(());
+        };
+        let tick = other.instance.exports.get_function("tick")?;
+        other.host_env.as_mut(&mut other.store).drawing.clear();
+        let results = tick.call(&mut other.store, &[])?;
+        let offset = match results.first() {
+            Some(Value::I32(ptr)) if self.config.tick_returns_ptr => *ptr as u32 as u64,
+            _ => 0,
+        };
+
+        let view = other
+            .instance
+            .exports
+            .get_memory(&other.memory_name)?
+            .view(&other.store);
+        let need = offset + frame.len() as u64;
+        if view.data_size() < need {
+            return Err(RunnerError::MemoryTooSmall {
+                have: view.data_size(),
+                need,
+            });
+        }
+        self.diff_frame.resize(frame.len(), 0);
+        view.read(offset, &mut self.diff_frame)?;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let bpp = self.pixel_format.bytes_per_pixel();
+        self.config.layout.to_linear(
+            &mut self.diff_frame,
+            width,
+            height,
+            bpp,
+            &mut self.layout_scratch,
+        );
+
+        let (other_frame, format, stats) =
+            (&self.diff_frame, self.pixel_format, &mut self.diff_stats);
+        frame.modify(|buf| diff::difference(buf, other_frame, format, stats))?;
+        Ok(())
+    }
+
+    // copies the row returned by the module's `next_scanline` export into the canvas, then fills
+    // `frame` with the canvas so the partially complete image is displayed
+    fn scanline(&mut self, frame: &mut Frame) -> Result<(), RunnerError> {
+        self.optional_tick()?;
+        let next_scanline = self
+            .module_instance
+            .exports
+            .get_typed_function::<(), i32>(&self.wasm_store, "next_scanline")?;
+        let row = next_scanline.call(&mut self.wasm_store)?;
+
+        // a resize starts the image over
+        if self.scanline_canvas.len() != self.bytes_required as usize {
+            self.scanline_canvas = vec![0; self.bytes_required as usize];
+        }
+
+        if row >= 0 {
+            let row_bytes = self.width as usize * self.pixel_format.bytes_per_pixel();
+            let offset = (row as usize % self.height as usize) * row_bytes;
+            let view = self
+                .module_instance
+                .exports
+                .get_memory(&self.memory_name)?
+                .view(&self.wasm_store);
+            view.read(
+                offset as u64,
+                &mut self.scanline_canvas[offset..offset + row_bytes],
+            )?;
+        }
+
+        frame.copy_from_slice(&self.scanline_canvas)?;
+        Ok(())
+    }
+
+    // adds the time since `start` to `stage` under `--profile`
+    fn profile(&mut self, stage: &'static str, start: Instant) {
+        if let Some(profile) = &mut self.profile {
+            profile.record(stage, start.elapsed());
+        }
+    }
+
+    /// runs one tick and returns the frame it produced, which is also kept as the most recent one
+    ///
+    /// ```
+    /// use clap::Parser;
+    /// use wasm_renderer::{Config, WasmDemoRunner};
+    ///
+    /// let args = ["wasm-renderer", "--builtin", "plasma"];
+    /// let mut runner = WasmDemoRunner::new(&Config::parse_from(args))?;
+    /// // RGBA, at the default size of 256x256
+    /// let frame = runner.tick_once()?;
+    /// assert_eq!(frame.len(), 256 * 256 * 4);
+    /// # Ok::<(), wasm_renderer::RunnerError>(())
+    /// ```
+    pub fn tick_once(&mut self) -> Result<Frame, RunnerError> {
+        self.tick()?;
+        match &self.frame_manager.last_updated {
+            Some(frame) => Ok(frame.clone()),
+            None => Err(RunnerError::Frame("the tick produced no frame".to_string())),
+        }
+    }
+
+    fn tick(&mut self) -> Result<(), RunnerError> {
+        #[cfg(feature = "audio")]
+        self.update_spectrum();
+
+        self.frame_manager.last_updated = None;
+        let tick_start = Instant::now();
+        let start = Instant::now();
+        let mut frame = self.frame_manager.get_free_frame()?;
+        self.metrics.record_pool_usage(self.frame_manager.in_use());
+        // `--auto-pool` grows the pool on demand, then shrinks it to what's been needed once it
+        // has seen enough frames
+        if self.config.auto_pool && self.ticks == AUTO_POOL_WARMUP_TICKS {
+            let size = self.metrics.recommended_pool_size();
+            self.frame_manager.trim(size);
+            tracing::info!("auto pool: keeping {} frames", size);
+        }
+        self.profile("get_free_frame", start);
+
+        self.deliver_clicks()?;
+        // growth the runner does itself, to fit frames, happens between ticks
+        let pages_before = self.memory_pages()?;
+        let start = Instant::now();
+        if let Some(pattern) = self.config.test_pattern {
+            let (width, height) = (self.width as usize, self.height as usize);
+            let format = self.pixel_format;
+            frame.modify(|buf| pattern.fill(buf, width, height, format))?;
+            self.profile("test_pattern", start);
+        } else if self.config.per_pixel {
+            self.shade(&mut frame)?;
+            self.profile("shade", start);
+        } else if self.config.scanlines {
+            self.scanline(&mut frame)?;
+            self.profile("scanlines", start);
+        } else {
+            match self.subframes()? {
+                1 => self.tick_and_copy(&mut frame)?,
+                subframes => self.render_subframes(&mut frame, subframes)?,
+            }
+            if self.diff_module.is_some() {
+                let start = Instant::now();
+                self.diff(&mut frame)?;
+                self.profile("diff", start);
+            }
+        }
+        let pages_after = self.memory_pages()?;
+        self.growth_warning
+            .record(self.ticks, pages_before, pages_after);
+
+        if let Some(post_pass) = &mut self.post_pass {
+            let start = Instant::now();
+            frame.modify(|buf| post_pass.run(buf))??;
+            self.profile("post", start);
+        }
+
+        let start = Instant::now();
+        if self.accumulator.is_some() {
+            let dirty = self.take_dirty_flag()?;
+            let format = self.pixel_format;
+            if let Some(accumulator) = &mut self.accumulator {
+                if dirty {
+                    accumulator.reset();
+                }
+                frame.modify(|buf| accumulator.accumulate(buf, format))?;
+            }
+            self.profile("accumulate", start);
+        }
+
+        if let Some(exposure) = &mut self.auto_exposure {
+            let start = Instant::now();
+            let format = self.pixel_format;
+            frame.modify(|buf| exposure.expose(buf, format))?;
+            self.profile("auto_exposure", start);
+        }
+
+        if self.pixel_format == PixelFormat::Indexed8 {
+            self.palette = self.read_palette()?;
+            if let Some(step) = self.config.palette_rotate {
+                let mut palette = self.palette.to_vec();
+                let shift = (step as i64 * self.ticks as i64).rem_euclid(palette.len() as i64);
+                palette.rotate_left(shift as usize);
+                self.palette = palette.into();
+            }
+        }
+
+        self.frame_manager.last_updated = Some(frame.clone());
+        self.host_env
+            .as_mut(&mut self.wasm_store)
+            .time
+            .advance_tick();
+        // only `--log-file` records events this fine grained
+        tracing::trace!(tick = self.ticks, elapsed = ?tick_start.elapsed(), "tick");
+        self.ticks += 1;
+        Ok(())
+    }
+}
+
+// reads and compiles the module at `path`, returning the binary form of it along with the
+// compiled module
+fn load_module(path: &Path, store: &Store) -> Result<(Vec<u8>, Module), RunnerError> {
+    compile_module(&fs::read(path)?, store)
+}
+
+// the `--builtin` module if one was picked, otherwise the module file
+fn load_main_module(config: &Config, store: &Store) -> Result<(Vec<u8>, Module), RunnerError> {
+    match config.builtin.as_deref().and_then(builtins::find) {
+        Some(builtin) => compile_module(builtin.source, store),
+        None => load_module(&config.module, store),
+    }
+}
+
+fn compile_module(source: &[u8], store: &Store) -> Result<(Vec<u8>, Module), RunnerError> {
+    // modules may be text or binary; normalizing to binary lets us inspect their sections
+    let wasm = wasmer::wat2wasm(source)
+        .map_err(|e| CompileError::Wasm(WasmError::Generic(e.to_string())))?
+        .into_owned();
+    let module = Module::new(store, &wasm).map_err(|e| {
+        if features::is_disabled_feature(&e) {
+            RunnerError::DisabledFeature(e.to_string())
+        } else {
+            e.into()
+        }
+    })?;
+    Ok((wasm, module))
+}
+
+// `--check`: lists the module's imports without instantiating it, failing if any can't be
+// satisfied
+fn check_module(config: &Config) -> Result<(), RunnerError> {
+    let mut store = new_store(config);
+    let (_, module) = load_main_module(config, &store)?;
+    if let Some(requirements) = &config.require {
+        require::check(&module, requirements)?;
+    }
+    let checks = imports::check_imports(&mut store, &module, &config.import_namespace);
+
+    if checks.is_empty() {
+        println!("imports: none");
+    } else {
+        println!("imports:");
+    }
+    let mut unsupported = Vec::new();
+    for check in &checks {
+        let qualified = format!("{}.{}", check.namespace, check.name);
+        match &check.problem {
+            None => println!("  {}: {}", qualified, imports::describe(&check.ty)),
+            Some(problem) => {
+                println!(
+                    "  {}: {} ({})",
+                    qualified,
+                    imports::describe(&check.ty),
+                    problem
+                );
+                unsupported.push(qualified);
+            }
+        }
+    }
+
+    if unsupported.is_empty() {
+        Ok(())
+    } else {
+        Err(RunnerError::UnsupportedImports(unsupported))
+    }
+}
+
+// what `instance` exports under `name` and its type, or `None` if it exports nothing by that name
+fn export_kind(store: &impl AsStoreRef, instance: &Instance, name: &str) -> Option<ExportKind> {
+    let export = instance.exports.get_extern(name)?;
+    Some(match export.ty(store) {
+        ExternType::Function(ty) => ExportKind::Function(ty),
+        ExternType::Global(ty) => ExportKind::Global(ty),
+        ExternType::Memory(ty) => ExportKind::Memory(ty),
+        ExternType::Table(ty) => ExportKind::Table(ty),
+    })
+}
+
+// looks up a function `instance` may choose not to export. something else exported under `name`
+// is an error rather than the function being left out.
+fn optional_function<Args, Rets>(
+    store: &impl AsStoreRef,
+    instance: &Instance,
+    name: &str,
+) -> Result<Option<TypedFunction<Args, Rets>>, RunnerError>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    match export_kind(store, instance, name) {
+        None => Ok(None),
+        Some(ExportKind::Function(_)) => {
+            Ok(Some(instance.exports.get_typed_function(store, name)?))
+        }
+        Some(other) => Err(RunnerError::invalid_export(
+            name,
+            format!("expected a function, got {}", other),
+        )),
+    }
+}
+
+// looks up a global `instance` may choose not to export, like `optional_function`
+fn optional_global(
+    store: &impl AsStoreRef,
+    instance: &Instance,
+    name: &str,
+) -> Result<Option<Global>, RunnerError> {
+    match export_kind(store, instance, name) {
+        None => Ok(None),
+        Some(ExportKind::Global(_)) => Ok(Some(instance.exports.get_global(name)?.clone())),
+        Some(other) => Err(RunnerError::invalid_export(
+            name,
+            format!("expected a global, got {}", other),
+        )),
+    }
+}
+
+// picks the exported memory frames are read from: the one named on the command line, or the only
+// memory the module exports
+fn resolve_memory_name(
+    instance: &Instance,
+    requested: Option<&str>,
+) -> Result<String, RunnerError> {
+    if let Some(name) = requested {
+        instance.exports.get_memory(name)?;
+        return Ok(name.to_string());
+    }
+
+    let mut memories: Vec<String> = instance
+        .exports
+        .iter()
+        .memories()
+        .map(|(name, _)| name.clone())
+        .collect();
+    match memories.len() {
+        0 => Err(ExportError::Missing("any memory".to_string()).into()),
+        1 => Ok(memories.remove(0)),
+        _ => Err(RunnerError::AmbiguousMemory(memories)),
+    }
+}
+
+// lists the module's custom sections and prints any `wr_metadata` it embeds
+fn print_metadata(wasm: &[u8], module: &Module) {
+    let sections = metadata::custom_sections(wasm);
+    if sections.is_empty() {
+        println!("custom sections: none");
+    } else {
+        println!("custom sections:");
+        for section in &sections {
+            println!("  {} ({} bytes)", section.name, section.data.len());
+        }
+    }
+
+    for data in module.custom_sections(metadata::METADATA_SECTION) {
+        println!("{}:", metadata::METADATA_SECTION);
+        for (key, value) in metadata::parse_metadata(&data) {
+            println!("  {} = {}", key, value);
+        }
+    }
+}
+
+// the framebuffer size modules start out with, before any `resize`
+fn default_size(config: &Config) -> (u32, u32) {
+    // stereo modules render both eyes side by side in one framebuffer
+    let eyes = if config.stereo.is_some() { 2 } else { 1 };
+    (256 * eyes, 256)
+}
+
+// the module file's modification time, when `--watch` needs it and it can be read. builtins
+// never change.
+fn module_modified(config: &Config) -> Option<SystemTime> {
+    if !config.watch || config.builtin.is_some() {
+        return None;
+    }
+    fs::metadata(&config.module).and_then(|m| m.modified()).ok()
+}
+
+// a store whose memories are capped at `--max-memory-pages`, if given. worker stores share its
+// engine and so the same cap.
+fn new_store(config: &Config) -> Store {
+    let engine = match &config.wasm_features {
+        Some(enabled) => features::engine(enabled),
+        None => Engine::default(),
+    };
+    match config.max_memory_pages {
+        Some(pages) => Store::new(limits::MemoryLimit::apply(engine, pages)),
+        None => Store::new(engine),
+    }
+}
+
+// calls `reload` until it succeeds, up to `RELOAD_ATTEMPTS` times with exponential backoff, since
+// editors that write files incrementally can make the first attempts read a partial module
+fn retry_reload(mut reload: impl FnMut() -> Result<(), RunnerError>) -> Result<(), RunnerError> {
+    let mut delay = RELOAD_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match reload() {
+            Err(e) if attempt < RELOAD_ATTEMPTS => {
+                tracing::debug!("reload attempt {} failed, retrying: {}", attempt, e);
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+// rejects modules declaring memories that start out larger than `limit` pages, before any of that
+// memory gets allocated
+fn check_memory_limit(module: &Module, limit: u32) -> Result<(), RunnerError> {
+    for export in module.exports().memories() {
+        let pages = export.ty().minimum.0;
+        if pages > limit {
+            return Err(RunnerError::MemoryLimitExceeded { pages, limit });
+        }
+    }
+    Ok(())
+}
+
+// grows `memory` until it holds at least `bytes_required` bytes, as long as that fits in
+// `limit` pages. with `grow` false it fails instead if the memory is smaller, see `--no-grow`.
+fn grow_memory(
+    memory: &Memory,
+    store: &mut Store,
+    bytes_required: u64,
+    limit: Option<u32>,
+    grow: bool,
+) -> Result<(), RunnerError> {
+    let page_size = wasmer::WASM_PAGE_SIZE as u64;
+    let pages = bytes_required.div_ceil(page_size);
+    if let Some(limit) = limit {
+        if pages > limit as u64 {
+            return Err(RunnerError::MemoryLimitExceeded {
+                pages: pages.min(u32::MAX as u64) as u32,
+                limit,
+            });
+        }
+    }
+
+    let data_size = memory.view(store).data_size();
+    if data_size < bytes_required && !grow {
+        return Err(RunnerError::MemoryTooSmall {
+            have: data_size,
+            need: bytes_required,
+        });
+    }
+    if data_size < bytes_required {
+        let pages_missing = (bytes_required - data_size).div_ceil(page_size);
+        memory.grow(store, pages_missing as u32)?;
+    }
+    Ok(())
+}
+
+/// does whatever `config` asks for, e.g. opening a window showing the module, until the window
+/// closes or the module stops
+pub fn run(config: &Config) -> Result<(), RunnerError> {
+    if let Some(path) = &config.log_file {
+        // the parent and child of `--isolate` would otherwise truncate each other's log
+        match config.isolated_child {
+            Some(_) => logging::init(&path.with_extension("child.log"), config.quiet)?,
+            None => logging::init(path, config.quiet)?,
+        }
+    }
+    // before any module code runs, including start functions during instantiation
+    if let Some(kib) = config.max_stack {
+        limits::set_max_stack(kib);
+    }
+    if let (Some(pages), Some(limit)) = (config.reserve_pages, config.max_memory_pages) {
+        if pages > limit {
+            return Err(RunnerError::MemoryLimitExceeded { pages, limit });
+        }
+    }
+    if config.list_builtins {
+        builtins::list();
+        return Ok(());
+    }
+    if config.check {
+        return check_module(config);
+    }
+    if config.isolate && config.isolated_child.is_none() {
+        return run_isolated(config);
+    }
+
+    let mut wasm_runner = WasmDemoRunner::new(config)?;
+    if config.describe {
+        match wasm_runner.description()? {
+            Some(description) => println!("{}", description.json),
+            None => println!("the module doesn't export describe"),
+        }
+        return Ok(());
+    }
+
+    // kept alive on this thread until the runner has finished
+    #[cfg(feature = "audio")]
+    let _audio = match &config.audio {
+        Some(path) => {
+            let player = audio::AudioPlayer::open(path)?;
+            wasm_runner.attach_audio(player.clock());
+            Some(player)
+        }
+        None => None,
+    };
+    let stdin_commands = config.stdin_commands.then(stdin::spawn);
+    let (command_sender, commands) = mpsc::channel();
+
+    if let Some(address) = config.isolated_child {
+        // the parent's window sends its commands over the connection instead
+        drop(command_sender);
+        let (mut sink, commands) = isolate::connect(address)?;
+        return wasm_runner.run(&mut sink, commands, stdin_commands);
+    }
+
+    if config.term {
+        // nothing sends commands without a window, but the runner stops once every sender is gone
+        let _command_sender = command_sender;
+        let mut preview = term::TermPreview::new(config, wasm_runner.metrics.drop_counter());
+        return wasm_runner.run(&mut preview, commands, stdin_commands);
+    }
+
+    let state = AppState {
+        sliders: Arc::new(wasm_runner.ui_sliders()),
+        scenes: Arc::new(wasm_runner.scenes()?),
+        description: wasm_runner.description()?.map(|d| d.summary()),
+        scrub: 0.0,
+        debug_panel: true,
+    };
+
+    let drop_counter = wasm_runner.metrics.drop_counter();
+    launch_window(
+        config,
+        state,
+        command_sender,
+        drop_counter,
+        move |event_sink| wasm_runner.run(event_sink, commands, stdin_commands),
+    )
+}
+
+// `--isolate`: shows the frames of a child process running the module. its sliders can't be
+// shown since the module's globals only exist in the child.
+fn run_isolated(config: &Config) -> Result<(), RunnerError> {
+    let child = isolate::IsolatedChild::spawn()?;
+    let (command_sender, commands) = mpsc::channel();
+    let state = AppState {
+        sliders: Arc::new(Vec::new()),
+        scenes: Arc::new(Vec::new()),
+        description: None,
+        scrub: 0.0,
+        debug_panel: true,
+    };
+    // the child reports its own metrics, this only keeps the UI's bookkeeping working
+    let metrics = Metrics::new(config.jitter_threshold);
+    launch_window(
+        config,
+        state,
+        command_sender,
+        metrics.drop_counter(),
+        move |event_sink| child.run(event_sink, commands),
+    )
+}
+
+// runs the window on this thread and `publish_frames` on another until the window is closed
+// spawns the thread producing frames for the window, named so profilers grouping by thread can
+// tell it apart from the UI
+fn spawn_producer<T: Send + 'static>(
+    produce: impl FnOnce() -> T + Send + 'static,
+) -> std::io::Result<thread::JoinHandle<T>> {
+    thread::Builder::new()
+        .name("wasm-producer".to_string())
+        .spawn(produce)
+}
+
+fn launch_window(
+    config: &Config,
+    state: AppState,
+    command_sender: mpsc::Sender<Command>,
+    drop_counter: metrics::DropCounter,
+    publish_frames: impl FnOnce(&mut ExtEventSink) -> Result<(), RunnerError> + Send + 'static,
+) -> Result<(), RunnerError> {
+    let window = WindowDesc::new(ui::make_ui(config, command_sender, drop_counter))
+        .title("wasm demo runner");
+
+    let launcher = AppLauncher::with_window(window);
+
+    let event_sink = launcher.get_external_handle();
+
+    let runner_thread = spawn_producer(move || {
+        let mut event_sink = event_sink;
+        publish_frames(&mut event_sink)
+    })?;
+
+    // without a subscriber nothing logged through `tracing` is printed. `--log-file` sets up
+    // its own, which covers the console as well
+    let launcher = if config.quiet || config.log_file.is_some() {
+        launcher
+    } else {
+        launcher.log_to_console()
+    };
+    launcher.launch(state)?;
+
+    // the UI's command sender is dropped along with the window, which tells the runner to stop
+    match runner_thread.join() {
+        Ok(result) => result,
+        Err(_) => Err(RunnerError::RunnerPanicked),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use wasmer::Type;
+
+    use super::*;
+    use test_util::*;
+
+    #[test]
+    fn resize_reallocates_frames() {
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (global $width (export "width") (mut i32) (i32.const 0))
+                (global $height (export "height") (mut i32) (i32.const 0))
+                (func (export "resize") (param i32 i32)
+                    (global.set $width (local.get 0))
+                    (global.set $height (local.get 1)))
+                (func (export "tick")))"#,
+            &[],
+        );
+        runner
+            .handle_command(Command::Resize {
+                width: 100,
+                height: 50,
+            })
+            .unwrap();
+
+        assert_eq!(
+            (
+                runner.i32_global("width").unwrap(),
+                runner.i32_global("height").unwrap()
+            ),
+            (100, 50)
+        );
+        assert_eq!((runner.width, runner.height), (100, 50));
+        assert_eq!(runner.tick_once().unwrap().len(), 100 * 50 * 4);
+    }
+
+    #[test]
+    fn pixel_format_can_change_mid_run() {
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (func (export "tick")
+                    (memory.fill (i32.const 0) (i32.const 0x80) (i32.const 0x40000))))"#,
+            &[],
+        );
+        let rgba = runner.tick_once().unwrap();
+        runner
+            .handle_command(Command::SetPixelFormat(PixelFormat::Alpha8))
+            .unwrap();
+        let alpha = runner.tick_once().unwrap();
+
+        // the frame from before the switch keeps its buffer and is still whole
+        assert_eq!(rgba.len(), 256 * 256 * 4);
+        assert_eq!(alpha.len(), 256 * 256);
+        assert_eq!(runner.pixel_format, PixelFormat::Alpha8);
+        assert!(rgba.iter().chain(alpha.iter()).all(|&b| b == 0x80));
+    }
+
+    // the whole of `runner`'s memory
+    fn memory(runner: &WasmDemoRunner) -> Vec<u8> {
+        let memory = runner
+            .module_instance
+            .exports
+            .get_memory(&runner.memory_name)
+            .unwrap();
+        let view = memory.view(&runner.wasm_store);
+        let mut bytes = vec![0; view.data_size() as usize];
+        view.read(0, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn dumped_memory_matches_the_view() {
+        // a counter the module bumps every tick, and some data besides
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 1024) "some data")
+                (func (export "tick")
+                    (i32.store (i32.const 16) (i32.add (i32.load (i32.const 16)) (i32.const 1)))))"#,
+            &[],
+        );
+        runner.tick().unwrap();
+        runner.tick().unwrap();
+
+        let path = temp_dir("dump").join("memory.bin");
+        runner.dump_memory(&path).unwrap();
+        let dumped = fs::read(&path).unwrap();
+        assert_eq!(dumped, memory(&runner));
+        assert_eq!(&dumped[16..20], 2u32.to_le_bytes());
+    }
+
+    #[test]
+    fn shutdown_is_called_once() {
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (global $shutdowns (export "shutdowns") (mut i32) (i32.const 0))
+                (func (export "tick"))
+                (func (export "shutdown")
+                    (global.set $shutdowns (i32.add (global.get $shutdowns) (i32.const 1)))))"#,
+            &["--max-frames", "3", "--quiet"],
+        );
+        let mut sink = Collect::default();
+        run_until_stopped(&mut runner, &mut sink).unwrap();
+
+        assert_eq!(sink.frames.len(), 3);
+        assert_eq!(runner.i32_global("shutdowns").unwrap(), 1);
+    }
+
+    // an opaque red of x ^ y
+    const XOR_SHADER: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "resize") (param i32 i32))
+        (func (export "shade") (param i32 i32) (result i32)
+            (i32.or (i32.xor (local.get 0) (local.get 1)) (i32.const 0xff000000))))"#;
+
+    #[test]
+    fn per_pixel_shades_every_pixel() {
+        let mut runner = runner(XOR_SHADER, &["--per-pixel", "--internal-res", "16x8"]);
+        let frame = runner.tick_once().unwrap();
+        for (i, pixel) in frame.chunks_exact(4).enumerate() {
+            let (x, y) = (i % 16, i / 16);
+            assert_eq!(pixel, [(x ^ y) as u8, 0, 0, 0xff], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn threaded_per_pixel_matches_single_threaded() {
+        // a height that doesn't split evenly between the threads
+        let args = ["--per-pixel", "--internal-res", "37x13"];
+        let single = runner(XOR_SHADER, &args).tick_once().unwrap().to_vec();
+        let threaded = runner(
+            XOR_SHADER,
+            &[&args[..], &["--per-pixel-threads", "4"]].concat(),
+        )
+        .tick_once()
+        .unwrap()
+        .to_vec();
+        assert_eq!(single.len(), 37 * 13 * 4);
+        assert!(single == threaded);
+    }
+
+    #[test]
+    fn yuv420p_shaders_write_luma_and_neutral_chroma() {
+        let args = [
+            "--per-pixel",
+            "--internal-res",
+            "37x13",
+            "--pixel-format",
+            "yuv420p",
+        ];
+        let single = runner(XOR_SHADER, &args).tick_once().unwrap().to_vec();
+        let threaded = runner(
+            XOR_SHADER,
+            &[&args[..], &["--per-pixel-threads", "4"]].concat(),
+        )
+        .tick_once()
+        .unwrap()
+        .to_vec();
+        assert_eq!(single.len(), PixelFormat::Yuv420p.frame_len(37, 13));
+        let (luma, chroma) = single.split_at(37 * 13);
+        // black has the lowest luma, and the shader's red grows along the first row
+        assert_eq!(luma[0], 16);
+        assert!(luma[0] < luma[36]);
+        assert!(chroma.iter().all(|&c| c == 0x80));
+        assert!(single == threaded);
+    }
+
+    #[test]
+    fn shading_threads_use_the_configured_time_source() {
+        // shades every pixel with the whole milliseconds `now_ms` reports
+        let clock_shader = r#"(module
+            (import "env" "now_ms" (func $now_ms (result f64)))
+            (memory (export "memory") 1)
+            (func (export "resize") (param i32 i32))
+            (func (export "shade") (param i32 i32) (result i32)
+                (i32.or (i32.trunc_f64_u (call $now_ms)) (i32.const 0xff000000))))"#;
+        let args = [
+            "--per-pixel",
+            "--internal-res",
+            "4x4",
+            "--time-source",
+            "manual:1234",
+        ];
+        for threads in ["1", "2"] {
+            let mut runner = runner(
+                clock_shader,
+                &[&args[..], &["--per-pixel-threads", threads]].concat(),
+            );
+            let frame = runner.tick_once().unwrap();
+            assert!(
+                frame
+                    .chunks_exact(4)
+                    .all(|pixel| pixel == [0xd2, 0x04, 0, 0xff]),
+                "{} threads",
+                threads
+            );
+        }
+    }
+
+    #[test]
+    fn shaders_without_a_memory_render_into_the_host_framebuffer() {
+        let memoryless = XOR_SHADER.replace(r#"(memory (export "memory") 1)"#, "");
+        assert!(!memoryless.contains("memory"));
+        let args = ["--per-pixel", "--internal-res", "16x8"];
+        let expected = runner(XOR_SHADER, &args).tick_once().unwrap().to_vec();
+        for threads in ["1", "3"] {
+            let mut runner = runner(
+                &memoryless,
+                &[&args[..], &["--per-pixel-threads", threads]].concat(),
+            );
+            let frame = runner.tick_once().unwrap();
+            assert!(frame.to_vec() == expected, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn slices_are_copied_into_frames_of_the_same_length() {
+        let mut frame: Frame = Frame::new(8);
+        let bytes: Vec<u8> = (1..=8).collect();
+        frame.copy_from_slice(&bytes).unwrap();
+        assert_eq!(frame.to_vec(), bytes);
+
+        // a mismatched length leaves the frame as it was
+        assert!(frame.copy_from_slice(&[0; 7]).is_err());
+        assert!(frame.copy_from_slice(&[0; 9]).is_err());
+        assert_eq!(frame.to_vec(), bytes);
+    }
+
+    #[test]
+    fn undersized_memories_fail_under_no_grow() {
+        let module = |pages: u32| {
+            format!(
+                r#"(module
+                    (memory (export "memory") {})
+                    (func (export "resize") (param i32 i32))
+                    (func (export "tick")))"#,
+                pages
+            )
+        };
+        // frames in the default 256x256 window take 4 pages
+        let mut grown = runner(&module(1), &[]);
+        grown.tick_once().unwrap();
+        assert_eq!(memory(&grown).len(), 262144);
+
+        let strict = config(&module(1), &["--no-grow"]);
+        match WasmDemoRunner::new(&strict) {
+            Err(RunnerError::MemoryTooSmall { have, need }) => {
+                assert_eq!((have, need), (65536, 262144))
+            }
+            other => panic!("expected MemoryTooSmall, got {:?}", other.map(|_| ())),
+        }
+
+        // fits the window at first, but not once it grows
+        let mut strict = runner(&module(4), &["--no-grow"]);
+        strict.resize(64, 64).unwrap();
+        assert!(matches!(
+            strict.resize(512, 256),
+            Err(RunnerError::MemoryTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn frames_past_the_end_of_memory_are_reported() {
+        let mut store = Store::default();
+        let memory = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+        let page = wasmer::WASM_PAGE_SIZE as u64;
+        // rows packed together in memory as they are in the frame
+        let copy =
+            |frame: &mut Frame, offset| frame.copy_from_memory(memory.view(&store), offset, 16, 16);
+
+        let mut fits: Frame = Frame::new(page as usize);
+        copy(&mut fits, 0).unwrap();
+        let mut too_big: Frame = Frame::new(page as usize + 1);
+        match copy(&mut too_big, 0) {
+            Err(RunnerError::MemoryTooSmall { have, need }) => {
+                assert_eq!((have, need), (page, page + 1))
+            }
+            other => panic!("expected MemoryTooSmall, got {:?}", other),
+        }
+        // the offset counts towards the end of the frame
+        let mut offset: Frame = Frame::new(16);
+        match copy(&mut offset, page - 8) {
+            Err(RunnerError::MemoryTooSmall { have, need }) => {
+                assert_eq!((have, need), (page, page + 8))
+            }
+            other => panic!("expected MemoryTooSmall, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn frames_round_trip_bytes() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let frame = Frame::from_bytes(&bytes);
+        assert_eq!(frame.as_ref(), &bytes[..]);
+
+        // clones share the buffer rather than copying it
+        let clone = frame.clone();
+        assert_eq!(Frame::count(&frame), 2);
+        assert_eq!(clone.as_ref().as_ptr(), frame.as_ref().as_ptr());
+        drop(clone);
+        assert_eq!(Frame::count(&frame), 1);
+        assert!(Frame::from_bytes(&[]).as_ref().is_empty());
+    }
+
+    #[test]
+    fn pool_grows_up_to_its_cap() {
+        let mut pool = FrameManager::new(4, 7);
+        // held, like frames the UI hasn't let go of yet
+        let held: Vec<_> = (0..7).map(|_| pool.get_free_frame().unwrap()).collect();
+        assert_eq!(pool.frames.len(), 7);
+        assert_eq!(pool.in_use(), 7);
+        assert!(pool.get_free_frame().is_err());
+
+        // freed frames are reused rather than growing the pool further
+        drop(held);
+        let _frame = pool.get_free_frame().unwrap();
+        assert_eq!(pool.frames.len(), 7);
+    }
+
+    #[test]
+    fn fixed_pool_never_grows() {
+        let mut pool = FrameManager::new(4, 0);
+        let _held: Vec<_> = (0..5).map(|_| pool.get_free_frame().unwrap()).collect();
+        assert!(pool.get_free_frame().is_err());
+        assert_eq!(pool.frames.len(), 5);
+    }
+
+    #[test]
+    fn pool_high_water_mark_is_the_most_frames_in_use_at_once() {
+        let mut pool = FrameManager::new(4, 10);
+        let mut metrics = Metrics::new(Duration::from_millis(5));
+        let mut held = Vec::new();
+        // the UI holds on to up to 7 frames at once, letting go of some in between
+        for holding in [1, 4, 7, 2, 3] {
+            held.truncate(holding);
+            while held.len() < holding {
+                held.push(pool.get_free_frame().unwrap());
+                metrics.record_pool_usage(pool.in_use());
+            }
+        }
+        assert_eq!(pool.frames.len(), 7);
+        assert_eq!(metrics.recommended_pool_size(), 7 + metrics::POOL_HEADROOM);
+
+        // trimming only drops free frames, never those still held
+        pool.trim(2);
+        assert_eq!(pool.frames.len(), 3);
+        assert_eq!(pool.in_use(), 3);
+    }
+
+    #[test]
+    fn checksums_are_fnv1a() {
+        let mut frame = Frame::new(1);
+        frame.modify(|buf| buf[0] = b'a').unwrap();
+        assert_eq!(frame.checksum(), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(Frame::new(1).checksum(), frame.checksum());
+    }
+
+    #[test]
+    fn stops_once_the_module_is_done() {
+        let done_after_five = r#"(module
+            (memory (export "memory") 4)
+            (global $ticks (mut i32) (i32.const 0))
+            (func (export "tick")
+                (global.set $ticks (i32.add (global.get $ticks) (i32.const 1))))
+            (func (export "is_done") (result i32)
+                (i32.ge_u (global.get $ticks) (i32.const 5))))"#;
+
+        let mut sink = Collect::default();
+        run_until_stopped(&mut runner(done_after_five, &["--quiet"]), &mut sink).unwrap();
+        assert_eq!(sink.frames.len(), 5);
+        assert!(!sink.closed);
+
+        let mut sink = Collect::default();
+        let mut closing = runner(done_after_five, &["--quiet", "--close-on-done"]);
+        run_until_stopped(&mut closing, &mut sink).unwrap();
+        assert_eq!(sink.frames.len(), 5);
+        assert!(sink.closed);
+    }
+
+    #[test]
+    fn internal_resolution_ignores_the_window_size() {
+        // writes the size it was last told about into the frame
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (global $width (mut i32) (i32.const 0))
+                (global $height (mut i32) (i32.const 0))
+                (func (export "resize") (param i32 i32)
+                    (global.set $width (local.get 0))
+                    (global.set $height (local.get 1)))
+                (func (export "tick")
+                    (i32.store (i32.const 0) (global.get $width))
+                    (i32.store (i32.const 4) (global.get $height))))"#,
+            &["--internal-res", "40x30"],
+        );
+        for (width, height) in [(800, 600), (1920, 1080), (7, 3)] {
+            runner.resize(width, height).unwrap();
+            let frame = runner.tick_once().unwrap();
+            assert_eq!(frame.len(), 40 * 30 * 4);
+            assert_eq!(frame[..8], [40, 0, 0, 0, 30, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn double_buffered_frames_are_read_from_the_returned_offset() {
+        // flips between a red buffer at 4096 and a blue one at 8192
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 4096) "\ff\00\00\ff\ff\00\00\ff")
+                (data (i32.const 8192) "\00\00\ff\ff\00\00\ff\ff")
+                (global $front (mut i32) (i32.const 8192))
+                (func (export "resize") (param i32 i32))
+                (func (export "tick") (result i32)
+                    (global.set $front (i32.sub (i32.const 12288) (global.get $front)))
+                    (global.get $front)))"#,
+            &["--internal-res", "2x1", "--tick-returns-ptr"],
+        );
+        let (red, blue) = ([0xff, 0, 0, 0xff], [0, 0, 0xff, 0xff]);
+        for expected in [red, blue, red] {
+            let frame = runner.tick_once().unwrap();
+            assert_eq!(frame.to_vec(), [expected, expected].concat());
+        }
+        // the offset isn't taken for a status
+        assert_eq!(runner.tick_status, None);
+    }
+
+    #[test]
+    fn clicks_between_ticks_are_delivered_in_order() {
+        // appends each click's x, y and button to the frame as bytes
+        let mut clicked = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (global $end (mut i32) (i32.const 0))
+                (func $push (param i32)
+                    (i32.store8 (global.get $end) (local.get 0))
+                    (global.set $end (i32.add (global.get $end) (i32.const 1))))
+                (func (export "on_click") (param i32 i32 i32)
+                    (call $push (local.get 0))
+                    (call $push (local.get 1))
+                    (call $push (local.get 2)))
+                (func (export "tick")))"#,
+            &[],
+        );
+        for (x, y, button) in [(10, 20, 0), (30, 40, 2)] {
+            clicked
+                .handle_command(Command::Click { x, y, button })
+                .unwrap();
+        }
+        let frame = clicked.tick_once().unwrap();
+        assert_eq!(frame[..7], [10, 20, 0, 30, 40, 2, 0]);
+        // delivered once only
+        let frame = clicked.tick_once().unwrap();
+        assert_eq!(frame[6], 0);
+
+        // modules without `on_click` don't get them
+        let mut clickless = runner(EMPTY_MODULE, &[]);
+        let click = Command::Click {
+            x: 1,
+            y: 2,
+            button: 0,
+        };
+        clickless.handle_command(click).unwrap();
+        clickless.tick_once().unwrap();
+    }
+
+    #[test]
+    fn padded_rows_are_packed_into_the_frame() {
+        // 2x2 pixels in rows padded out to 12 bytes
+        let padded = |pitch: i32| {
+            format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (data (i32.const 0) "\01\02\03\04\05\06\07\08\ee\ee\ee\ee")
+                    (data (i32.const 12) "\11\12\13\14\15\16\17\18\ee\ee\ee\ee")
+                    (global (export "frame_pitch") i32 (i32.const {}))
+                    (func (export "resize") (param i32 i32))
+                    (func (export "tick")))"#,
+                pitch
+            )
+        };
+        let args = ["--internal-res", "2x2"];
+        let frame = runner(&padded(12), &args).tick_once().unwrap();
+        let expected: Vec<u8> = (0x01..=0x08).chain(0x11..=0x18).collect();
+        assert_eq!(frame.to_vec(), expected);
+
+        // a pitch shorter than a row would overlap rows
+        match runner(&padded(7), &args).tick_once() {
+            Err(RunnerError::InvalidExport { name, .. }) => assert_eq!(name, "frame_pitch"),
+            other => panic!("expected InvalidExport, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn subframes_of_a_moving_dot_blur_together() {
+        // a white dot moving one pixel right every sub-frame across a black 4x1 frame
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (global $x (mut i32) (i32.const 0))
+                (func (export "resize") (param i32 i32))
+                (func (export "subframes") (result i32) (i32.const 4))
+                (func (export "tick")
+                    (i32.store (i32.const 0) (i32.const 0xff000000))
+                    (i32.store (i32.const 4) (i32.const 0xff000000))
+                    (i32.store (i32.const 8) (i32.const 0xff000000))
+                    (i32.store (i32.const 12) (i32.const 0xff000000))
+                    (i32.store (i32.shl (global.get $x) (i32.const 2)) (i32.const -1))
+                    (global.set $x (i32.rem_u (i32.add (global.get $x) (i32.const 1)) (i32.const 4)))))"#,
+            &["--internal-res", "4x1"],
+        );
+        for _ in 0..2 {
+            let frame = runner.tick_once().unwrap();
+            // a quarter of the white in every pixel, rather than the dot where it ended up
+            for pixel in frame.chunks_exact(4) {
+                assert!(
+                    pixel[..3].iter().all(|c| (63..=64).contains(c)),
+                    "{:?}",
+                    pixel
+                );
+                assert_eq!(pixel[3], 0xff);
+            }
+        }
+    }
+
+    #[test]
+    fn tick_once_returns_the_new_frame() {
+        // counts its ticks into the first byte of the frame
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (func (export "tick")
+                    (i32.store8 (i32.const 0) (i32.add (i32.load8_u (i32.const 0)) (i32.const 1)))))"#,
+            &[],
+        );
+        for ticks in 1..=3 {
+            let frame = runner.tick_once().unwrap();
+            assert_eq!(frame.len() as u64, runner.bytes_required);
+            assert_eq!(frame[0], ticks);
+            let last = runner.frame_manager.last_updated.as_ref().unwrap();
+            assert_eq!(last.as_ptr(), frame.as_ptr());
+        }
+    }
+
+    #[test]
+    fn the_producer_thread_is_named() {
+        let name = spawn_producer(|| thread::current().name().map(str::to_string));
+        assert_eq!(
+            name.unwrap().join().unwrap().as_deref(),
+            Some("wasm-producer")
+        );
+    }
+
+    #[test]
+    fn the_frame_callback_sees_every_published_frame_once() {
+        // counts its ticks in the first pixel
+        let mut counting = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")
+                    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))))"#,
+            &["--frames", "4", "--internal-res", "4x4"],
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let callback_seen = seen.clone();
+        counting.set_frame_callback(move |frame| callback_seen.lock().unwrap().push(frame[0]));
+        let mut sink = Collect::default();
+        run_until_stopped(&mut counting, &mut sink).unwrap();
+
+        let published: Vec<u8> = sink.frames.iter().map(|p| p.frame[0]).collect();
+        assert_eq!(published, [1, 2, 3, 4]);
+        assert_eq!(*seen.lock().unwrap(), published);
+    }
+
+    #[test]
+    fn export_kinds_describe_what_is_exported() {
+        let exporting = runner(
+            r#"(module
+                (memory (export "memory") 4 8)
+                (table (export "table") 2 funcref)
+                (global (export "dirty") (mut i32) (i32.const 0))
+                (func (export "tick"))
+                (func (export "shade") (param i32 i32) (result i32) (i32.const 0)))"#,
+            &[],
+        );
+        match exporting.export_kind("shade") {
+            Some(ExportKind::Function(ty)) => {
+                assert_eq!(
+                    (ty.params(), ty.results()),
+                    (&[Type::I32, Type::I32][..], &[Type::I32][..])
+                )
+            }
+            other => panic!("expected a function, got {:?}", other),
+        }
+        assert_eq!(
+            exporting.export_kind("dirty"),
+            Some(ExportKind::Global(GlobalType::new(
+                Type::I32,
+                Mutability::Var
+            )))
+        );
+        assert_eq!(
+            exporting.export_kind("memory"),
+            Some(ExportKind::Memory(MemoryType::new(4, Some(8), false)))
+        );
+        assert!(matches!(
+            exporting.export_kind("table"),
+            Some(ExportKind::Table(_))
+        ));
+        assert_eq!(exporting.export_kind("resize"), None);
+
+        // optional lookups leave out what's missing, but not what's exported as something else
+        assert!(exporting
+            .optional_function::<(i32, i32), ()>("resize")
+            .unwrap()
+            .is_none());
+        match exporting.optional_function::<(), i32>("dirty") {
+            Err(RunnerError::InvalidExport { name, reason }) => {
+                assert_eq!(
+                    (name.as_str(), reason.as_str()),
+                    ("dirty", "expected a function, got global I32 (mutable)")
+                )
+            }
+            other => panic!("expected InvalidExport, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn several_memories_need_a_memory_name() {
+        let two_memories = r#"(module
+            (memory (export "frame") 4)
+            (memory (export "scratch") 1)
+            (func (export "tick")))"#;
+        let features = ["--wasm-features", "multi-memory"];
+
+        match WasmDemoRunner::new(&config(two_memories, &features)) {
+            Err(RunnerError::AmbiguousMemory(names)) => assert_eq!(names, ["frame", "scratch"]),
+            other => panic!("expected AmbiguousMemory, got {:?}", other.err()),
+        }
+        let mut runner = runner(
+            two_memories,
+            &[&features[..], &["--memory-name", "frame"]].concat(),
+        );
+        assert_eq!(runner.memory_name, "frame");
+        runner.tick_once().unwrap();
+    }
+
+    #[test]
+    fn scanlines_fill_rows_in_order() {
+        // a 4x3 image whose rows are filled with 1, 2 and 3
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\01\01\01\01\01\01\01\01\01\01\01\01\01\01\01\01")
+                (data (i32.const 16) "\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02\02")
+                (data (i32.const 32) "\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03\03")
+                (global $row (mut i32) (i32.const 0))
+                (func (export "resize") (param i32 i32))
+                (func (export "next_scanline") (result i32)
+                    (global.set $row (i32.add (global.get $row) (i32.const 1)))
+                    (i32.sub (global.get $row) (i32.const 1))))"#,
+            &["--scanlines", "--internal-res", "4x3"],
+        );
+        for ticks in 1..=3 {
+            let frame = runner.tick_once().unwrap();
+            for (row, pixels) in frame.chunks_exact(16).enumerate() {
+                let expected = if row < ticks { row as u8 + 1 } else { 0 };
+                assert_eq!(pixels, [expected; 16], "row {} after {} ticks", row, ticks);
+            }
+        }
+        // row 3 wraps around to the top, which is already done
+        let frame = runner.tick_once().unwrap();
+        assert_eq!(frame[..16], [1; 16]);
+    }
+
+    #[test]
+    fn modules_past_the_memory_limit_are_rejected() {
+        let huge = r#"(module (memory (export "memory") 64) (func (export "tick")))"#;
+        match WasmDemoRunner::new(&config(huge, &["--max-memory-pages", "16"])) {
+            Err(RunnerError::MemoryLimitExceeded { pages, limit }) => {
+                assert_eq!((pages, limit), (64, 16))
+            }
+            other => panic!("expected MemoryLimitExceeded, got {:?}", other.err()),
+        }
+
+        // nor can they be resized past it
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")))"#,
+            &["--max-memory-pages", "4"],
+        );
+        match runner.resize(512, 512) {
+            Err(RunnerError::MemoryLimitExceeded { pages, limit }) => {
+                assert_eq!((pages, limit), (16, 4))
+            }
+            other => panic!("expected MemoryLimitExceeded, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reloads_are_retried_until_they_succeed() {
+        let mut attempts = 0;
+        let result = retry_reload(|| {
+            attempts += 1;
+            match attempts {
+                // the editor hadn't finished writing the file yet
+                1 | 2 => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                _ => Ok(()),
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result = retry_reload(|| {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound).into())
+        });
+        assert!(matches!(result, Err(RunnerError::Io(_))));
+        assert_eq!(attempts, RELOAD_ATTEMPTS);
+    }
+
+    #[test]
+    fn palettes_can_have_16_colors() {
+        // entry i is (i, 2i, 3i), followed by garbage past the 16th
+        let palette: String = (0..16u8)
+            .map(|i| format!("\\{:02x}\\{:02x}\\{:02x}\\ff", i, i * 2, i * 3))
+            .collect();
+        let mut runner = runner(
+            &format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (data (i32.const 0) "\00\05\0f\c8")
+                    (data (i32.const 1024) "{}\ee\ee\ee\ee")
+                    (global (export "palette") i32 (i32.const 1024))
+                    (global (export "palette_size") i32 (i32.const 16))
+                    (func (export "resize") (param i32 i32))
+                    (func (export "tick")))"#,
+                palette
+            ),
+            &["--pixel-format", "indexed8", "--internal-res", "4x1"],
+        );
+        let frame = runner.tick_once().unwrap();
+        assert_eq!(runner.palette.len(), 16);
+
+        let mut rgba = Vec::new();
+        let format = PixelFormat::Indexed8;
+        format.to_rgba8(&frame, 4, dither::Dither::None, &runner.palette, &mut rgba);
+        // index 200 is past the end and clamped to the last entry
+        assert_eq!(
+            rgba,
+            [0, 0, 0, 0xff, 5, 10, 15, 0xff, 15, 30, 45, 0xff, 15, 30, 45, 0xff]
+        );
+    }
+
+    #[test]
+    fn rotated_palettes_shift_by_the_step_every_frame() {
+        // a 4x1 frame of indices 0 to 3, with entry i of the palette being (i, 0, 0)
+        let still = r#"(module
+            (memory (export "memory") 1)
+            (data (i32.const 0) "\00\01\02\03")
+            (data (i32.const 1024) "\00\00\00\ff\01\00\00\ff\02\00\00\ff\03\00\00\ff")
+            (global (export "palette") i32 (i32.const 1024))
+            (global (export "palette_size") i32 (i32.const 4))
+            (func (export "resize") (param i32 i32))
+            (func (export "tick")))"#;
+        for (step, shown) in [
+            ("1", [[0, 1, 2, 3], [1, 2, 3, 0], [2, 3, 0, 1]]),
+            ("-1", [[0, 1, 2, 3], [3, 0, 1, 2], [2, 3, 0, 1]]),
+            ("6", [[0, 1, 2, 3], [2, 3, 0, 1], [0, 1, 2, 3]]),
+        ] {
+            let args = ["--pixel-format", "indexed8", "--internal-res", "4x1"];
+            let mut cycling = runner(still, &[&args[..], &["--palette-rotate", step]].concat());
+            for reds in shown {
+                let frame = cycling.tick_once().unwrap();
+                let mut rgba = Vec::new();
+                let format = PixelFormat::Indexed8;
+                format.to_rgba8(&frame, 4, dither::Dither::None, &cycling.palette, &mut rgba);
+                let shown_reds: Vec<u8> = rgba.chunks_exact(4).map(|pixel| pixel[0]).collect();
+                assert_eq!(shown_reds, reds, "rotating by {}", step);
+            }
+        }
+    }
+
+    #[test]
+    fn check_flags_imports_we_dont_supply() {
+        let config = config(
+            r#"(module
+                (import "env" "log" (func (param i32 i32)))
+                (import "env" "explode" (func))
+                (import "env" "now_ms" (func (result i32)))
+                (memory (export "memory") 4)
+                (func (export "tick")))"#,
+            &["--check"],
+        );
+        match check_module(&config) {
+            Err(RunnerError::UnsupportedImports(names)) => {
+                assert_eq!(names, ["env.explode", "env.now_ms"])
+            }
+            other => panic!("expected UnsupportedImports, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn max_frames_stops_after_exactly_that_many_ticks() {
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (global $ticks (export "ticks") (mut i32) (i32.const 0))
+                (func (export "tick")
+                    (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))))"#,
+            &["--max-frames", "4", "--quiet"],
+        );
+        let mut sink = Collect::default();
+        run_until_stopped(&mut runner, &mut sink).unwrap();
+        assert_eq!(runner.i32_global("ticks").unwrap(), 4);
+        assert_eq!(sink.frames.len(), 4);
+        assert!(sink.closed);
+    }
+
+    #[test]
+    fn vsync_ticks_once_per_refresh() {
+        let mut runner = runner(
+            EMPTY_MODULE,
+            &["--vsync", "--quiet", "--pool-policy", "grow"],
+        );
+        let (commands_sender, commands) = mpsc::channel();
+        // a display refreshing every 30 ms, three times as long as the default tick interval,
+        // unplugged a refresh after the fifth
+        let refresh = Duration::from_millis(30);
+        let display = thread::spawn(move || {
+            for _ in 0..5 {
+                thread::sleep(refresh);
+                commands_sender.send(Command::Vblank).unwrap();
+            }
+            thread::sleep(refresh);
+        });
+        let mut sink = Collect::default();
+        runner.run(&mut sink, commands, None).unwrap();
+        display.join().unwrap();
+
+        // the first frame doesn't wait for a refresh
+        assert_eq!(sink.frames.len(), 6);
+        for frames in sink.frames.windows(2) {
+            let interval = frames[1].published_at - frames[0].published_at;
+            assert!(interval > refresh * 2 / 3, "{:?}", interval);
+        }
+    }
+
+    #[test]
+    fn switching_scenes_changes_the_output() {
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 5)
+                (data (i32.const 0x40000) "onetwo")
+                (global $scene (mut i32) (i32.const 0))
+                (func (export "scene_count") (result i32) (i32.const 2))
+                (func (export "scene_name") (param i32 i32 i32) (result i32)
+                    (memory.copy
+                        (local.get 1)
+                        (i32.add (i32.const 0x40000) (i32.mul (local.get 0) (i32.const 3)))
+                        (i32.const 3))
+                    (i32.const 3))
+                (func (export "set_scene") (param i32) (global.set $scene (local.get 0)))
+                (func (export "tick")
+                    (memory.fill
+                        (i32.const 0)
+                        (i32.add (global.get $scene) (i32.const 1))
+                        (i32.const 0x40000))))"#,
+            &[],
+        );
+        let names: Vec<_> = runner
+            .scenes()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["one", "two"]);
+
+        assert!(runner.tick_once().unwrap().iter().all(|&b| b == 1));
+        runner.handle_command(Command::SetScene(1)).unwrap();
+        assert!(runner.tick_once().unwrap().iter().all(|&b| b == 2));
+        // scenes past the end are ignored
+        runner.handle_command(Command::SetScene(2)).unwrap();
+        assert!(runner.tick_once().unwrap().iter().all(|&b| b == 2));
+    }
+
+    #[test]
+    fn profiles_time_the_core_stages() {
+        let mut runner = runner(EMPTY_MODULE, &["--profile"]);
+        for _ in 0..3 {
+            runner.tick_once().unwrap();
+        }
+        let summary = runner.profile.as_ref().unwrap().summary();
+        for stage in ["tick", "get_free_frame", "copy_from_memory"] {
+            assert!(
+                summary
+                    .lines()
+                    .any(|line| line.trim_start().starts_with(stage)),
+                "{}",
+                summary
+            );
+        }
+    }
+
+    #[test]
+    fn half_red_over_blue_is_purple() {
+        let red = Frame::from_bytes(&[0xff, 0, 0, 0xff].repeat(4));
+        let blue = Frame::from_bytes(&[0, 0, 0xff, 0xff].repeat(4));
+        let mut out = Frame::from_bytes(&[0; 16]);
+        red.blend_over(&blue, 0.5, &mut out).unwrap();
+        assert_eq!(&out[..], &[0x80, 0, 0x80, 0xff].repeat(4)[..]);
+    }
+
+    #[test]
+    fn errors_are_reported_to_the_sink() {
+        let trap_on_third = r#"(module
+            (memory (export "memory") 4)
+            (global $ticks (mut i32) (i32.const 0))
+            (func (export "tick")
+                (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))
+                (if (i32.eq (global.get $ticks) (i32.const 3)) (then unreachable))))"#;
+        let mut sink = Collect::default();
+        let result = run_until_stopped(&mut runner(trap_on_third, &["--quiet"]), &mut sink);
+
+        assert!(matches!(result, Err(RunnerError::Trap(_))), "{:?}", result);
+        assert_eq!(sink.errors.len(), 1);
+        assert!(
+            sink.errors[0].starts_with("module trapped"),
+            "{}",
+            sink.errors[0]
+        );
+        // the frames from before the error stay on screen
+        assert_eq!(sink.frames.len(), 2);
+        assert!(!sink.closed);
+    }
+
+    #[test]
+    fn tick_results_are_published_as_statuses() {
+        // counts up from 1, then fails on the fourth tick
+        let counter = r#"(module
+            (memory (export "memory") 4)
+            (global $ticks (mut i32) (i32.const 0))
+            (func (export "tick") (result i32)
+                (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))
+                (select
+                    (i32.const -5)
+                    (global.get $ticks)
+                    (i32.eq (global.get $ticks) (i32.const 4)))))"#;
+        let mut sink = Collect::default();
+        let result = run_until_stopped(&mut runner(counter, &["--quiet"]), &mut sink);
+
+        let statuses: Vec<_> = sink.frames.iter().map(|frame| frame.status).collect();
+        assert_eq!(statuses, [Some(1), Some(2), Some(3)]);
+        assert!(
+            matches!(result, Err(RunnerError::ModuleFailed(-5))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn playlists_switch_between_modules() {
+        let filling = |byte: u8| {
+            format!(
+                r#"(module
+                    (memory (export "memory") 4)
+                    (func (export "tick")
+                        (memory.fill (i32.const 0) (i32.const {}) (i32.const 0x40000))))"#,
+                byte
+            )
+        };
+        let second = module_file(&filling(0x22));
+        let mut runner = runner(&filling(0x11), &[second.to_str().unwrap()]);
+        assert_eq!(runner.tick_once().unwrap()[0], 0x11);
+
+        let mut bytes = Vec::new();
+        for command in [
+            Command::NextModule,
+            Command::NextModule,
+            Command::PreviousModule,
+        ] {
+            runner.handle_command(command).unwrap();
+            bytes.push(runner.tick_once().unwrap()[0]);
+        }
+        // switching wraps around at either end
+        assert_eq!(bytes, [0x22, 0x11, 0x22]);
+        assert_eq!(runner.playlist_position, 1);
+    }
+
+    #[test]
+    fn external_pulses_drive_one_tick_each() {
+        // a port nothing else is listening on
+        let port = std::net::UdpSocket::bind("127.0.0.1:0")
+            .and_then(|socket| socket.local_addr())
+            .unwrap()
+            .port();
+        let address = format!("127.0.0.1:{}", port);
+        let mut runner = runner(EMPTY_MODULE, &["--external-clock", &address, "--quiet"]);
+
+        let (commands_sender, commands) = mpsc::channel();
+        let controller = thread::spawn(move || {
+            let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            for _ in 0..4 {
+                socket.send_to(b"tick", &address).unwrap();
+            }
+            // long enough for every pulse's tick to run before the window goes away
+            thread::sleep(Duration::from_millis(300));
+            drop(commands_sender);
+        });
+        let mut sink = Collect::default();
+        runner.run(&mut sink, commands, None).unwrap();
+        controller.join().unwrap();
+        assert_eq!(sink.frames.len(), 4);
+    }
+
+    #[test]
+    fn reads_never_see_torn_frames() {
+        let frame: Frame = Frame::new(64 * 1024);
+        let mut writer = frame.clone();
+        let done = Arc::new(atomic::AtomicBool::new(false));
+        let writing = {
+            let done = done.clone();
+            thread::spawn(move || {
+                // every write fills the whole frame with one value
+                for value in (0..=255u8).cycle().take(2000) {
+                    writer.modify(|buf| buf.fill(value)).unwrap();
+                }
+                done.store(true, Ordering::Relaxed);
+            })
+        };
+
+        let mut reads = 0;
+        while !done.load(Ordering::Relaxed) {
+            let (first, uniform) = frame.read(|buf| (buf[0], buf.iter().all(|&b| b == buf[0])));
+            assert!(uniform, "read a torn frame starting with {}", first);
+            reads += 1;
+        }
+        writing.join().unwrap();
+        assert!(reads > 0);
+    }
+}
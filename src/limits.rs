@@ -0,0 +1,210 @@
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+use wasmer::vm::{
+    MemoryError, MemoryStyle, TableStyle, VMMemory, VMMemoryDefinition, VMTable, VMTableDefinition,
+};
+use wasmer::{BaseTunables, Engine, MemoryType, Pages, TableType, Target, Tunables};
+
+/// the smallest and largest `--max-stack`, in KiB. wasmer clamps the size to this range anyway.
+pub const MIN_STACK_KIB: u64 = 8;
+pub const MAX_STACK_KIB: u64 = 100 * 1024;
+
+/// sets the size of the stack wasm calls run on, for every store. a module that recurses past
+/// it traps with a stack overflow instead of running into the host's own stack.
+pub fn set_max_stack(kib: u64) {
+    wasmer_vm::set_stack_size(kib as usize * 1024);
+}
+
+/// the shortest time between two warnings about a module growing its memory
+pub const GROWTH_WARNING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// warns when a module grows its memory during a tick, which a module that allocates everything
+/// up front never does, so it usually means a leak. warnings are rate limited to one per
+/// `GROWTH_WARNING_INTERVAL`, counting the growths left out in between.
+#[derive(Debug, Default)]
+pub struct GrowthWarning {
+    last_warning: Option<Instant>,
+    suppressed: u32,
+}
+
+impl GrowthWarning {
+    /// compares the pages the memory had before tick `tick` with those it has after
+    pub fn record(&mut self, tick: u64, before: Pages, after: Pages) {
+        if after <= before {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .last_warning
+            .is_some_and(|last| now - last < GROWTH_WARNING_INTERVAL)
+        {
+            self.suppressed += 1;
+            return;
+        }
+        tracing::warn!(
+            "module grew its memory from {} to {} pages during tick {}{}",
+            before.0,
+            after.0,
+            tick,
+            match std::mem::take(&mut self.suppressed) {
+                0 => String::new(),
+                n => format!(", after {} more growths since the last warning", n),
+            }
+        );
+        self.last_warning = Some(now);
+    }
+}
+
+/// caps the size of every memory created by an engine at `limit` pages. memories are created
+/// with their maximum lowered to the limit so a module's own `memory.grow` fails past it, and
+/// memories whose initial size already exceeds it aren't created at all.
+pub struct MemoryLimit {
+    limit: Pages,
+    base: BaseTunables,
+}
+
+impl MemoryLimit {
+    /// caps the memories `engine` creates at `pages`
+    pub fn apply(mut engine: Engine, pages: u32) -> Engine {
+        engine.set_tunables(Self {
+            limit: Pages(pages),
+            base: BaseTunables::for_target(&Target::default()),
+        });
+        engine
+    }
+
+    fn adjust(&self, requested: &MemoryType) -> MemoryType {
+        let mut adjusted = *requested;
+        adjusted.maximum = Some(
+            requested
+                .maximum
+                .map_or(self.limit, |max| max.min(self.limit)),
+        );
+        adjusted
+    }
+
+    fn validate(&self, ty: &MemoryType) -> Result<(), MemoryError> {
+        if ty.minimum > self.limit {
+            return Err(MemoryError::Generic(format!(
+                "memory needs {} pages, more than the limit of {}",
+                ty.minimum.0, self.limit.0
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Tunables for MemoryLimit {
+    // the style is picked from the requested type so changing the maximum doesn't change how
+    // the memory is laid out
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<VMMemory, MemoryError> {
+        let adjusted = self.adjust(ty);
+        self.validate(&adjusted)?;
+        self.base.create_host_memory(&adjusted, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<VMMemory, MemoryError> {
+        let adjusted = self.adjust(ty);
+        self.validate(&adjusted)?;
+        self.base
+            .create_vm_memory(&adjusted, style, vm_definition_location)
+    }
+
+    fn create_host_table(&self, ty: &TableType, style: &TableStyle) -> Result<VMTable, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<VMTable, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmer::Pages;
+
+    use crate::error::RunnerError;
+    use crate::test_util::*;
+
+    #[test]
+    fn memory_growth_during_ticks_is_warned_about() {
+        let leaking = r#"(module
+            (memory (export "memory") 4)
+            (func (export "tick") (drop (memory.grow (i32.const 1)))))"#;
+        let mut leaks = runner(leaking, &[]);
+        for _ in 0..3 {
+            leaks.tick_once().unwrap();
+        }
+        // the first growth is warned about, the next two within the interval only counted
+        assert!(leaks.growth_warning.last_warning.is_some());
+        assert_eq!(leaks.growth_warning.suppressed, 2);
+
+        // growing memory to fit the frame before the first tick doesn't count
+        let mut runner = runner(
+            r#"(module (memory (export "memory") 1) (func (export "tick")))"#,
+            &[],
+        );
+        runner.tick_once().unwrap();
+        assert!(runner.growth_warning.last_warning.is_none());
+    }
+
+    #[test]
+    fn reserved_memory_isnt_grown_while_ticking() {
+        // wants 8 pages and grows its memory to them on the first tick if it has to
+        let wants_8_pages = r#"(module
+            (memory (export "memory") 4)
+            (func (export "tick")
+                (if (i32.lt_u (memory.size) (i32.const 8))
+                    (then (drop (memory.grow (i32.sub (i32.const 8) (memory.size))))))))"#;
+        let mut growing = runner(wants_8_pages, &[]);
+        growing.tick_once().unwrap();
+        assert!(growing.growth_warning.last_warning.is_some());
+
+        let mut reserved = runner(wants_8_pages, &["--reserve-pages", "8"]);
+        let memory = reserved
+            .module_instance
+            .exports
+            .get_memory("memory")
+            .unwrap();
+        assert_eq!(memory.view(&reserved.wasm_store).size(), Pages(8));
+        for _ in 0..3 {
+            reserved.tick_once().unwrap();
+        }
+        assert!(reserved.growth_warning.last_warning.is_none());
+        assert_eq!(reserved.growth_warning.suppressed, 0);
+
+        let too_much = config(
+            EMPTY_MODULE,
+            &["--reserve-pages", "9", "--max-memory-pages", "8"],
+        );
+        match crate::run(&too_much) {
+            Err(RunnerError::MemoryLimitExceeded { pages, limit }) => {
+                assert_eq!((pages, limit), (9, 8))
+            }
+            other => panic!("expected MemoryLimitExceeded, got {:?}", other),
+        }
+    }
+}
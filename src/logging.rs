@@ -0,0 +1,33 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+use crate::error::RunnerError;
+
+/// `--log-file`: writes everything logged through `tracing`, down to every tick's timing, to
+/// `path`, which is truncated first so each run starts a fresh log. unless `quiet`, the same is
+/// logged to the console as the window would, minus the per tick events. installs the global
+/// subscriber, so the window mustn't install its own.
+pub fn init(path: &Path, quiet: bool) -> Result<(), RunnerError> {
+    let file = File::create(path)?;
+    let file_layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_thread_names(true)
+        .with_writer(Mutex::new(file))
+        .with_filter(LevelFilter::TRACE);
+    let console_layer = (!quiet).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_target(true)
+            .with_filter(LevelFilter::DEBUG)
+    });
+
+    tracing_subscriber::registry()
+        .with(file_layer)
+        .with(console_layer)
+        .try_init()
+        .map_err(|e| RunnerError::Io(io::Error::other(e)))
+}
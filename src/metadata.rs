@@ -0,0 +1,110 @@
+/// name of the custom section demos can use to describe themselves with `key=value` lines
+pub const METADATA_SECTION: &str = "wr_metadata";
+
+/// a custom section found in a wasm binary
+#[derive(Debug)]
+pub struct CustomSection<'a> {
+    pub name: String,
+    pub data: &'a [u8],
+}
+
+/// enumerates the custom sections in a wasm binary, in the order they appear. wasmer only
+/// supports looking custom sections up by name so this walks the section headers itself;
+/// malformed binaries yield whatever sections could be read before the problem.
+pub fn custom_sections(wasm: &[u8]) -> Vec<CustomSection<'_>> {
+    let mut sections = Vec::new();
+    // skip the magic number and version
+    let mut rest = match wasm.get(8..) {
+        Some(rest) => rest,
+        None => return sections,
+    };
+
+    while let Some((&id, after_id)) = rest.split_first() {
+        let Some((size, after_size)) = read_leb128(after_id) else {
+            break;
+        };
+        if after_size.len() < size {
+            break;
+        }
+        let (payload, next) = after_size.split_at(size);
+        rest = next;
+
+        if id != 0 {
+            continue;
+        }
+        let Some((name_len, after_len)) = read_leb128(payload) else {
+            break;
+        };
+        if after_len.len() < name_len {
+            break;
+        }
+        let (name, data) = after_len.split_at(name_len);
+        sections.push(CustomSection {
+            name: String::from_utf8_lossy(name).into_owned(),
+            data,
+        });
+    }
+
+    sections
+}
+
+/// parses `key=value` lines, ignoring blank lines and lines without an `=`
+pub fn parse_metadata(data: &[u8]) -> Vec<(String, String)> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+// reads an unsigned LEB128 u32, returning it along with the remaining bytes
+fn read_leb128(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let mut value = 0usize;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a custom section named `name` holding `data`
+    fn custom_section(name: &str, data: &[u8]) -> Vec<u8> {
+        let size = 1 + name.len() + data.len();
+        let mut section = vec![0, size as u8, name.len() as u8];
+        section.extend(name.as_bytes());
+        section.extend(data);
+        section
+    }
+
+    #[test]
+    fn finds_custom_sections_among_others() {
+        let metadata = b"author = someone\nversion=1.2\n\nnot a pair\n";
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend(custom_section(METADATA_SECTION, metadata));
+        // an empty type section
+        wasm.extend([1, 1, 0]);
+        wasm.extend(custom_section("other", b"x"));
+
+        let sections = custom_sections(&wasm);
+        let names: Vec<_> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, [METADATA_SECTION, "other"]);
+        assert_eq!(sections[0].data, metadata);
+        assert_eq!(
+            parse_metadata(sections[0].data),
+            [
+                ("author".to_string(), "someone".to_string()),
+                ("version".to_string(), "1.2".to_string())
+            ]
+        );
+
+        // a section cut short ends the list
+        wasm.truncate(wasm.len() - 1);
+        assert_eq!(custom_sections(&wasm).len(), 1);
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// number of inter-frame intervals collected before frame pacing statistics are evaluated
+const PACING_WINDOW: usize = 120;
+
+/// frames recommended for the pool on top of the most ever in use at once
+pub const POOL_HEADROOM: usize = 1;
+
+/// frame pacing statistics over a window of inter-frame intervals
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PacingStats {
+    pub mean: Duration,
+    pub std_dev: Duration,
+    pub p99: Duration,
+}
+
+impl PacingStats {
+    /// computes pacing statistics for `intervals`, or `None` if there are none
+    pub fn from_intervals(intervals: &[Duration]) -> Option<Self> {
+        if intervals.is_empty() {
+            return None;
+        }
+
+        let secs: Vec<f64> = intervals.iter().map(Duration::as_secs_f64).collect();
+        let mean = secs.iter().sum::<f64>() / secs.len() as f64;
+        let variance = secs.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / secs.len() as f64;
+
+        let mut sorted = intervals.to_vec();
+        sorted.sort();
+        // nearest-rank percentile
+        let rank = (0.99 * sorted.len() as f64).ceil() as usize;
+        let p99 = sorted[rank.saturating_sub(1)];
+
+        Some(Self {
+            mean: Duration::from_secs_f64(mean),
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+            p99,
+        })
+    }
+
+    pub fn fps(&self) -> f64 {
+        1.0 / self.mean.as_secs_f64()
+    }
+}
+
+/// the time from a frame being published to it being displayed, at `displayed_at`
+pub fn display_latency(published_at: Instant, displayed_at: Instant) -> Duration {
+    displayed_at.saturating_duration_since(published_at)
+}
+
+/// display latencies of every displayed frame, summed in microseconds so they can be shared
+/// between threads without a lock
+#[derive(Debug, Default)]
+struct LatencyTotals {
+    frames: AtomicU64,
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl LatencyTotals {
+    fn record(&self, latency: Duration) {
+        let us = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    // the mean and the largest latency, if any frame has been displayed
+    fn summary(&self) -> Option<(Duration, Duration)> {
+        let frames = self.frames.load(Ordering::Relaxed);
+        if frames == 0 {
+            return None;
+        }
+        let mean = self.total_us.load(Ordering::Relaxed) / frames;
+        let max = self.max_us.load(Ordering::Relaxed);
+        Some((Duration::from_micros(mean), Duration::from_micros(max)))
+    }
+}
+
+/// counts published frames the UI never displayed, noticed as gaps in the indices of the frames
+/// it does display, and how long the ones it does display took to get there. lives in the UI and
+/// shares its counts with the runner's `Metrics`.
+#[derive(Debug)]
+pub struct DropCounter {
+    last_displayed: Option<u64>,
+    dropped: Arc<AtomicU64>,
+    latency: Arc<LatencyTotals>,
+}
+
+impl DropCounter {
+    /// records that the frame with `index`, published at `published_at`, has been displayed
+    pub fn displayed(&mut self, index: u64, published_at: Instant) {
+        if let Some(last) = self.last_displayed {
+            if index == last {
+                // repainting the same frame
+                return;
+            }
+            if index > last + 1 {
+                self.dropped.fetch_add(index - last - 1, Ordering::Relaxed);
+            }
+        }
+        self.last_displayed = Some(index);
+        self.latency
+            .record(display_latency(published_at, Instant::now()));
+    }
+}
+
+/// tracks when frames are published by the runner
+#[derive(Debug)]
+pub struct Metrics {
+    jitter_threshold: Duration,
+    last_frame: Option<Instant>,
+    intervals: Vec<Duration>,
+    published: u64,
+    dropped: Arc<AtomicU64>,
+    latency: Arc<LatencyTotals>,
+    // the most frames of the pool ever in use at once, across reallocations
+    pool_high_water: usize,
+}
+
+impl Metrics {
+    pub fn new(jitter_threshold: Duration) -> Self {
+        Self {
+            jitter_threshold,
+            last_frame: None,
+            intervals: Vec::with_capacity(PACING_WINDOW),
+            published: 0,
+            dropped: Arc::new(AtomicU64::new(0)),
+            latency: Arc::default(),
+            pool_high_water: 0,
+        }
+    }
+
+    /// a counter for the UI to report the frames it never displayed through
+    pub fn drop_counter(&self) -> DropCounter {
+        DropCounter {
+            last_displayed: None,
+            dropped: self.dropped.clone(),
+            latency: self.latency.clone(),
+        }
+    }
+
+    /// number of published frames the UI never displayed
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// records that `in_use` frames of the pool were in use at once
+    pub fn record_pool_usage(&mut self, in_use: usize) {
+        self.pool_high_water = self.pool_high_water.max(in_use);
+    }
+
+    /// the pool size the run so far called for: the most frames in use at once plus headroom
+    pub fn recommended_pool_size(&self) -> usize {
+        self.pool_high_water + POOL_HEADROOM
+    }
+
+    /// logs a summary of the whole run
+    pub fn report(&self) {
+        tracing::info!(
+            "published {} frames, {} never displayed",
+            self.published,
+            self.dropped_frames()
+        );
+        if let Some((mean, max)) = self.latency.summary() {
+            tracing::info!(
+                "frames took {:?} from publishing to display on average, {:?} at most",
+                mean,
+                max
+            );
+        }
+        tracing::info!(
+            "at most {} pool frames were in use at once, a pool of {} would do",
+            self.pool_high_water,
+            self.recommended_pool_size()
+        );
+    }
+
+    /// records a frame published at `now`. once a full window of intervals has been collected the
+    /// pacing statistics are recomputed and a warning is logged if the jitter exceeds the
+    /// threshold.
+    pub fn record_frame(&mut self, now: Instant) {
+        self.published += 1;
+        if let Some(last) = self.last_frame.replace(now) {
+            self.intervals.push(now.saturating_duration_since(last));
+        }
+
+        if self.intervals.len() < PACING_WINDOW {
+            return;
+        }
+
+        let stats = PacingStats::from_intervals(&self.intervals);
+        self.intervals.clear();
+
+        if let Some(stats) = stats {
+            tracing::debug!(
+                "frame pacing: std dev {:?}, p99 {:?}, mean {:?}, {} frames dropped so far",
+                stats.std_dev,
+                stats.p99,
+                stats.mean,
+                self.dropped_frames(),
+            );
+            if stats.std_dev > self.jitter_threshold {
+                tracing::warn!(
+                    "frame pacing jitter over threshold: std dev {:?}, p99 {:?}, mean {:?} ({:.1} fps)",
+                    stats.std_dev,
+                    stats.p99,
+                    stats.mean,
+                    stats.fps(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pacing_stats_of_a_hitch() {
+        // two 30 ms hitches in an otherwise steady 10 ms
+        let mut intervals = vec![Duration::from_millis(10); 98];
+        intervals.extend([Duration::from_millis(30); 2]);
+        let stats = PacingStats::from_intervals(&intervals).unwrap();
+
+        let close = |a: Duration, b: Duration| a.abs_diff(b) < Duration::from_nanos(100);
+        assert!(
+            close(stats.mean, Duration::from_micros(10_400)),
+            "{:?}",
+            stats.mean
+        );
+        assert!(
+            close(stats.std_dev, Duration::from_micros(2_800)),
+            "{:?}",
+            stats.std_dev
+        );
+        assert_eq!(stats.p99, Duration::from_millis(30));
+        assert_eq!(PacingStats::from_intervals(&[]), None);
+    }
+
+    #[test]
+    fn counts_frames_a_slow_consumer_skipped() {
+        let metrics = Metrics::new(Duration::from_millis(4));
+        let mut ui = metrics.drop_counter();
+        let now = Instant::now();
+        // the runner published frames 0 to 9 while the UI only got around to some of them,
+        // repainting one of those twice
+        for index in [0, 1, 4, 4, 5, 9] {
+            ui.displayed(index, now);
+        }
+        assert_eq!(metrics.dropped_frames(), 5);
+        assert_eq!(metrics.latency.frames.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn latency_from_synthetic_timestamps() {
+        let published = Instant::now();
+        let ms = Duration::from_millis;
+        assert_eq!(display_latency(published, published + ms(16)), ms(16));
+        // a paint timestamp from before publishing, e.g. from another clock, isn't negative
+        assert_eq!(
+            display_latency(published + ms(1), published),
+            Duration::ZERO
+        );
+
+        let totals = LatencyTotals::default();
+        assert_eq!(totals.summary(), None);
+        for painted in [5, 15, 10] {
+            totals.record(display_latency(published, published + ms(painted)));
+        }
+        assert_eq!(totals.summary(), Some((ms(10), ms(15))));
+    }
+}
@@ -0,0 +1,89 @@
+use wasmer::{Instance, Mutability, Store, Type, Value};
+
+use crate::error::RunnerError;
+
+/// parses a `name=value` module parameter
+pub fn parse_param(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected name=value, got '{}'", s)),
+    }
+}
+
+/// sets the module's exported mutable `f32` global `name`
+pub fn set_f32_global(
+    store: &mut Store,
+    instance: &Instance,
+    name: &str,
+    value: f32,
+) -> Result<(), RunnerError> {
+    instance
+        .exports
+        .get_global(name)?
+        .set(store, Value::F32(value))
+        .map_err(|e| RunnerError::invalid_export(name, e.message()))
+}
+
+/// sets the module's exported mutable global `name` to `value`, parsed according to the global's
+/// type
+pub fn set_global(
+    store: &mut Store,
+    instance: &Instance,
+    name: &str,
+    value: &str,
+) -> Result<(), RunnerError> {
+    let global = instance.exports.get_global(name)?;
+    let ty = global.ty(store);
+    if ty.mutability != Mutability::Var {
+        return Err(RunnerError::invalid_export(name, "global is not mutable"));
+    }
+
+    let invalid = |e: &dyn std::fmt::Display| {
+        RunnerError::invalid_export(
+            name,
+            format!("can't set {:?} global to '{}': {}", ty.ty, value, e),
+        )
+    };
+    let value = match ty.ty {
+        Type::I32 => Value::I32(value.parse().map_err(|e| invalid(&e))?),
+        Type::I64 => Value::I64(value.parse().map_err(|e| invalid(&e))?),
+        Type::F32 => Value::F32(value.parse().map_err(|e| invalid(&e))?),
+        Type::F64 => Value::F64(value.parse().map_err(|e| invalid(&e))?),
+        other => {
+            return Err(RunnerError::invalid_export(
+                name,
+                format!("{:?} globals can't be set from the command line", other),
+            ))
+        }
+    };
+
+    global
+        .set(store, value)
+        .map_err(|e| RunnerError::invalid_export(name, e.message()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+    use crate::WasmDemoRunner;
+
+    // copies `speed` into the first pixel every tick
+    const SPEED: &str = r#"(module
+        (memory (export "memory") 4)
+        (global (export "speed") (mut f32) (f32.const 1))
+        (global (export "fixed") f32 (f32.const 1))
+        (func (export "tick")
+            (f32.store (i32.const 0) (global.get 0))))"#;
+
+    #[test]
+    fn params_set_globals_the_module_reads() {
+        let mut runner = runner(SPEED, &["--param", "speed=2.5"]);
+        let frame = runner.tick_once().unwrap();
+        assert_eq!(frame[..4], 2.5f32.to_le_bytes());
+
+        for invalid in ["speed=fast", "fixed=2", "missing=1"] {
+            let config = config(SPEED, &["--param", invalid]);
+            assert!(WasmDemoRunner::new(&config).is_err(), "{}", invalid);
+        }
+    }
+}
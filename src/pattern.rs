@@ -0,0 +1,78 @@
+use clap::ValueEnum;
+
+use crate::format::PixelFormat;
+
+/// synthetic images used in place of module output to exercise the display path on its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TestPattern {
+    /// eight vertical color bars: white, yellow, cyan, green, magenta, red, blue, black
+    Bars,
+    /// alternating black and white 32x32 squares
+    Checker,
+    /// red increasing left to right, green increasing top to bottom
+    Gradient,
+}
+
+const BARS: [[u8; 4]; 8] = [
+    [0xff, 0xff, 0xff, 0xff],
+    [0xff, 0xff, 0x00, 0xff],
+    [0x00, 0xff, 0xff, 0xff],
+    [0x00, 0xff, 0x00, 0xff],
+    [0xff, 0x00, 0xff, 0xff],
+    [0xff, 0x00, 0x00, 0xff],
+    [0x00, 0x00, 0xff, 0xff],
+    [0x00, 0x00, 0x00, 0xff],
+];
+
+const CHECKER_SIZE: usize = 32;
+
+impl TestPattern {
+    /// the color of the pixel at (`x`, `y`) in a `width` x `height` image
+    pub fn pixel(self, x: usize, y: usize, width: usize, height: usize) -> [u8; 4] {
+        match self {
+            TestPattern::Bars => BARS[x * BARS.len() / width.max(1)],
+            TestPattern::Checker => {
+                if (x / CHECKER_SIZE + y / CHECKER_SIZE).is_multiple_of(2) {
+                    [0xff, 0xff, 0xff, 0xff]
+                } else {
+                    [0x00, 0x00, 0x00, 0xff]
+                }
+            }
+            TestPattern::Gradient => {
+                let r = x * 255 / width.saturating_sub(1).max(1);
+                let g = y * 255 / height.saturating_sub(1).max(1);
+                [r as u8, g as u8, 0x00, 0xff]
+            }
+        }
+    }
+
+    /// fills `buf`, a `width` x `height` image in `format`, with this pattern
+    pub fn fill(self, buf: &mut [u8], width: usize, height: usize, format: PixelFormat) {
+        let bpp = format.bytes_per_pixel();
+        let len = width * height * bpp;
+        for (i, pixel) in buf[..len].chunks_exact_mut(bpp).enumerate() {
+            let rgba = self.pixel(i % width, i / width, width, height);
+            format.write_rgba8(rgba, pixel);
+        }
+        // neutral chroma, leaving `Yuv420p` patterns in grayscale
+        buf[len..].fill(0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bars_are_eight_columns() {
+        let (width, height) = (16, 2);
+        let mut buf = vec![0; width * height * 4];
+        TestPattern::Bars.fill(&mut buf, width, height, PixelFormat::Rgba8);
+        for (i, pixel) in buf.chunks_exact(4).enumerate() {
+            // two pixels per bar, the same on every row
+            assert_eq!(pixel, BARS[i % width / 2], "pixel {}", i);
+        }
+        assert_eq!(&buf[..4], [0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(&buf[buf.len() - 4..], [0x00, 0x00, 0x00, 0xff]);
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+// width of the bar drawn for a stage taking all of the profiled time
+const BAR_WIDTH: usize = 40;
+
+/// `--profile`: how long each stage of producing frames took over the whole run, in the order
+/// stages first ran
+#[derive(Debug, Default)]
+pub struct Profile {
+    stages: Vec<Stage>,
+}
+
+#[derive(Debug)]
+struct Stage {
+    name: &'static str,
+    total: Duration,
+    calls: u64,
+}
+
+impl Profile {
+    pub fn record(&mut self, name: &'static str, elapsed: Duration) {
+        match self.stages.iter_mut().find(|stage| stage.name == name) {
+            Some(stage) => {
+                stage.total += elapsed;
+                stage.calls += 1;
+            }
+            None => self.stages.push(Stage {
+                name,
+                total: elapsed,
+                calls: 1,
+            }),
+        }
+    }
+
+    /// prints `summary`
+    pub fn report(&self) {
+        print!("{}", self.summary());
+    }
+
+    /// a line for every stage with its total and average time along with a bar of its share of
+    /// the total
+    pub fn summary(&self) -> String {
+        let total: Duration = self.stages.iter().map(|stage| stage.total).sum();
+        let mut summary = String::from("profile:\n");
+        for stage in &self.stages {
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                stage.total.as_secs_f64() / total.as_secs_f64()
+            };
+            let _ = writeln!(
+                summary,
+                "  {:<16} {:>12.3?} total {:>12.3?} avg {:>5.1}% {}",
+                stage.name,
+                stage.total,
+                stage.total.div_f64(stage.calls as f64),
+                share * 100.0,
+                "#".repeat((share * BAR_WIDTH as f64).round() as usize),
+            );
+        }
+        summary
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::blend;
+use crate::dither::Dither;
+use crate::error::RunnerError;
+use crate::screenshot;
+use crate::ui::PublishedFrame;
+
+/// how wide thumbnails in the contact sheet are, in pixels
+pub const THUMBNAIL_WIDTH: usize = 96;
+// thumbnails per row of the contact sheet
+const COLUMNS: usize = 4;
+
+/// `--replay-dir`: keeps small copies of the last few frames so that when the runner stops
+/// because of an error, e.g. a trap, what led up to it can be looked at afterwards
+pub struct Replay {
+    dir: PathBuf,
+    capacity: usize,
+    frames: VecDeque<Thumbnail>,
+    rgba: Vec<u8>,
+}
+
+struct Thumbnail {
+    index: u64,
+    checksum: u64,
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+}
+
+impl Replay {
+    pub fn new(dir: &Path, capacity: usize) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            capacity: capacity.max(1),
+            frames: VecDeque::new(),
+            rgba: Vec::new(),
+        }
+    }
+
+    /// keeps a thumbnail of `published`, dropping the oldest one once there are `capacity`
+    pub fn record(&mut self, published: &PublishedFrame, checksum: u64, dither: Dither) {
+        let (width, height) = (published.width as usize, published.height as usize);
+        if width == 0 || height == 0 {
+            return;
+        }
+        published.format.to_rgba8(
+            &published.frame,
+            width,
+            dither,
+            &published.palette,
+            &mut self.rgba,
+        );
+        if published.premultiplied {
+            blend::unpremultiply(&mut self.rgba);
+        }
+
+        // nearest neighbor, which is plenty to recognize what was on screen
+        let thumb_width = THUMBNAIL_WIDTH.min(width);
+        let thumb_height = (height * thumb_width / width).max(1);
+        let mut rgba = Vec::with_capacity(thumb_width * thumb_height * 4);
+        for y in 0..thumb_height {
+            let src_y = y * height / thumb_height;
+            for x in 0..thumb_width {
+                let offset = (src_y * width + x * width / thumb_width) * 4;
+                rgba.extend_from_slice(self.rgba.get(offset..offset + 4).unwrap_or(&[0; 4]));
+            }
+        }
+
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Thumbnail {
+            index: published.index,
+            checksum,
+            width: thumb_width,
+            height: thumb_height,
+            rgba,
+        });
+    }
+
+    /// writes the kept frames, oldest first, as `contact.png` and their indices and checksums
+    /// along with `error` to `replay.log`, returning the directory they were written to
+    pub fn dump(&self, error: &str) -> Result<&Path, RunnerError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut log = String::new();
+        for frame in &self.frames {
+            let _ = writeln!(log, "frame {} {:016x}", frame.index, frame.checksum);
+        }
+        let _ = writeln!(log, "error: {}", error);
+        fs::write(self.dir.join("replay.log"), log)?;
+
+        if self.frames.is_empty() {
+            return Ok(&self.dir);
+        }
+        let cell_width = self.frames.iter().map(|f| f.width).max().unwrap_or(0);
+        let cell_height = self.frames.iter().map(|f| f.height).max().unwrap_or(0);
+        let columns = COLUMNS.min(self.frames.len());
+        let rows = self.frames.len().div_ceil(columns);
+        let (width, height) = (cell_width * columns, cell_height * rows);
+
+        let mut sheet = vec![0; width * height * 4];
+        for (i, frame) in self.frames.iter().enumerate() {
+            let (left, top) = ((i % columns) * cell_width, (i / columns) * cell_height);
+            for (y, row) in frame.rgba.chunks_exact(frame.width * 4).enumerate() {
+                let offset = ((top + y) * width + left) * 4;
+                sheet[offset..offset + row.len()].copy_from_slice(row);
+            }
+        }
+        screenshot::save_png(
+            &self.dir.join("contact.png"),
+            &sheet,
+            width as u32,
+            height as u32,
+        )?;
+        Ok(&self.dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::test_util::*;
+
+    #[test]
+    fn trapping_modules_leave_a_replay() {
+        let dir = temp_dir("replay").join("replay");
+        // counts its ticks in the first pixel, trapping on the fourth
+        let mut trapping = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")
+                    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+                    (if (i32.eq (i32.load (i32.const 0)) (i32.const 4)) (then unreachable))))"#,
+            &[
+                "--replay-dir",
+                dir.to_str().unwrap(),
+                "--replay-frames",
+                "2",
+                "--internal-res",
+                "4x2",
+                "--quiet",
+            ],
+        );
+        let mut sink = Collect::default();
+        assert!(run_until_stopped(&mut trapping, &mut sink).is_err());
+
+        let log = std::fs::read_to_string(dir.join("replay.log")).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+        // the last two of the three frames published before the trap, and what stopped the run
+        assert!(lines[0].starts_with("frame 1 "), "{}", log);
+        assert!(lines[1].starts_with("frame 2 "), "{}", log);
+        assert!(lines[2].starts_with("error: module trapped"), "{}", log);
+
+        // the two frames kept side by side, oldest first
+        let decoder = png::Decoder::new(File::open(dir.join("contact.png")).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut sheet = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut sheet).unwrap();
+        assert_eq!((info.width, info.height), (8, 2));
+        assert_eq!([sheet[0], sheet[16]], [2, 3]);
+    }
+}
@@ -0,0 +1,143 @@
+use wasmer::{ExternType, Module, Type};
+
+use crate::error::RunnerError;
+use crate::imports;
+
+/// `--require`: exports a module must have before it's run, each optionally with the signature
+/// the function must have
+#[derive(Clone, Debug)]
+pub struct Requirements(Vec<RequiredExport>);
+
+#[derive(Clone, Debug)]
+struct RequiredExport {
+    name: String,
+    // params and results, for requirements written as `name(params)->results`
+    signature: Option<(Vec<Type>, Vec<Type>)>,
+}
+
+/// parses a comma separated list of export names, each of which may be followed by a function
+/// signature such as `resize(i32,i32)` or `is_done()->i32`
+pub fn parse_requirements(s: &str) -> Result<Requirements, String> {
+    let mut required = Vec::new();
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        // commas inside a signature's parentheses don't end the entry
+        let mut depth = 0;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                c == ',' && depth == 0
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        required.push(parse_entry(rest[..end].trim())?);
+        rest = rest[end..].trim_start_matches(',').trim();
+    }
+    if required.is_empty() {
+        return Err("expected at least one export name".to_string());
+    }
+    Ok(Requirements(required))
+}
+
+fn parse_entry(entry: &str) -> Result<RequiredExport, String> {
+    let Some((name, signature)) = entry.split_once('(') else {
+        if entry.is_empty() {
+            return Err("empty export name".to_string());
+        }
+        return Ok(RequiredExport {
+            name: entry.to_string(),
+            signature: None,
+        });
+    };
+    let invalid = || format!("expected name(params)->results, got '{}'", entry);
+    let (params, results) = signature.split_once(')').ok_or_else(invalid)?;
+    let results = match results.trim() {
+        "" => "",
+        results => results.strip_prefix("->").ok_or_else(invalid)?,
+    };
+    Ok(RequiredExport {
+        name: name.trim().to_string(),
+        signature: Some((parse_types(params)?, parse_types(results)?)),
+    })
+}
+
+fn parse_types(list: &str) -> Result<Vec<Type>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|ty| !ty.is_empty())
+        .map(|ty| match ty {
+            "i32" => Ok(Type::I32),
+            "i64" => Ok(Type::I64),
+            "f32" => Ok(Type::F32),
+            "f64" => Ok(Type::F64),
+            "v128" => Ok(Type::V128),
+            "externref" => Ok(Type::ExternRef),
+            "funcref" => Ok(Type::FuncRef),
+            _ => Err(format!("unknown type '{}'", ty)),
+        })
+        .collect()
+}
+
+/// fails with every requirement `module` doesn't meet, before it's instantiated
+pub fn check(module: &Module, requirements: &Requirements) -> Result<(), RunnerError> {
+    let mut unmet = Vec::new();
+    for required in &requirements.0 {
+        let export = module.exports().find(|e| e.name() == required.name);
+        let Some(export) = export else {
+            unmet.push(format!("{} (missing)", required.name));
+            continue;
+        };
+        let Some((params, results)) = &required.signature else {
+            continue;
+        };
+        match export.ty() {
+            ExternType::Function(ty) if ty.params() == params && ty.results() == results => {}
+            ty => unmet.push(format!(
+                "{} (expected function {:?} -> {:?}, found {})",
+                required.name,
+                params,
+                results,
+                imports::describe(ty)
+            )),
+        }
+    }
+    if unmet.is_empty() {
+        Ok(())
+    } else {
+        Err(RunnerError::UnmetRequirements(unmet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::WasmDemoRunner;
+
+    #[test]
+    fn missing_exports_are_reported_before_running() {
+        let has_tick_and_resize = r#"(module
+            (memory (export "memory") 4)
+            (func (export "resize") (param i32 i32))
+            (func (export "tick")))"#;
+        let requiring = |required| config(has_tick_and_resize, &["--require", required]);
+
+        assert!(WasmDemoRunner::new(&requiring("tick,resize(i32, i32)")).is_ok());
+        match WasmDemoRunner::new(&requiring("tick,image_buffer,resize(i32,i32)->i32")) {
+            Err(RunnerError::UnmetRequirements(unmet)) => {
+                assert_eq!(unmet.len(), 2, "{:?}", unmet);
+                assert_eq!(unmet[0], "image_buffer (missing)");
+                assert!(
+                    unmet[1].starts_with("resize (expected function"),
+                    "{}",
+                    unmet[1]
+                );
+            }
+            other => panic!("expected UnmetRequirements, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::f64::consts::FRAC_PI_2;
+
+use clap::ValueEnum;
+
+/// clockwise rotation applied to frames when they're displayed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Rotation {
+    #[value(name = "90")]
+    Cw90,
+    #[value(name = "180")]
+    Cw180,
+    #[value(name = "270")]
+    Cw270,
+}
+
+impl Rotation {
+    pub fn radians(self) -> f64 {
+        match self {
+            Rotation::Cw90 => FRAC_PI_2,
+            Rotation::Cw180 => 2.0 * FRAC_PI_2,
+            Rotation::Cw270 => 3.0 * FRAC_PI_2,
+        }
+    }
+
+    /// whether the rotated frame is as wide as the original is tall
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Rotation::Cw90 | Rotation::Cw270)
+    }
+}
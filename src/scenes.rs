@@ -0,0 +1,47 @@
+use druid::{Data, Lens};
+use wasmer::{Instance, Store};
+
+use crate::error::RunnerError;
+use crate::optional_function;
+
+/// the longest scene name read from a module, in bytes
+pub const MAX_SCENE_NAME_LEN: usize = 64;
+
+/// one of the scenes a module exports through `scene_count` and `scene_name`
+#[derive(Clone, Debug, Data, Lens)]
+pub struct Scene {
+    pub index: i32,
+    pub name: String,
+}
+
+/// lists the module's scenes, empty unless it exports `scene_count() -> i32`. the name of scene
+/// `i` comes from `scene_name(i, buf_ptr, buf_len) -> i32`, which writes up to `buf_len` bytes of
+/// UTF-8 at `buf_ptr` and returns how many it wrote. the buffer is the start of the frame, which
+/// the next tick overwrites. scenes without `scene_name` are named after their number.
+pub fn discover(
+    store: &mut Store,
+    instance: &Instance,
+    memory_name: &str,
+) -> Result<Vec<Scene>, RunnerError> {
+    let Some(count) = optional_function::<(), i32>(store, instance, "scene_count")? else {
+        return Ok(Vec::new());
+    };
+    let count = count.call(store)?;
+    let scene_name = optional_function::<(i32, i32, i32), i32>(store, instance, "scene_name")?;
+    let memory = instance.exports.get_memory(memory_name)?;
+
+    let mut scenes = Vec::new();
+    for index in 0..count.max(0) {
+        let name = match &scene_name {
+            Some(scene_name) => {
+                let len = scene_name.call(store, index, 0, MAX_SCENE_NAME_LEN as i32)?;
+                let mut name = vec![0; (len.max(0) as usize).min(MAX_SCENE_NAME_LEN)];
+                memory.view(store).read(0, &mut name)?;
+                String::from_utf8_lossy(&name).into_owned()
+            }
+            None => format!("scene {}", index + 1),
+        };
+        scenes.push(Scene { index, name });
+    }
+    Ok(scenes)
+}
@@ -0,0 +1,363 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use crate::blend;
+use crate::dither::Dither;
+use crate::error::RunnerError;
+use crate::ui::PublishedFrame;
+
+/// writes a `width` x `height` 8 bit RGBA image to `path` as a PNG
+pub fn save_png(path: &Path, rgba: &[u8], width: u32, height: u32) -> Result<(), RunnerError> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let png_error = |e: png::EncodingError| RunnerError::Frame(e.to_string());
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(rgba).map_err(png_error)?;
+    writer.finish().map_err(png_error)
+}
+
+/// the file format screenshots are saved in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScreenshotFormat {
+    Png,
+    /// uncompressed 32 bit BMP, for tooling that can't read PNG
+    Bmp,
+}
+
+impl ScreenshotFormat {
+    /// writes a `width` x `height` 8 bit RGBA image to `path` in this format
+    pub fn save(
+        self,
+        path: &Path,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<(), RunnerError> {
+        match self {
+            ScreenshotFormat::Png => save_png(path, rgba, width, height),
+            ScreenshotFormat::Bmp => save_bmp(path, rgba, width, height),
+        }
+    }
+}
+
+// the sizes of the BITMAPFILEHEADER and the BITMAPV4HEADER, which unlike the basic info header
+// can say where the alpha channel is
+const BMP_FILE_HEADER_LEN: u32 = 14;
+const BMP_INFO_HEADER_LEN: u32 = 108;
+
+/// writes a `width` x `height` 8 bit RGBA image to `path` as a 32 bit BMP with an alpha channel.
+/// rows are stored bottom up, each padded to a multiple of 4 bytes.
+pub fn save_bmp(path: &Path, rgba: &[u8], width: u32, height: u32) -> Result<(), RunnerError> {
+    const BYTES_PER_PIXEL: u32 = 4;
+    let row_len = (width * BYTES_PER_PIXEL).div_ceil(4) * 4;
+    let padding = vec![0; (row_len - width * BYTES_PER_PIXEL) as usize];
+    let data_offset = BMP_FILE_HEADER_LEN + BMP_INFO_HEADER_LEN;
+    let image_len = row_len * height;
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"BM")?;
+    file.write_all(&(data_offset + image_len).to_le_bytes())?;
+    file.write_all(&[0; 4])?;
+    file.write_all(&data_offset.to_le_bytes())?;
+
+    file.write_all(&BMP_INFO_HEADER_LEN.to_le_bytes())?;
+    file.write_all(&(width as i32).to_le_bytes())?;
+    // a positive height means bottom up
+    file.write_all(&(height as i32).to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?;
+    file.write_all(&(BYTES_PER_PIXEL as u16 * 8).to_le_bytes())?;
+    // BI_BITFIELDS, with the red, green, blue and alpha masks following
+    file.write_all(&3u32.to_le_bytes())?;
+    file.write_all(&image_len.to_le_bytes())?;
+    // 72 dpi, in pixels per meter
+    file.write_all(&2835u32.to_le_bytes())?;
+    file.write_all(&2835u32.to_le_bytes())?;
+    // no palette
+    file.write_all(&[0; 8])?;
+    for mask in [0x00ff_0000u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+        file.write_all(&mask.to_le_bytes())?;
+    }
+    // LCS_sRGB, which leaves the endpoints and gamma unused
+    file.write_all(b"BGRs")?;
+    file.write_all(&[0; 48])?;
+
+    let row_bytes = (width * BYTES_PER_PIXEL) as usize;
+    for row in rgba.chunks_exact(row_bytes).rev() {
+        for pixel in row.chunks_exact(4) {
+            file.write_all(&[pixel[2], pixel[1], pixel[0], pixel[3]])?;
+        }
+        file.write_all(&padding)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// `--png-sequence`: writes every frame to its own PNG, named by a pattern with a printf style
+/// `%d` or zero padded `%05d` placeholder for the frame index, e.g. `out/frame_%05d.png`
+#[derive(Debug)]
+pub struct PngSequence {
+    prefix: String,
+    padding: usize,
+    suffix: String,
+    rgba: Vec<u8>,
+}
+
+impl PngSequence {
+    /// parses `pattern` and creates the directory it points into if it doesn't exist yet
+    pub fn new(pattern: &str) -> Result<Self, RunnerError> {
+        let invalid = || {
+            RunnerError::Frame(format!(
+                "png sequence pattern '{}' needs one %d or %0Nd placeholder",
+                pattern
+            ))
+        };
+        let (prefix, rest) = pattern.split_once('%').ok_or_else(invalid)?;
+        let (spec, suffix) = rest.split_once('d').ok_or_else(invalid)?;
+        let padding = match spec {
+            "" => 0,
+            _ if spec.starts_with('0') => spec.parse().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+        if suffix.contains('%') {
+            return Err(invalid());
+        }
+
+        let sequence = Self {
+            prefix: prefix.to_string(),
+            padding,
+            suffix: suffix.to_string(),
+            rgba: Vec::new(),
+        };
+        if let Some(dir) = sequence.path(0).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(sequence)
+    }
+
+    /// the file frame `index` is written to
+    pub fn path(&self, index: u64) -> PathBuf {
+        PathBuf::from(format!(
+            "{}{:0padding$}{}",
+            self.prefix,
+            index,
+            self.suffix,
+            padding = self.padding
+        ))
+    }
+
+    /// converts a frame to 8 bit RGBA and writes it to the file for its index
+    pub fn write(&mut self, published: &PublishedFrame, dither: Dither) -> Result<(), RunnerError> {
+        let (width, height) = (published.width, published.height);
+        published.format.to_rgba8(
+            &published.frame,
+            width as usize,
+            dither,
+            &published.palette,
+            &mut self.rgba,
+        );
+        if published.premultiplied {
+            blend::unpremultiply(&mut self.rgba);
+        }
+        save_png(&self.path(published.index), &self.rgba, width, height)
+    }
+}
+
+/// `--capture-on-change`: writes a frame to `DIR/NNNNN.png`, named by its index, whenever its
+/// checksum differs from the last frame captured, until `limit` frames have been captured
+#[derive(Debug)]
+pub struct ChangeCapture {
+    sequence: PngSequence,
+    last_checksum: Option<u64>,
+    captured: u32,
+    limit: u32,
+}
+
+impl ChangeCapture {
+    /// creates `dir` if it doesn't exist yet
+    pub fn new(dir: &Path, limit: u32) -> Result<Self, RunnerError> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            sequence: PngSequence {
+                prefix: format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR),
+                padding: 5,
+                suffix: ".png".to_string(),
+                rgba: Vec::new(),
+            },
+            last_checksum: None,
+            captured: 0,
+            limit,
+        })
+    }
+
+    /// captures `published`, whose checksum is `checksum`, if it changed since the last capture
+    pub fn record(
+        &mut self,
+        published: &PublishedFrame,
+        checksum: u64,
+        dither: Dither,
+    ) -> Result<(), RunnerError> {
+        if self.captured >= self.limit || self.last_checksum == Some(checksum) {
+            return Ok(());
+        }
+        self.sequence.write(published, dither)?;
+        self.last_checksum = Some(checksum);
+        self.captured += 1;
+        if self.captured == self.limit {
+            tracing::info!(
+                "captured {} frames, the most --max-captures allows",
+                self.limit
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn png_sequences_write_a_file_per_frame() {
+        let dir = temp_dir("png-sequence").join("missing");
+        let pattern = dir.join("frame_%05d.png");
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")))"#,
+            &[
+                "--png-sequence",
+                pattern.to_str().unwrap(),
+                "--frames",
+                "3",
+                "--internal-res",
+                "8x4",
+                "--quiet",
+            ],
+        );
+        run_until_stopped(&mut runner, &mut Collect::default()).unwrap();
+
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["frame_00000.png", "frame_00001.png", "frame_00002.png"]
+        );
+        for name in names {
+            let decoder = png::Decoder::new(File::open(dir.join(name)).unwrap());
+            let info = decoder.read_info().unwrap().info().clone();
+            assert_eq!((info.width, info.height), (8, 4));
+        }
+    }
+
+    #[test]
+    fn bmps_decode_back_to_the_original_pixels() {
+        let (width, height) = (3, 2);
+        let rgba: Vec<u8> = (0..width * height * 4).map(|i| (i * 10) as u8).collect();
+        let path = temp_dir("bmp").join("shot.bmp");
+        ScreenshotFormat::Bmp
+            .save(&path, &rgba, width, height)
+            .unwrap();
+
+        let bmp = fs::read(&path).unwrap();
+        let u32_at = |at: usize| u32::from_le_bytes(bmp[at..at + 4].try_into().unwrap());
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(u32_at(2) as usize, bmp.len());
+        assert_eq!((u32_at(18), u32_at(22)), (width, height));
+        let data = &bmp[u32_at(10) as usize..];
+        let row_len = width as usize * 4;
+        // rows are stored bottom up in BGRA order
+        let decoded: Vec<u8> = data
+            .chunks_exact(row_len)
+            .rev()
+            .flat_map(|row| row.chunks_exact(4))
+            .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+            .collect();
+        assert_eq!(decoded, rgba);
+    }
+
+    #[test]
+    fn only_changed_frames_are_captured() {
+        let captured = |name, wat, args: &[&str]| {
+            let dir = temp_dir(name).join("captures");
+            let dir_arg = dir.to_str().unwrap();
+            let common = [
+                "--capture-on-change",
+                dir_arg,
+                "--internal-res",
+                "4x4",
+                "--quiet",
+            ];
+            let mut runner = runner(wat, &[&common[..], args].concat());
+            run_until_stopped(&mut runner, &mut Collect::default()).unwrap();
+            let mut names: Vec<_> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let still = r#"(module
+            (memory (export "memory") 1)
+            (func (export "resize") (param i32 i32))
+            (func (export "tick")))"#;
+        assert_eq!(captured("still", still, &["--frames", "5"]), ["00000.png"]);
+
+        // counts its ticks in the first pixel
+        let counting = r#"(module
+            (memory (export "memory") 1)
+            (func (export "resize") (param i32 i32))
+            (func (export "tick")
+                (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))))"#;
+        let args = ["--frames", "5", "--max-captures", "3"];
+        assert_eq!(
+            captured("counting", counting, &args),
+            ["00000.png", "00001.png", "00002.png"]
+        );
+    }
+
+    #[test]
+    fn premultiplied_frames_are_saved_with_straight_alpha() {
+        let dir = temp_dir("premultiplied");
+        let pattern = dir.join("frame_%d.png");
+        // half transparent orange, opaque white and fully transparent, all premultiplied
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\80\40\00\80\ff\ff\ff\ff\00\00\00\00")
+                (func (export "resize") (param i32 i32))
+                (func (export "tick")))"#,
+            &[
+                "--premultiplied",
+                "--png-sequence",
+                pattern.to_str().unwrap(),
+                "--frames",
+                "1",
+                "--internal-res",
+                "3x1",
+                "--quiet",
+            ],
+        );
+        run_until_stopped(&mut runner, &mut Collect::default()).unwrap();
+
+        let decoder = png::Decoder::new(File::open(dir.join("frame_0.png")).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut rgba).unwrap();
+        assert_eq!(
+            rgba,
+            [0xff, 0x80, 0, 0x80, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use wasmer::{Engine, Function, FunctionEnv, Instance, Module, Store, TypedFunction};
+
+use crate::error::RunnerError;
+use crate::format::PixelFormat;
+use crate::imports::HostEnv;
+use crate::time::TimeSource;
+use crate::{export_kind, imports, params};
+
+/// renders `shade` frames across several threads. wasmer instances can't be shared between
+/// threads so every worker owns a separate instance of the module and renders a horizontal band
+/// of each frame with it.
+pub struct ShadePool {
+    workers: Vec<Worker>,
+}
+
+struct Worker {
+    jobs: Sender<Band>,
+    results: Receiver<Result<Vec<u8>, RunnerError>>,
+}
+
+struct Band {
+    rows: Range<usize>,
+    width: usize,
+    format: PixelFormat,
+}
+
+impl ShadePool {
+    pub fn new(
+        module: &Module,
+        engine: &Engine,
+        namespace: &str,
+        memory_name: &str,
+        params: &[(String, String)],
+        time_source: TimeSource,
+        threads: usize,
+    ) -> Result<Self, RunnerError> {
+        let workers = (0..threads.max(1))
+            .map(|index| {
+                Worker::spawn(
+                    index,
+                    module,
+                    engine,
+                    namespace,
+                    memory_name,
+                    params,
+                    time_source,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { workers })
+    }
+
+    /// fills `buf`, a `width` x `height` frame in `format`, splitting its rows between workers
+    pub fn shade(
+        &self,
+        buf: &mut [u8],
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Result<(), RunnerError> {
+        let band_height = height.div_ceil(self.workers.len());
+        let row_bytes = width * format.bytes_per_pixel();
+
+        // every worker gets a band, even an empty one, so each instance ticks exactly once
+        let mut bands = Vec::with_capacity(self.workers.len());
+        for (i, worker) in self.workers.iter().enumerate() {
+            let start = (i * band_height).min(height);
+            let end = (start + band_height).min(height);
+            let band = Band {
+                rows: start..end,
+                width,
+                format,
+            };
+            worker
+                .jobs
+                .send(band)
+                .map_err(|_| RunnerError::RunnerPanicked)?;
+            bands.push(start..end);
+        }
+
+        for (worker, rows) in self.workers.iter().zip(bands) {
+            let pixels = worker
+                .results
+                .recv()
+                .map_err(|_| RunnerError::RunnerPanicked)??;
+            buf[rows.start * row_bytes..rows.end * row_bytes].copy_from_slice(&pixels);
+        }
+        // neutral chroma, like the single-threaded path
+        buf[height * row_bytes..].fill(0x80);
+        Ok(())
+    }
+}
+
+impl Worker {
+    fn spawn(
+        index: usize,
+        module: &Module,
+        engine: &Engine,
+        namespace: &str,
+        memory_name: &str,
+        params: &[(String, String)],
+        time_source: TimeSource,
+    ) -> Result<Self, RunnerError> {
+        let mut store = Store::new(engine.clone());
+        let (import_object, host_env) = imports::host_imports(&mut store, namespace, time_source);
+        let instance = Instance::new(&mut store, module, &import_object)?;
+        if let Ok(memory) = instance.exports.get_memory(memory_name) {
+            host_env.as_mut(&mut store).memory = Some(memory.clone());
+        }
+        for (name, value) in params {
+            params::set_global(&mut store, &instance, name, value)?;
+        }
+
+        let tick = match export_kind(&store, &instance, "tick") {
+            Some(_) => Some(instance.exports.get_function("tick")?.clone()),
+            None => None,
+        };
+        let shade = instance
+            .exports
+            .get_typed_function::<(i32, i32), i32>(&store, "shade")?;
+
+        let (jobs, job_receiver) = mpsc::channel::<Band>();
+        let (result_sender, results) = mpsc::channel();
+
+        thread::Builder::new()
+            .name(format!("wasm-shade-{}", index))
+            .spawn(move || {
+                for band in job_receiver {
+                    let result = render_band(&mut store, &host_env, tick.as_ref(), &shade, &band);
+                    if result_sender.send(result).is_err() {
+                        return;
+                    }
+                }
+            })?;
+
+        Ok(Self { jobs, results })
+    }
+}
+
+fn render_band(
+    store: &mut Store,
+    host_env: &FunctionEnv<HostEnv>,
+    tick: Option<&Function>,
+    shade: &TypedFunction<(i32, i32), i32>,
+    band: &Band,
+) -> Result<Vec<u8>, RunnerError> {
+    // workers have no framebuffer to draw into, but what they draw is still recorded
+    host_env.as_mut(store).drawing.clear();
+    if let Some(tick) = tick {
+        tick.call(store, &[])?;
+    }
+
+    let bpp = band.format.bytes_per_pixel();
+    let mut pixels = vec![0; band.rows.len() * band.width * bpp];
+    for (i, pixel) in pixels.chunks_exact_mut(bpp).enumerate() {
+        let x = i % band.width;
+        let y = band.rows.start + i / band.width;
+        let color = shade.call(store, x as i32, y as i32)?;
+        band.format.write_rgba8(color.to_le_bytes(), pixel);
+    }
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::*;
+
+    // the names of this process's threads, as profilers see them
+    #[cfg(target_os = "linux")]
+    fn thread_names() -> Vec<String> {
+        std::fs::read_dir("/proc/self/task")
+            .unwrap()
+            .filter_map(|task| std::fs::read_to_string(task.unwrap().path().join("comm")).ok())
+            .map(|name| name.trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn workers_are_named_by_index() {
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "resize") (param i32 i32))
+                (func (export "shade") (param i32 i32) (result i32) (i32.const 0)))"#,
+            &[
+                "--per-pixel",
+                "--per-pixel-threads",
+                "3",
+                "--internal-res",
+                "4x4",
+            ],
+        );
+        // a thread only takes its name once it starts running, which it has by the time it's
+        // shaded a band
+        runner.tick_once().unwrap();
+        let names = thread_names();
+        for worker in ["wasm-shade-0", "wasm-shade-1", "wasm-shade-2"] {
+            assert!(names.iter().any(|name| name == worker), "{:?}", names);
+        }
+        drop(runner);
+    }
+}
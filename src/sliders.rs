@@ -0,0 +1,95 @@
+use druid::{Data, Lens};
+use wasmer::{Extern, Instance, Mutability, Store, Value};
+
+/// exported mutable `f32` globals whose names start with this get a slider in the window
+pub const UI_GLOBAL_PREFIX: &str = "ui_";
+
+/// a slider bound to one of the module's globals. the slider itself works on a normalized
+/// position so every slider can share the same widget regardless of its range.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct GlobalSlider {
+    pub name: String,
+    pub position: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl GlobalSlider {
+    /// creates a slider for a global currently holding `value`. globals in [0, 1] get that range,
+    /// anything else gets a range with room on either side of its initial value.
+    pub fn new(name: String, value: f64) -> Self {
+        let (min, max) = if (0.0..=1.0).contains(&value) {
+            (0.0, 1.0)
+        } else {
+            let span = value.abs() * 2.0;
+            (value.min(0.0) - span / 2.0, value.max(0.0) + span / 2.0)
+        };
+
+        Self {
+            name,
+            position: (value - min) / (max - min),
+            min,
+            max,
+        }
+    }
+
+    /// the value the global should hold for the slider's current position
+    pub fn value(&self) -> f64 {
+        self.min + self.position * (self.max - self.min)
+    }
+}
+
+/// finds the module's exported mutable `f32` globals named with `UI_GLOBAL_PREFIX`, in export
+/// order
+pub fn discover(store: &mut Store, instance: &Instance) -> Vec<GlobalSlider> {
+    let globals: Vec<_> = instance
+        .exports
+        .iter()
+        .filter(|(name, _)| name.starts_with(UI_GLOBAL_PREFIX))
+        .filter_map(|(name, export)| match export {
+            Extern::Global(global) => Some((name.clone(), global.clone())),
+            _ => None,
+        })
+        .collect();
+
+    globals
+        .into_iter()
+        .filter_map(|(name, global)| {
+            if global.ty(store).mutability != Mutability::Var {
+                return None;
+            }
+            match global.get(store) {
+                Value::F32(value) => Some(GlobalSlider::new(name, value as f64)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn discovers_mutable_f32_ui_globals() {
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (global (export "ui_speed") (mut f32) (f32.const 0.25))
+                (global (export "ui_fixed") f32 (f32.const 1))
+                (global (export "ui_count") (mut i32) (i32.const 1))
+                (global (export "zoom") (mut f32) (f32.const 1))
+                (global (export "ui_scale") (mut f32) (f32.const 4))
+                (func (export "tick")))"#,
+            &[],
+        );
+        let sliders = discover(&mut runner.wasm_store, &runner.module_instance);
+        let names: Vec<_> = sliders.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["ui_speed", "ui_scale"]);
+        assert_eq!((sliders[0].min, sliders[0].max), (0.0, 1.0));
+        assert_eq!(sliders[0].value(), 0.25);
+        assert_eq!((sliders[1].min, sliders[1].max), (-4.0, 8.0));
+        assert_eq!(sliders[1].value(), 4.0);
+    }
+}
@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+
+/// number of samples analyzed per tick; must be a power of two
+pub const WINDOW: usize = 1024;
+/// number of bins modules can read through `spectrum(bin)`
+pub const BINS: usize = 64;
+
+/// magnitudes of an audio window grouped into `BINS` equally wide bins. bin `i` covers
+/// frequencies from `i * sample_rate / (2 * BINS)` up to the next bin, so the bins together span
+/// 0 Hz up to the Nyquist frequency; at 44.1kHz each one is about 345Hz wide.
+///
+/// a bin holds the largest magnitude of the FFT bins it covers, normalized so that a full scale
+/// sine wave reads close to 1.0.
+#[derive(Clone, Debug)]
+pub struct Spectrum {
+    bins: [f32; BINS],
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self { bins: [0.0; BINS] }
+    }
+}
+
+impl Spectrum {
+    /// analyzes `WINDOW` mono samples after applying a Hann window to them
+    pub fn analyze(samples: &[f32; WINDOW]) -> Self {
+        let mut window_sum = 0.0;
+        let mut re = [0.0; WINDOW];
+        for (i, (out, sample)) in re.iter_mut().zip(samples).enumerate() {
+            let weight = 0.5 - 0.5 * (2.0 * PI * i as f32 / WINDOW as f32).cos();
+            window_sum += weight;
+            *out = sample * weight;
+        }
+        let mut im = [0.0; WINDOW];
+        fft(&mut re, &mut im);
+
+        // only the first half of the FFT is meaningful for real input
+        let per_bin = WINDOW / 2 / BINS;
+        let mut bins = [0.0; BINS];
+        for (i, bin) in bins.iter_mut().enumerate() {
+            *bin = (i * per_bin..(i + 1) * per_bin)
+                .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt() * 2.0 / window_sum)
+                .fold(0.0, f32::max);
+        }
+        Self { bins }
+    }
+
+    /// the magnitude in `bin`, or 0.0 for bins out of range
+    pub fn bin(&self, bin: i32) -> f32 {
+        usize::try_from(bin)
+            .ok()
+            .and_then(|bin| self.bins.get(bin))
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+// in-place iterative radix-2 FFT
+fn fft(re: &mut [f32; WINDOW], im: &mut [f32; WINDOW]) {
+    let bits = WINDOW.trailing_zeros();
+    for i in 0..WINDOW {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= WINDOW {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..WINDOW).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_peaks_in_its_bin() {
+        // a full scale sine completing 68 cycles per window, in the middle of bin 8: at 44.1kHz
+        // that's about 2.9kHz
+        let mut samples = [0.0; WINDOW];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample = (2.0 * PI * 68.0 * i as f32 / WINDOW as f32).sin();
+        }
+        let spectrum = Spectrum::analyze(&samples);
+
+        let peak = (0..BINS as i32)
+            .max_by(|&a, &b| spectrum.bin(a).total_cmp(&spectrum.bin(b)))
+            .unwrap();
+        assert_eq!(peak, 8);
+        assert!((spectrum.bin(8) - 1.0).abs() < 0.01, "{}", spectrum.bin(8));
+        assert!(spectrum.bin(20) < 0.01);
+        assert_eq!(spectrum.bin(-1), 0.0);
+        assert_eq!(spectrum.bin(BINS as i32), 0.0);
+    }
+}
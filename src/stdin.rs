@@ -0,0 +1,184 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use clap::ValueEnum;
+
+use crate::format::PixelFormat;
+use crate::Command;
+
+/// reads commands from stdin, one per line, on a thread of its own so waiting for input never
+/// holds up ticks. lines that fail to parse are reported and skipped. the returned receiver
+/// disconnects once stdin is closed.
+///
+/// commands are:
+///
+/// - `pause`, `resume` and `step`
+/// - `screenshot PATH`
+/// - `setfps N`
+/// - `format FORMAT`, taking the same formats as `--pixel-format`
+/// - `set NAME VALUE` for the module's `f32` globals
+/// - `scene N`, counting from 1 like the number keys
+/// - `debug N` to show or hide the Nth debug buffer, like ctrl and a number key
+/// - `next` and `prev` to switch modules of the playlist
+/// - `time MS` to hold the time `now_ms` reports, and `time real` to let it run again
+/// - `click X Y` to click the frame at pixel X, Y, optionally followed by the button, 0 for
+///   the left one by default
+///
+/// blank lines and lines starting with `#` are ignored.
+pub fn spawn() -> Receiver<Command> {
+    read(io::stdin())
+}
+
+// reads commands from `input` like `spawn` does from stdin
+fn read(input: impl Read + Send + 'static) -> Receiver<Command> {
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(input).lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse(line) {
+                Ok(command) => {
+                    if sender.send(command).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => eprintln!("stdin: {}", e),
+            }
+        }
+    });
+    commands
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+
+    let command = match (name, args.as_slice()) {
+        ("pause", []) => Command::Pause,
+        ("resume", []) => Command::Resume,
+        ("step", []) => Command::Step,
+        ("next", []) => Command::NextModule,
+        ("prev", []) => Command::PreviousModule,
+        ("screenshot", [path]) => Command::Screenshot(path.into()),
+        ("setfps", [fps]) => {
+            let fps: f64 = fps
+                .parse()
+                .map_err(|e| format!("invalid fps '{}': {}", fps, e))?;
+            if !(fps > 0.0 && fps.is_finite()) {
+                return Err(format!("fps must be positive, got {}", fps));
+            }
+            Command::SetFps(fps)
+        }
+        ("format", [format]) => Command::SetPixelFormat(PixelFormat::from_str(format, true)?),
+        ("set", [global, value]) => Command::SetGlobal {
+            name: global.to_string(),
+            value: value
+                .parse()
+                .map_err(|e| format!("invalid value '{}': {}", value, e))?,
+        },
+        ("time", ["real"]) => Command::ScrubTime(None),
+        ("time", [ms]) => Command::ScrubTime(Some(
+            ms.parse()
+                .map_err(|e| format!("invalid time '{}': {}", ms, e))?,
+        )),
+        ("debug", [number]) => {
+            let number: usize = number
+                .parse()
+                .map_err(|e| format!("invalid debug buffer '{}': {}", number, e))?;
+            if number == 0 {
+                return Err("debug buffers are counted from 1".to_string());
+            }
+            Command::ToggleDebugBuffer(number - 1)
+        }
+        ("click", [x, y, button @ ..]) if button.len() <= 1 => {
+            let number = |value: &str| {
+                value
+                    .parse::<i32>()
+                    .map_err(|e| format!("invalid click '{}': {}", value, e))
+            };
+            Command::Click {
+                x: number(x)?,
+                y: number(y)?,
+                button: button.first().map_or(Ok(0), |button| number(button))?,
+            }
+        }
+        ("scene", [number]) => {
+            let invalid = |e: &dyn std::fmt::Display| format!("invalid scene '{}': {}", number, e);
+            let number: usize = number.parse().map_err(|e| invalid(&e))?;
+            if number == 0 {
+                return Err("scenes are counted from 1".to_string());
+            }
+            Command::SetScene(i32::try_from(number - 1).map_err(|e| invalid(&e))?)
+        }
+        (
+            "pause" | "resume" | "step" | "next" | "prev" | "screenshot" | "setfps" | "format"
+            | "set" | "scene" | "time" | "debug" | "click",
+            _,
+        ) => return Err(format!("wrong number of arguments in '{}'", line)),
+        _ => return Err(format!("unknown command '{}'", name)),
+    };
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use wasmer::Value;
+
+    use super::*;
+    use crate::test_util::*;
+    use crate::State;
+
+    #[test]
+    fn piped_commands_reach_the_runner() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        let commands = read(reader);
+        writeln!(
+            writer,
+            "pause\n\n# a comment\nset ui_speed 2.5\nscene 0\nbogus\nsetfps 30"
+        )
+        .unwrap();
+        // closing the pipe ends the commands
+        drop(writer);
+
+        let mut runner = runner(
+            r#"(module
+                (memory (export "memory") 4)
+                (global (export "ui_speed") (mut f32) (f32.const 1))
+                (func (export "tick")))"#,
+            &[],
+        );
+        let mut received = 0;
+        for command in commands {
+            runner.handle_command(command).unwrap();
+            received += 1;
+        }
+        // the invalid scene and the unknown command were skipped
+        assert_eq!(received, 3);
+        assert!(matches!(runner.state, State::Idle));
+        let speed = runner
+            .module_instance
+            .exports
+            .get_global("ui_speed")
+            .unwrap();
+        assert_eq!(speed.get(&mut runner.wasm_store), Value::F32(2.5));
+        assert_eq!(runner.tick_interval, Duration::from_secs_f64(1.0 / 30.0));
+    }
+
+    #[test]
+    fn scenes_are_counted_from_1() {
+        assert!(matches!(parse("scene 2"), Ok(Command::SetScene(1))));
+        for invalid in ["scene 0", "scene -1", "scene 99999999999"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use clap::ValueEnum;
+
+/// how a stereo module's two eye images are presented. stereo modules lay both eyes out side by
+/// side in their framebuffer, left eye first, so the framebuffer is twice the eye width.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StereoMode {
+    /// show both eyes next to each other
+    Sbs,
+    /// combine the eyes into a single red/cyan image
+    Anaglyph,
+}
+
+impl StereoMode {
+    /// width of each eye's image when the frame view is `view_width` wide
+    pub fn eye_width(self, view_width: u32) -> u32 {
+        match self {
+            StereoMode::Sbs => view_width / 2,
+            StereoMode::Anaglyph => view_width,
+        }
+    }
+}
+
+/// combines a side by side RGBA frame, `eye_width` * 2 pixels wide, into a red/cyan anaglyph:
+/// red comes from the left eye, green and blue from the right.
+pub fn anaglyph(sbs: &[u8], eye_width: usize, dst: &mut Vec<u8>) {
+    let row_bytes = eye_width * 4;
+    dst.clear();
+    dst.reserve(sbs.len() / 2);
+
+    for row in sbs.chunks_exact(row_bytes * 2) {
+        let (left, right) = row.split_at(row_bytes);
+        for (l, r) in left.chunks_exact(4).zip(right.chunks_exact(4)) {
+            dst.extend_from_slice(&[l[0], r[1], r[2], l[3].max(r[3])]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anaglyph_takes_red_from_the_left_eye() {
+        // two rows of two pixel eyes
+        #[rustfmt::skip]
+        let sbs = [
+            10, 11, 12, 255,  20, 21, 22, 255,    30, 31, 32, 255,  40, 41, 42, 128,
+            50, 51, 52, 0,    60, 61, 62, 255,    70, 71, 72, 64,   80, 81, 82, 255,
+        ];
+        let mut dst = vec![1, 2, 3];
+        anaglyph(&sbs, 2, &mut dst);
+        #[rustfmt::skip]
+        assert_eq!(dst, [
+            10, 31, 32, 255,  20, 41, 42, 255,
+            50, 71, 72, 64,   60, 81, 82, 255,
+        ]);
+    }
+
+    #[test]
+    fn side_by_side_eyes_share_the_view() {
+        assert_eq!(StereoMode::Sbs.eye_width(640), 320);
+        assert_eq!(StereoMode::Anaglyph.eye_width(640), 640);
+    }
+}
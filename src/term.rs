@@ -0,0 +1,192 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::blend;
+use crate::config::Config;
+use crate::dither::Dither;
+use crate::metrics::DropCounter;
+use crate::ui::PublishedFrame;
+use crate::FrameSink;
+
+// terminals don't keep up with redrawing every tick, frames in between are skipped
+const REDRAW_INTERVAL: Duration = Duration::from_millis(33);
+
+/// a rough live preview drawn in the terminal with half block characters, for when there's no
+/// display. every character cell shows two pixels, the upper one as the foreground color of `▀`
+/// and the lower one as its background, using 24 bit color escapes.
+pub struct TermPreview {
+    columns: usize,
+    dither: Dither,
+    clear_color: [u8; 3],
+    drop_counter: DropCounter,
+    last_drawn: Option<Instant>,
+    rgba: Vec<u8>,
+}
+
+/// the colors of one character cell
+pub type Cell = ([u8; 3], [u8; 3]);
+
+impl TermPreview {
+    pub fn new(config: &Config, drop_counter: DropCounter) -> Self {
+        Self {
+            columns: config.term_width.max(1),
+            dither: config.dither,
+            clear_color: config.clear_color,
+            drop_counter,
+            last_drawn: None,
+            rgba: Vec::new(),
+        }
+    }
+
+    fn draw(&mut self, published: &PublishedFrame) -> io::Result<()> {
+        let (width, height) = (published.width as usize, published.height as usize);
+        published.format.to_rgba8(
+            &published.frame,
+            width,
+            self.dither,
+            &published.palette,
+            &mut self.rgba,
+        );
+        if let Some(key) = published.color_key {
+            blend::apply_color_key(&mut self.rgba, key);
+        }
+        let cells = cells(&self.rgba, width, height, self.columns, self.clear_color);
+
+        let mut out = String::new();
+        // the first frame clears the screen, later ones draw over the previous one
+        if self.last_drawn.is_none() {
+            out.push_str("\x1b[2J");
+        }
+        out.push_str("\x1b[H");
+        out.push_str(&render(&cells));
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+impl FrameSink for TermPreview {
+    fn publish(&mut self, published: PublishedFrame) -> bool {
+        if self
+            .last_drawn
+            .is_some_and(|last| last.elapsed() < REDRAW_INTERVAL)
+        {
+            return true;
+        }
+        if let Err(e) = self.draw(&published) {
+            // most likely stdout was closed, which nobody is going to see frames through
+            eprintln!("failed to draw frame: {}", e);
+            return false;
+        }
+        self.last_drawn = Some(Instant::now());
+        self.drop_counter
+            .displayed(published.index, published.published_at);
+        true
+    }
+
+    fn close(&mut self) {
+        print!("\x1b[0m");
+    }
+}
+
+/// downscales a `width` x `height` RGBA image to `columns` character cells across, keeping its
+/// aspect ratio with each cell two pixels tall. every downscaled pixel is the average of the
+/// pixels it covers, composited over `background`.
+pub fn cells(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    columns: usize,
+    background: [u8; 3],
+) -> Vec<Vec<Cell>> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let columns = columns.min(width);
+    // two pixel rows per cell, rounded up so the bottom row isn't lost
+    let pixel_rows = (height * columns).div_ceil(width).max(1);
+    let rows = pixel_rows.div_ceil(2);
+
+    let average = |x: usize, y: usize| -> [u8; 3] {
+        if y >= pixel_rows {
+            return background;
+        }
+        // every downscaled pixel covers at least one source pixel
+        let x0 = x * width / columns;
+        let x1 = ((x + 1) * width / columns).clamp(x0 + 1, width);
+        let y0 = (y * height / pixel_rows).min(height - 1);
+        let y1 = ((y + 1) * height / pixel_rows).clamp(y0 + 1, height);
+        let mut sum = [0u32; 3];
+        for sy in y0..y1 {
+            for sx in x0..x1 {
+                let pixel = &rgba[(sy * width + sx) * 4..][..4];
+                let pixel = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                let [r, g, b] = background;
+                let composited = blend::over(pixel, [r, g, b, 0xff], 1.0);
+                for c in 0..3 {
+                    sum[c] += composited[c] as u32;
+                }
+            }
+        }
+        let count = ((y1 - y0) * (x1 - x0)) as u32;
+        sum.map(|c| (c / count) as u8)
+    };
+
+    (0..rows)
+        .map(|row| {
+            (0..columns)
+                .map(|x| (average(x, row * 2), average(x, row * 2 + 1)))
+                .collect()
+        })
+        .collect()
+}
+
+/// the escape sequences drawing `cells`, one line per row
+pub fn render(cells: &[Vec<Cell>]) -> String {
+    let mut out = String::new();
+    for row in cells {
+        for ([tr, tg, tb], [br, bg, bb]) in row {
+            out.push_str(&format!(
+                "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                tr, tg, tb, br, bg, bb
+            ));
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_average_and_composite_pixels() {
+        const BACKGROUND: [u8; 3] = [10, 20, 30];
+        // 4x4 pixels in 2x2 blocks: reds averaging to 100, white, transparent and green
+        let mut rgba = Vec::new();
+        for y in 0..4 {
+            for x in 0..4 {
+                let red = [0, 100, 200, 100][y % 2 * 2 + x % 2];
+                rgba.extend(match (x / 2, y / 2) {
+                    (0, 0) => [red, 0, 0, 0xff],
+                    (1, 0) => [0xff; 4],
+                    (0, 1) => [0xff, 0xff, 0xff, 0],
+                    _ => [0, 0xff, 0, 0xff],
+                });
+            }
+        }
+
+        let cells = cells(&rgba, 4, 4, 2, BACKGROUND);
+        assert_eq!(
+            cells,
+            [vec![([100, 0, 0], BACKGROUND), ([0xff; 3], [0, 0xff, 0])]]
+        );
+        assert_eq!(
+            render(&cells),
+            "\x1b[38;2;100;0;0m\x1b[48;2;10;20;30m\u{2580}\
+             \x1b[38;2;255;255;255m\x1b[48;2;0;255;0m\u{2580}\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! helpers shared by the tests
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use clap::Parser;
+
+use crate::config::Config;
+use crate::error::RunnerError;
+use crate::ui::PublishedFrame;
+use crate::{FrameSink, WasmDemoRunner};
+
+/// a fresh, empty directory under the system's temp dir with `name` in its name
+pub fn temp_dir(name: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "wasm-renderer-test-{}-{}-{}",
+        std::process::id(),
+        name,
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// a module rendering nothing, with a memory exactly big enough for the default frame
+pub const EMPTY_MODULE: &str = r#"(module (memory (export "memory") 4) (func (export "tick")))"#;
+
+/// writes the module `wat` to a file of its own
+pub fn module_file(wat: &str) -> PathBuf {
+    let path = temp_dir("module").join("module.wat");
+    fs::write(&path, wat).unwrap();
+    path
+}
+
+/// the config for running `wat` with `args` after it on the command line
+pub fn config(wat: &str, args: &[&str]) -> Config {
+    let module = module_file(wat);
+    let module = module.to_str().unwrap();
+    Config::try_parse_from(["wasm-renderer", module].iter().chain(args)).unwrap()
+}
+
+/// a runner for `wat` with `args` after it on the command line
+pub fn runner(wat: &str, args: &[&str]) -> WasmDemoRunner {
+    WasmDemoRunner::new(&config(wat, args)).unwrap()
+}
+
+/// runs `runner` until it stops by itself, e.g. because of `--max-frames`, as if its window
+/// stayed open the whole time
+pub fn run_until_stopped(
+    runner: &mut WasmDemoRunner,
+    sink: &mut Collect,
+) -> Result<(), RunnerError> {
+    let (_commands_sender, commands) = mpsc::channel();
+    runner.run(sink, commands, None)
+}
+
+/// a sink keeping everything it's handed. since it holds on to every frame, runs publishing
+/// more frames than the pool starts out with need `--pool-policy grow`.
+#[derive(Default)]
+pub struct Collect {
+    pub frames: Vec<PublishedFrame>,
+    pub closed: bool,
+    pub errors: Vec<String>,
+}
+
+impl FrameSink for Collect {
+    fn publish(&mut self, published: PublishedFrame) -> bool {
+        self.frames.push(published);
+        true
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+    }
+
+    fn report_error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+}
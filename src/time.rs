@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+/// where the time a module sees through `now_ms` comes from, picked with `--time-source`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeSource {
+    /// the wall clock, counting from when the module was loaded
+    RealTime,
+    /// advances by this many milliseconds every tick however long ticks take, so runs are
+    /// reproducible
+    FixedStep(f64),
+    /// held at this many milliseconds, e.g. while the time scrub slider is dragged
+    Manual(f64),
+    /// the `--audio` playback position, or the wall clock without one
+    Audio,
+}
+
+/// parses `real`, `audio`, `fixed:MS` or `manual:MS`
+pub fn parse_time_source(s: &str) -> Result<TimeSource, String> {
+    let ms = |value: &str| {
+        value
+            .parse::<f64>()
+            .ok()
+            .filter(|ms| ms.is_finite() && *ms >= 0.0)
+            .ok_or_else(|| format!("invalid time '{}' in '{}'", value, s))
+    };
+    match s.split_once(':') {
+        None if s == "real" => Ok(TimeSource::RealTime),
+        None if s == "audio" => Ok(TimeSource::Audio),
+        Some(("fixed", step)) => {
+            let step = ms(step)?;
+            if step > 0.0 {
+                Ok(TimeSource::FixedStep(step))
+            } else {
+                Err(format!("a fixed step has to be longer than 0, got '{}'", s))
+            }
+        }
+        Some(("manual", at)) => Ok(TimeSource::Manual(ms(at)?)),
+        _ => Err(format!(
+            "expected real, audio, fixed:MS or manual:MS, got '{}'",
+            s
+        )),
+    }
+}
+
+/// the time a module sees: its source along with what's needed to read it
+#[derive(Debug)]
+pub struct ModuleTime {
+    source: TimeSource,
+    // `RealTime` counts from here
+    start: Instant,
+    // how far `FixedStep` has advanced
+    stepped_ms: f64,
+    // what to go back to once a scrub ends
+    scrubbed_from: Option<TimeSource>,
+    /// added to whatever the source reports, so each of a module's sub-frames sees a different
+    /// moment
+    pub offset_ms: f64,
+}
+
+impl ModuleTime {
+    pub fn new(source: TimeSource) -> Self {
+        Self {
+            source,
+            start: Instant::now(),
+            stepped_ms: 0.0,
+            scrubbed_from: None,
+            offset_ms: 0.0,
+        }
+    }
+
+    /// the current time in milliseconds. `audio_ms` is the playback position, if anything is
+    /// playing.
+    pub fn now_ms(&self, audio_ms: Option<f64>) -> f64 {
+        let real_ms = || self.start.elapsed().as_secs_f64() * 1000.0;
+        let ms = match self.source {
+            TimeSource::RealTime => real_ms(),
+            TimeSource::FixedStep(_) => self.stepped_ms,
+            TimeSource::Manual(ms) => ms,
+            TimeSource::Audio => audio_ms.unwrap_or_else(real_ms),
+        };
+        (ms + self.offset_ms).max(0.0)
+    }
+
+    /// called once per tick, after the module has rendered
+    pub fn advance_tick(&mut self) {
+        if let TimeSource::FixedStep(step) = self.source {
+            self.stepped_ms += step;
+        }
+    }
+
+    /// holds the time at `ms` until `resume` is called
+    pub fn scrub_to(&mut self, ms: f64) {
+        if self.scrubbed_from.is_none() {
+            self.scrubbed_from = Some(self.source);
+        }
+        self.source = TimeSource::Manual(ms.max(0.0));
+    }
+
+    /// goes back to the source from before the scrub, continuing from wherever it was scrubbed
+    /// to. the audio can't seek, so it picks up where playback is.
+    pub fn resume(&mut self) {
+        let (Some(previous), TimeSource::Manual(ms)) = (self.scrubbed_from.take(), self.source)
+        else {
+            return;
+        };
+        self.source = match previous {
+            TimeSource::RealTime => {
+                let elapsed = Duration::from_secs_f64(ms / 1000.0);
+                self.start = Instant::now().checked_sub(elapsed).unwrap_or(self.start);
+                previous
+            }
+            TimeSource::FixedStep(_) => {
+                self.stepped_ms = ms;
+                previous
+            }
+            TimeSource::Manual(_) => TimeSource::Manual(ms),
+            TimeSource::Audio => previous,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_source_reports_its_own_time() {
+        let real = ModuleTime::new(TimeSource::RealTime);
+        let before = real.now_ms(None);
+        std::thread::sleep(Duration::from_millis(5));
+        let after = real.now_ms(Some(1e9));
+        // the wall clock, ignoring the audio
+        assert!(
+            after - before >= 5.0 && after < 1e9,
+            "{} then {}",
+            before,
+            after
+        );
+
+        let mut fixed = ModuleTime::new(TimeSource::FixedStep(16.5));
+        let mut times = Vec::new();
+        for _ in 0..3 {
+            times.push(fixed.now_ms(None));
+            fixed.advance_tick();
+        }
+        assert_eq!(times, [0.0, 16.5, 33.0]);
+
+        let mut manual = ModuleTime::new(TimeSource::Manual(500.0));
+        manual.advance_tick();
+        assert_eq!(manual.now_ms(Some(20.0)), 500.0);
+
+        let audio = ModuleTime::new(TimeSource::Audio);
+        assert_eq!(audio.now_ms(Some(1234.0)), 1234.0);
+        // nothing playing falls back to the wall clock
+        assert!(audio.now_ms(None) < 1000.0);
+    }
+
+    #[test]
+    fn scrubbing_holds_the_time_and_resumes_from_it() {
+        let mut time = ModuleTime::new(TimeSource::FixedStep(10.0));
+        time.advance_tick();
+        time.scrub_to(250.0);
+        time.advance_tick();
+        assert_eq!(time.now_ms(None), 250.0);
+        time.resume();
+        assert_eq!(time.now_ms(None), 250.0);
+        time.advance_tick();
+        assert_eq!(time.now_ms(None), 260.0);
+
+        // sub-frames see the time shifted, but never before 0
+        time.offset_ms = -300.0;
+        assert_eq!(time.now_ms(None), 0.0);
+    }
+}
@@ -0,0 +1,673 @@
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Instant;
+
+use druid::lens::Unit;
+use druid::piet::{ImageFormat, InterpolationMode, Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::widget::{Button, Controller, Either, Flex, Label, SizedBox, Slider};
+use druid::{Affine, Color, Data, Lens, MouseButton, Point, Rect, Selector, WidgetExt};
+
+use crate::blend;
+use crate::config::Config;
+use crate::debug_buffers::DebugBuffer;
+use crate::debug_panel::{self, DebugPanelToggle, TOGGLE_DEBUG_PANEL};
+use crate::dither::Dither;
+use crate::format::PixelFormat;
+use crate::metrics::DropCounter;
+use crate::rotate::Rotation;
+use crate::scenes::Scene;
+use crate::sliders::GlobalSlider;
+use crate::stereo::{self, StereoMode};
+use crate::{Command, Frame};
+
+/// submitted by the wasm runner thread every time a new frame is ready to be displayed
+pub const FRAME_PUBLISHED: Selector<PublishedFrame> =
+    Selector::new("wasm-renderer.frame-published");
+
+/// submitted by the wasm runner thread when it stops because of an error, with the error's text
+pub const RUNNER_FAILED: Selector<String> = Selector::new("wasm-renderer.runner-failed");
+
+// height of the banner showing why the runner stopped
+const ERROR_BANNER_HEIGHT: f64 = 32.0;
+// debug buffers are drawn at this fraction of the view's width, this far apart
+const DEBUG_BUFFER_SCALE: f64 = 0.2;
+const DEBUG_BUFFER_MARGIN: f64 = 8.0;
+
+#[derive(Clone, Data, Lens)]
+pub struct AppState {
+    /// one per `ui_` global the module exports
+    pub sliders: Arc<Vec<GlobalSlider>>,
+    /// the module's scenes, if it has more than one part
+    pub scenes: Arc<Vec<Scene>>,
+    /// the module's `describe` summary, shown in the debug panel
+    pub description: Option<String>,
+    /// where the time scrub slider is, from 0 to 1
+    pub scrub: f64,
+    /// whether the debug panel with the module's controls is shown, toggled with `d`
+    pub debug_panel: bool,
+}
+
+/// a frame along with what's needed to interpret its bytes
+#[derive(Clone, Debug)]
+pub struct PublishedFrame {
+    /// counts up from 0 with every frame the runner publishes
+    pub index: u64,
+    pub frame: Frame,
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    /// the module's palette as of this frame, empty unless `format` is `Indexed8`
+    pub palette: Arc<[[u8; 4]]>,
+    /// pixels of this color are displayed as transparent, see `--color-key`
+    pub color_key: Option<[u8; 3]>,
+    /// whether the frame's colors are premultiplied by their alpha, see `--premultiplied`
+    pub premultiplied: bool,
+    /// what the module's `tick` returned for this frame, if it returns an i32. negative values
+    /// are errors and stop the runner, so this is never negative.
+    pub status: Option<i32>,
+    /// size in bytes of the module's memory when the frame was published
+    pub memory_size: u64,
+    /// the module's debug buffers shown with ctrl and a number key, as of this frame
+    pub debug_buffers: Arc<[DebugBuffer]>,
+    /// when the runner published the frame, for measuring how long it takes to be displayed
+    pub published_at: Instant,
+}
+
+/// displays the most recently published frame, converting it to 8 bit RGBA as needed. frames that
+/// don't match the view's size are scaled to fit and letterboxed.
+struct FrameView {
+    dither: Dither,
+    clear_color: Color,
+    clear_rgb: [u8; 3],
+    stereo: Option<StereoMode>,
+    flip_h: bool,
+    flip_v: bool,
+    rotate: Option<Rotation>,
+    vsync: bool,
+    commands: Sender<Command>,
+    drop_counter: DropCounter,
+    current: Option<PublishedFrame>,
+    // where the last frame was drawn, before orientation, and its size in pixels, for mapping
+    // clicks back onto it
+    drawn: Option<(Rect, Size)>,
+    // why the runner stopped, shown over the last frame
+    error: Option<String>,
+    // reused across paints so converting a frame for display doesn't allocate every time
+    rgba: Vec<u8>,
+    debug_rgba: Vec<u8>,
+    anaglyph: Vec<u8>,
+    flattened: Option<Flattened>,
+    #[cfg(feature = "clipboard")]
+    clipboard: Option<arboard::Clipboard>,
+}
+
+// straight alpha frames are composited over the clear color before they're handed to piet, in
+// frames reused while the displayed size stays the same: the converted frame, an opaque frame of
+// the clear color, and the result
+struct Flattened {
+    top: Frame,
+    clear: Frame,
+    out: Frame,
+}
+
+impl Flattened {
+    // composites `pixels`, 8 bit RGBA, over `clear` into an opaque image
+    fn flatten<'a>(
+        scratch: &'a mut Option<Flattened>,
+        clear: [u8; 3],
+        pixels: &[u8],
+    ) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        if scratch.as_ref().map(|flattened| flattened.out.len()) != Some(pixels.len()) {
+            let [r, g, b] = clear;
+            *scratch = Some(Flattened {
+                top: Frame::new(pixels.len()),
+                clear: Frame::from_vec([r, g, b, 0xff].repeat(pixels.len() / 4)),
+                out: Frame::new(pixels.len()),
+            });
+        }
+        let flattened = scratch.as_mut().unwrap();
+        flattened.top.modify(|buf| buf.copy_from_slice(pixels))?;
+        flattened
+            .top
+            .blend_over(&flattened.clear, 1.0, &mut flattened.out)?;
+        Ok(&flattened.out)
+    }
+}
+
+impl FrameView {
+    fn new(
+        config: &Config,
+        clear_color: Color,
+        commands: Sender<Command>,
+        drop_counter: DropCounter,
+    ) -> Self {
+        Self {
+            dither: config.dither,
+            clear_color,
+            clear_rgb: config.clear_color,
+            stereo: config.stereo,
+            flip_h: config.flip_h,
+            flip_v: config.flip_v,
+            rotate: config.rotate,
+            vsync: config.vsync,
+            commands,
+            drop_counter,
+            current: None,
+            drawn: None,
+            error: None,
+            rgba: Vec::new(),
+            debug_rgba: Vec::new(),
+            anaglyph: Vec::new(),
+            flattened: None,
+            #[cfg(feature = "clipboard")]
+            clipboard: None,
+        }
+    }
+
+    // converts the current frame into the 8 bit RGBA image it's displayed as, returning the image
+    // along with its width and height
+    fn convert(&mut self) -> Option<(&[u8], usize, usize)> {
+        let published = self.current.as_ref()?;
+        let (width, height) = (published.width as usize, published.height as usize);
+        // the window reads frames on a thread of its own
+        published.frame.read(|frame| {
+            published.format.to_rgba8(
+                frame,
+                width,
+                self.dither,
+                &published.palette,
+                &mut self.rgba,
+            )
+        });
+        if let Some(key) = published.color_key {
+            blend::apply_color_key(&mut self.rgba, key);
+        }
+
+        match self.stereo {
+            Some(StereoMode::Anaglyph) => {
+                let eye_width = width / 2;
+                stereo::anaglyph(&self.rgba, eye_width, &mut self.anaglyph);
+                Some((&self.anaglyph, eye_width, height))
+            }
+            _ => Some((&self.rgba, width, height)),
+        }
+    }
+
+    // copies the frame, as displayed but before any flip or rotation, to the system clipboard
+    #[cfg(feature = "clipboard")]
+    fn copy_to_clipboard(&mut self) {
+        let mut clipboard = self.clipboard.take();
+        if let Some((pixels, width, height)) = self.convert() {
+            let image = crate::clipboard::image(pixels, width, height);
+            if let Err(e) = crate::clipboard::copy(&mut clipboard, image) {
+                tracing::warn!("failed to copy frame to the clipboard: {}", e);
+            }
+        }
+        self.clipboard = clipboard;
+    }
+
+    // flips and then rotates whatever is drawn around `center`, as configured
+    fn orientation(&self, center: Point) -> Affine {
+        let scale_x = if self.flip_h { -1.0 } else { 1.0 };
+        let scale_y = if self.flip_v { -1.0 } else { 1.0 };
+        let angle = self.rotate.map_or(0.0, Rotation::radians);
+        let center = center.to_vec2();
+        Affine::translate(center)
+            * Affine::rotate(angle)
+            * Affine::scale_non_uniform(scale_x, scale_y)
+            * Affine::translate(-center)
+    }
+
+    // the pixel of the last drawn frame under `pos` in the view, undoing flips and rotation
+    fn frame_pixel(&self, pos: Point, center: Point) -> Option<(i32, i32)> {
+        let (rect, frame_size) = self.drawn?;
+        let pos = self.orientation(center).inverse() * pos;
+        if !rect.contains(pos) {
+            return None;
+        }
+        let x = (pos.x - rect.x0) / rect.width() * frame_size.width;
+        let y = (pos.y - rect.y0) / rect.height() * frame_size.height;
+        Some((x as i32, y as i32))
+    }
+
+    // the size a frame takes up on screen once rotated
+    fn oriented(&self, size: Size) -> Size {
+        match self.rotate {
+            Some(rotation) if rotation.swaps_axes() => Size::new(size.height, size.width),
+            _ => size,
+        }
+    }
+}
+
+impl Widget<()> for FrameView {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut (), _env: &Env) {
+        match event {
+            // keyboard shortcuts only reach the focused widget
+            Event::WindowConnected => {
+                ctx.request_focus();
+                if self.vsync {
+                    ctx.request_anim_frame();
+                }
+            }
+            // animation frames arrive once per display refresh for as long as they're requested
+            Event::AnimFrame(_) if self.vsync => {
+                let _ = self.commands.send(Command::Vblank);
+                ctx.request_anim_frame();
+            }
+            Event::Command(cmd) => {
+                if let Some(published) = cmd.get(FRAME_PUBLISHED) {
+                    self.current = Some(published.clone());
+                    ctx.request_paint();
+                }
+                if let Some(error) = cmd.get(RUNNER_FAILED) {
+                    self.error = Some(error.clone());
+                    ctx.request_paint();
+                }
+            }
+            Event::MouseDown(mouse) => {
+                let button = match mouse.button {
+                    MouseButton::Left => 0,
+                    MouseButton::Middle => 1,
+                    MouseButton::Right => 2,
+                    _ => return,
+                };
+                let center = ctx.size().to_rect().center();
+                if let Some((x, y)) = self.frame_pixel(mouse.pos, center) {
+                    let _ = self.commands.send(Command::Click { x, y, button });
+                }
+            }
+            #[cfg(feature = "clipboard")]
+            Event::KeyDown(key) if key.key == druid::KbKey::Character("c".to_string()) => {
+                self.copy_to_clipboard();
+            }
+            // number keys switch scenes, 1 being the first, or with ctrl held show and hide debug
+            // buffers
+            Event::KeyDown(key) => {
+                if let druid::KbKey::Character(c) = &key.key {
+                    if let Ok(number @ 1..=9) = c.parse::<i32>() {
+                        let command = if key.mods.ctrl() {
+                            Command::ToggleDebugBuffer(number as usize - 1)
+                        } else {
+                            Command::SetScene(number - 1)
+                        };
+                        let _ = self.commands.send(command);
+                    }
+                    match c.as_str() {
+                        "d" => ctx.submit_command(TOGGLE_DEBUG_PANEL),
+                        "n" => {
+                            let _ = self.commands.send(Command::NextModule);
+                        }
+                        "p" => {
+                            let _ = self.commands.send(Command::PreviousModule);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &(), _env: &Env) {
+        if let LifeCycle::Size(size) = event {
+            // rotating by 90 or 270 degrees turns the view's width into the frame's height
+            let size = self.oriented(*size);
+            // the runner only goes away when the app is shutting down
+            let _ = self.commands.send(Command::Resize {
+                width: size.width as u32,
+                height: size.height as u32,
+            });
+        }
+    }
+
+    fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &(), _data: &(), _env: &Env) {}
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &(),
+        _env: &Env,
+    ) -> Size {
+        if bc.is_width_bounded() && bc.is_height_bounded() {
+            return bc.max();
+        }
+        bc.constrain(Size::new(256.0, 256.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &(), _env: &Env) {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &self.clear_color);
+        self.paint_frame(ctx, bounds);
+        self.paint_debug_buffers(ctx, bounds);
+
+        // the module's own status, in the bottom left corner
+        if let Some(status) = self.current.as_ref().and_then(|published| published.status) {
+            let layout = ctx
+                .text()
+                .new_text_layout(format!("status {}", status))
+                .text_color(Color::WHITE)
+                .build();
+            match layout {
+                Ok(layout) => {
+                    let size = layout.size();
+                    let origin = Point::new(4.0, bounds.height() - size.height - 4.0);
+                    let background = Rect::from_origin_size(origin, size).inflate(2.0, 2.0);
+                    ctx.fill(background, &Color::rgba8(0, 0, 0, 0xa0));
+                    ctx.draw_text(&layout, origin);
+                }
+                Err(e) => eprintln!("failed to lay out module status: {}", e),
+            }
+        }
+
+        if let Some(error) = &self.error {
+            let banner = Rect::new(0.0, 0.0, bounds.width(), ERROR_BANNER_HEIGHT);
+            ctx.fill(banner, &Color::rgba8(0x80, 0, 0, 0xd0));
+            let layout = ctx
+                .text()
+                .new_text_layout(format!("runner stopped: {}", error))
+                .text_color(Color::WHITE)
+                .max_width(bounds.width() - 16.0)
+                .build();
+            match layout {
+                Ok(layout) => {
+                    // centered vertically in the banner, overflowing it if the text wraps
+                    let y = ((ERROR_BANNER_HEIGHT - layout.size().height) / 2.0).max(0.0);
+                    ctx.draw_text(&layout, Point::new(8.0, y));
+                }
+                Err(e) => eprintln!("failed to lay out error banner: {}", e),
+            }
+        }
+    }
+}
+
+impl FrameView {
+    fn paint_frame(&mut self, ctx: &mut PaintCtx, bounds: Rect) {
+        let Some(published) = &self.current else {
+            return;
+        };
+        // frames replaced before a paint got to them count as dropped
+        self.drop_counter
+            .displayed(published.index, published.published_at);
+
+        let premultiplied = published.premultiplied;
+
+        let (clear, mut flattened) = (self.clear_rgb, self.flattened.take());
+        let Some((pixels, width, height)) = self.convert() else {
+            self.flattened = flattened;
+            return;
+        };
+        // premultiplied frames are left to piet to composite, since `blend_over` takes straight
+        // alpha
+        let (pixels, image_format) = if premultiplied {
+            (pixels, ImageFormat::RgbaPremul)
+        } else {
+            match Flattened::flatten(&mut flattened, clear, pixels) {
+                Ok(flat) => (flat, ImageFormat::RgbaSeparate),
+                Err(e) => {
+                    eprintln!("failed to composite frame over the clear color: {}", e);
+                    (pixels, ImageFormat::RgbaSeparate)
+                }
+            }
+        };
+        let image = ctx.make_image(width, height, pixels, image_format);
+        self.flattened = flattened;
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("failed to create frame image: {}", e);
+                return;
+            }
+        };
+        let size = ctx.size();
+        let frame_size = Size::new(width as f64, height as f64);
+        let displayed = self.oriented(frame_size);
+        let scale = (size.width / displayed.width).min(size.height / displayed.height);
+        // the image is drawn unrotated around the view's center and the transform turns it into
+        // place, which saves copying the frame into a flipped or rotated buffer
+        let center = bounds.center();
+        let rect = Rect::from_center_size(center, frame_size * scale);
+        self.drawn = Some((rect, frame_size));
+        let transform = self.orientation(center);
+        ctx.with_save(|ctx| {
+            ctx.transform(transform);
+            ctx.draw_image(&image, rect, InterpolationMode::NearestNeighbor);
+        });
+    }
+}
+
+impl FrameView {
+    // draws the shown debug buffers as small pictures along the bottom right corner, right to
+    // left in the order they're exported, each labeled with its name. they're drawn upright,
+    // ignoring `--flip` and `--rotate`.
+    fn paint_debug_buffers(&mut self, ctx: &mut PaintCtx, bounds: Rect) {
+        let Some(published) = &self.current else {
+            return;
+        };
+        let (width, height) = (published.width as f64, published.height as f64);
+        if width == 0.0 || height == 0.0 {
+            return;
+        }
+        let scale = bounds.width() * DEBUG_BUFFER_SCALE / width;
+        let size = Size::new(width * scale, height * scale);
+
+        let mut right = bounds.width() - DEBUG_BUFFER_MARGIN;
+        for buffer in published.debug_buffers.iter() {
+            buffer.format.to_rgba8(
+                &buffer.bytes,
+                published.width as usize,
+                self.dither,
+                &published.palette,
+                &mut self.debug_rgba,
+            );
+            let image = match ctx.make_image(
+                published.width as usize,
+                published.height as usize,
+                &self.debug_rgba,
+                ImageFormat::RgbaSeparate,
+            ) {
+                Ok(image) => image,
+                Err(e) => {
+                    eprintln!("failed to create debug buffer image: {}", e);
+                    return;
+                }
+            };
+            let origin = Point::new(
+                right - size.width,
+                bounds.height() - size.height - DEBUG_BUFFER_MARGIN,
+            );
+            let rect = Rect::from_origin_size(origin, size);
+            ctx.fill(rect.inflate(1.0, 1.0), &Color::WHITE);
+            ctx.draw_image(&image, rect, InterpolationMode::NearestNeighbor);
+
+            let layout = ctx
+                .text()
+                .new_text_layout(buffer.name.clone())
+                .text_color(Color::WHITE)
+                .build();
+            match layout {
+                Ok(layout) => {
+                    let origin = Point::new(origin.x, origin.y - layout.size().height - 2.0);
+                    ctx.draw_text(&layout, origin);
+                }
+                Err(e) => eprintln!("failed to lay out debug buffer name: {}", e),
+            }
+            right -= size.width + DEBUG_BUFFER_MARGIN;
+        }
+    }
+}
+
+// forwards slider movements to the runner, which writes them into the module's globals before the
+// next tick
+pub struct SliderController {
+    pub commands: Sender<Command>,
+}
+
+impl<W: Widget<Arc<Vec<GlobalSlider>>>> Controller<Arc<Vec<GlobalSlider>>, W> for SliderController {
+    fn update(
+        &mut self,
+        child: &mut W,
+        ctx: &mut UpdateCtx,
+        old_data: &Arc<Vec<GlobalSlider>>,
+        data: &Arc<Vec<GlobalSlider>>,
+        env: &Env,
+    ) {
+        for (old, new) in old_data.iter().zip(data.iter()) {
+            if !old.same(new) {
+                let _ = self.commands.send(Command::SetGlobal {
+                    name: new.name.clone(),
+                    value: new.value() as f32,
+                });
+            }
+        }
+        child.update(ctx, old_data, data, env)
+    }
+}
+
+pub fn make_slider() -> impl Widget<GlobalSlider> {
+    Flex::row()
+        .with_child(
+            Label::dynamic(|slider: &GlobalSlider, _| {
+                format!("{}: {:.3}", slider.name, slider.value())
+            })
+            .fix_width(160.0),
+        )
+        .with_flex_child(
+            Slider::new().lens(GlobalSlider::position).expand_width(),
+            1.0,
+        )
+}
+
+pub fn make_scene_button(commands: Sender<Command>) -> impl Widget<Scene> {
+    Button::dynamic(|scene: &Scene, _| format!("{}: {}", scene.index + 1, scene.name)).on_click(
+        move |_ctx, scene: &mut Scene, _env| {
+            let _ = commands.send(Command::SetScene(scene.index));
+        },
+    )
+}
+
+pub fn make_ui(
+    config: &Config,
+    commands: Sender<Command>,
+    drop_counter: DropCounter,
+) -> impl Widget<AppState> {
+    let [r, g, b] = config.clear_color;
+    let frame_view = FrameView::new(config, Color::rgb8(r, g, b), commands.clone(), drop_counter);
+
+    let max_memory = config
+        .max_memory_pages
+        .map(|pages| pages as u64 * wasmer::WASM_PAGE_SIZE as u64);
+    let debug_panel = Either::new(
+        |data: &AppState, _| data.debug_panel,
+        debug_panel::make_debug_panel(commands, max_memory),
+        SizedBox::empty(),
+    );
+
+    Flex::column()
+        .with_flex_child(frame_view.padding(10.0).lens(Unit), 1.0)
+        .with_child(debug_panel)
+        .controller(DebugPanelToggle)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::test_util::*;
+
+    // a view configured with `args` that has last drawn a `width` x `height` frame into the same
+    // sized rect at the origin
+    fn view(args: &[&str], width: f64, height: f64) -> FrameView {
+        let config =
+            Config::try_parse_from(["wasm-renderer", "unused.wasm"].iter().chain(args)).unwrap();
+        let (commands, _) = mpsc::channel();
+        let drop_counter = Metrics::new(Duration::ZERO).drop_counter();
+        let mut view = FrameView::new(&config, Color::BLACK, commands, drop_counter);
+        let size = Size::new(width, height);
+        view.drawn = Some((size.to_rect(), size));
+        view
+    }
+
+    #[test]
+    fn frames_are_flattened_over_the_clear_color() {
+        let mut scratch = None;
+        // opaque green, then blue at a quarter of its alpha
+        let pixels = [0, 0xff, 0, 0xff, 0, 0, 0xff, 0x40];
+        let flat = Flattened::flatten(&mut scratch, [0xff, 0, 0], &pixels).unwrap();
+        assert_eq!(flat, [0, 0xff, 0, 0xff, 0xbf, 0, 0x40, 0xff]);
+
+        // later frames of the same size reuse the scratch frames
+        let out = scratch.as_ref().unwrap().out.as_ptr();
+        Flattened::flatten(&mut scratch, [0xff, 0, 0], &[0; 8]).unwrap();
+        assert_eq!(scratch.as_ref().unwrap().out.as_ptr(), out);
+        assert_eq!(
+            &scratch.as_ref().unwrap().out[..],
+            [0xff, 0, 0, 0xff].repeat(2)
+        );
+    }
+
+    #[test]
+    fn flip_v_reverses_rows() {
+        let view = view(&["--flip-v"], 4.0, 3.0);
+        let center = Point::new(2.0, 1.5);
+        for y in 0..3 {
+            let on_screen = Point::new(1.5, y as f64 + 0.5);
+            assert_eq!(view.frame_pixel(on_screen, center), Some((1, 2 - y)));
+        }
+        // the frame's top left corner is drawn at the bottom left
+        let corner = view.orientation(center) * Point::new(0.5, 0.5);
+        assert_eq!(corner, Point::new(0.5, 2.5));
+    }
+
+    #[test]
+    fn rotating_90_degrees_swaps_axes() {
+        let view = view(&["--rotate", "90"], 4.0, 2.0);
+        assert_eq!(view.oriented(Size::new(4.0, 2.0)), Size::new(2.0, 4.0));
+
+        // the frame's top left pixel ends up in the top right corner of the rotated image,
+        // which spans x 1 to 3 and y -1 to 3 around the center
+        let center = Point::new(2.0, 1.0);
+        let corner = view.orientation(center) * Point::new(0.5, 0.5);
+        assert!(
+            (corner - Point::new(2.5, -0.5)).hypot() < 1e-9,
+            "{:?}",
+            corner
+        );
+        assert_eq!(
+            view.frame_pixel(Point::new(2.5, -0.5), center),
+            Some((0, 0))
+        );
+        assert_eq!(view.frame_pixel(Point::new(1.5, 2.5), center), Some((3, 1)));
+    }
+
+    #[test]
+    fn color_keyed_pixels_are_transparent() {
+        // magenta, then a pixel that's only nearly magenta
+        let sprite = r#"(module
+            (memory (export "memory") 4)
+            (data (i32.const 0) "\ff\00\ff\ff\fe\00\ff\ff")
+            (global (export "color_key") i32 (i32.const 0xffff00ff))
+            (func (export "tick")))"#;
+        let displayed = |args: &[&str]| {
+            let mut sink = Collect::default();
+            let args = [&["--max-frames", "1", "--quiet"], args].concat();
+            run_until_stopped(&mut runner(sprite, &args), &mut sink).unwrap();
+            let mut view = view(&[], 256.0, 256.0);
+            view.current = sink.frames.pop();
+            view.convert().unwrap().0[..8].to_vec()
+        };
+
+        assert_eq!(displayed(&[]), [0xff, 0, 0xff, 0, 0xfe, 0, 0xff, 0xff]);
+        // the override replaces the module's key
+        assert_eq!(
+            displayed(&["--color-key", "fe00ff"]),
+            [0xff, 0, 0xff, 0xff, 0xfe, 0, 0xff, 0]
+        );
+    }
+}
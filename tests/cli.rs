@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn wasm_renderer(module: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wasm-renderer"))
+        .arg(module)
+        .output()
+        .unwrap()
+}
+
+// writes `wat` to a module file named after `name`
+fn module_file(name: &str, wat: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "wasm-renderer-cli-{}-{}.wat",
+        std::process::id(),
+        name
+    ));
+    fs::write(&path, wat).unwrap();
+    path
+}
+
+#[test]
+fn missing_module_exits_with_the_io_code() {
+    let output = wasm_renderer("does/not/exist.wasm");
+    assert_eq!(output.status.code(), Some(13));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error: "), "{}", stderr);
+}
+
+#[test]
+fn invalid_module_exits_with_the_compile_code() {
+    let path = module_file("invalid", "(module (func (export \"tick\") (i32.const 1)))");
+    let output = wasm_renderer(path.to_str().unwrap());
+    let _ = fs::remove_file(&path);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn checksums_are_printed_every_interval() {
+    let path = module_file(
+        "checksums",
+        r#"(module (memory (export "memory") 4) (func (export "tick")))"#,
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_wasm-renderer"))
+        .arg(&path)
+        .args([
+            "--term",
+            "--quiet",
+            "--max-frames",
+            "8",
+            "--checksum-interval",
+            "3",
+        ])
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&path);
+    assert!(output.status.success(), "{:?}", output);
+
+    // the terminal preview is printed to stdout as well
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let frames: Vec<_> = stdout
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .filter(|(_, checksum)| checksum.len() == 16)
+        .map(|(frame, _)| frame)
+        .collect();
+    assert_eq!(frames, ["0", "3", "6"]);
+}
+
+#[test]
+fn quiet_runs_only_print_the_preview() {
+    let path = module_file(
+        "quiet",
+        r#"(module (memory (export "memory") 4) (func (export "tick")))"#,
+    );
+    let dump = path.with_extension("dump");
+    let run = |quiet: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_wasm-renderer"))
+            .arg(&path)
+            .args(["--term", "--term-width", "4", "--max-frames", "2"])
+            .arg("--dump-memory")
+            .arg(&dump)
+            .args(quiet)
+            .output()
+            .unwrap()
+    };
+    let (loud, quiet) = (run(&[]), run(&["--quiet"]));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&dump);
+    assert!(loud.status.success() && quiet.status.success());
+
+    assert!(String::from_utf8_lossy(&loud.stdout).contains("memory dump: "));
+    // every line of the preview starts with an escape sequence
+    let stdout = String::from_utf8_lossy(&quiet.stdout);
+    assert!(stdout.lines().all(|line| line.starts_with('\x1b')), "{}", stdout);
+    assert!(quiet.stderr.is_empty(), "{:?}", quiet);
+}
+
+#[test]
+fn deep_recursion_traps_instead_of_crashing() {
+    let path = module_file(
+        "recursion",
+        r#"(module
+            (memory (export "memory") 4)
+            (func $recurse (param i32) (result i32)
+                (i32.add (call $recurse (i32.add (local.get 0) (i32.const 1))) (i32.const 1)))
+            (func (export "tick") (drop (call $recurse (i32.const 0)))))"#,
+    );
+    let run = |kib: &str| {
+        Command::new(env!("CARGO_BIN_EXE_wasm-renderer"))
+            .arg(&path)
+            .args(["--term", "--quiet", "--max-stack", kib])
+            .output()
+            .unwrap()
+    };
+    let (trapped, too_small) = (run("64"), run("4"));
+    let _ = fs::remove_file(&path);
+
+    assert_eq!(trapped.status.code(), Some(7), "{:?}", trapped);
+    let stderr = String::from_utf8_lossy(&trapped.stderr);
+    assert!(stderr.contains("call stack exhausted"), "{}", stderr);
+    // clap's usage errors exit with 2
+    assert_eq!(too_small.status.code(), Some(2), "{:?}", too_small);
+}
+
+#[test]
+fn log_files_record_the_load_and_every_tick() {
+    let path = module_file(
+        "log-file",
+        r#"(module (memory (export "memory") 4) (func (export "tick")))"#,
+    );
+    let log = path.with_extension("log");
+    fs::write(&log, "left over from an earlier run\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_wasm-renderer"))
+        .arg(&path)
+        .args(["--term", "--quiet", "--max-frames", "3", "--log-file"])
+        .arg(&log)
+        .output()
+        .unwrap();
+    let contents = fs::read_to_string(&log).unwrap_or_default();
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&log);
+    assert!(output.status.success(), "{:?}", output);
+
+    // truncated rather than appended to
+    assert!(!contents.contains("left over"), "{}", contents);
+    assert!(contents.contains("compiled module"), "{}", contents);
+    assert!(contents.contains("exports: memory (memory"), "{}", contents);
+    let ticks: Vec<_> = contents
+        .lines()
+        .filter_map(|line| line.split_once(" tick tick=")?.1.split_once(' '))
+        .map(|(tick, _)| tick)
+        .collect();
+    assert_eq!(ticks, ["0", "1", "2"], "{}", contents);
+}
+
+#[test]
+fn the_module_comes_from_the_argument_then_the_environment() {
+    let invalid = module_file("env-invalid", "(module (func (export \"tick\") (i32.const 1)))");
+    let missing = PathBuf::from("does/not/exist.wasm");
+    // run somewhere without a `demo.wast`, so falling back to the default fails to find it
+    let dir = std::env::temp_dir().join(format!("wasm-renderer-cli-{}-env", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // every module here fails to load, so the window is never opened
+    let run = |env: Option<&PathBuf>, arg: Option<&PathBuf>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_wasm-renderer"));
+        command.current_dir(&dir).args(arg);
+        match env {
+            Some(module) => command.env("WASM_RENDERER_MODULE", module),
+            None => command.env_remove("WASM_RENDERER_MODULE"),
+        };
+        command.output().unwrap().status.code()
+    };
+
+    assert_eq!(run(None, None), Some(13));
+    assert_eq!(run(Some(&invalid), None), Some(3));
+    assert_eq!(run(Some(&invalid), Some(&missing)), Some(13));
+    let _ = fs::remove_file(&invalid);
+}